@@ -1,2 +0,0 @@
--- Add up migration script here
-INSERT INTO answers(content, question_id) VALUES ('Answer  Content', 1);
@@ -0,0 +1,8 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS users
+(
+    id            serial PRIMARY KEY,
+    email         VARCHAR(255) NOT NULL UNIQUE,
+    password_hash TEXT         NOT NULL,
+    created_on    TIMESTAMPTZ  NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE comments DROP COLUMN author_id;
+ALTER TABLE answers DROP COLUMN author_id;
+ALTER TABLE questions DROP COLUMN author_id;
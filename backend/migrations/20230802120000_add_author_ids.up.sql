@@ -0,0 +1,4 @@
+-- Rows written before accounts existed keep a NULL author
+ALTER TABLE questions ADD COLUMN author_id integer REFERENCES users ON DELETE SET NULL;
+ALTER TABLE answers ADD COLUMN author_id integer REFERENCES users ON DELETE SET NULL;
+ALTER TABLE comments ADD COLUMN author_id integer REFERENCES users ON DELETE SET NULL;
@@ -0,0 +1,4 @@
+-- Promote the first admin by hand: UPDATE users SET role = 'admin' WHERE email = '...';
+ALTER TABLE users
+    ADD COLUMN role VARCHAR(16) NOT NULL DEFAULT 'user'
+        CHECK (role IN ('user', 'moderator', 'admin'));
@@ -0,0 +1,9 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS comments_search_vector_idx;
+ALTER TABLE comments DROP COLUMN IF EXISTS search_vector;
+
+DROP INDEX IF EXISTS answers_search_vector_idx;
+ALTER TABLE answers DROP COLUMN IF EXISTS search_vector;
+
+DROP INDEX IF EXISTS questions_search_vector_idx;
+ALTER TABLE questions DROP COLUMN IF EXISTS search_vector;
@@ -0,0 +1,19 @@
+-- Full text search for GET /search. Titles outrank bodies when ranking questions.
+ALTER TABLE questions
+    ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', coalesce(title, '')), 'A') ||
+        setweight(to_tsvector('english', coalesce(content, '')), 'B')
+    ) STORED;
+CREATE INDEX questions_search_vector_idx ON questions USING GIN (search_vector);
+
+ALTER TABLE answers
+    ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+        to_tsvector('english', coalesce(content, ''))
+    ) STORED;
+CREATE INDEX answers_search_vector_idx ON answers USING GIN (search_vector);
+
+ALTER TABLE comments
+    ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+        to_tsvector('english', coalesce(content, ''))
+    ) STORED;
+CREATE INDEX comments_search_vector_idx ON comments USING GIN (search_vector);
@@ -0,0 +1,16 @@
+-- One row per user per question or answer they voted on, a retracted vote is deleted
+CREATE TABLE IF NOT EXISTS votes
+(
+    id          serial PRIMARY KEY,
+    user_id     integer     NOT NULL REFERENCES users ON DELETE CASCADE,
+    question_id integer REFERENCES questions ON DELETE CASCADE,
+    answer_id   integer REFERENCES answers ON DELETE CASCADE,
+    value       smallint    NOT NULL CHECK (value IN (-1, 1)),
+    created_on  TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (num_nonnulls(question_id, answer_id) = 1),
+    UNIQUE (user_id, question_id),
+    UNIQUE (user_id, answer_id)
+);
+
+CREATE INDEX votes_question_id_idx ON votes (question_id);
+CREATE INDEX votes_answer_id_idx ON votes (answer_id);
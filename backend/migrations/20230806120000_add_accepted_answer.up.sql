@@ -0,0 +1,4 @@
+-- The answer the question's author marked as solving it. That the answer belongs to this
+-- question is checked by Store::accept_answer.
+ALTER TABLE questions
+    ADD COLUMN accepted_answer_id integer REFERENCES answers ON DELETE SET NULL;
@@ -0,0 +1,13 @@
+-- Add down migration script here
+ALTER TABLE questions ADD COLUMN tags TEXT[];
+
+UPDATE questions
+SET tags = (
+    SELECT array_agg(tags.slug ORDER BY tags.slug)
+    FROM question_tags
+    JOIN tags ON tags.id = question_tags.tag_id
+    WHERE question_tags.question_id = questions.id
+);
+
+DROP TABLE IF EXISTS question_tags;
+DROP TABLE IF EXISTS tags;
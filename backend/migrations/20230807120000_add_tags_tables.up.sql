@@ -0,0 +1,32 @@
+-- Tags get one canonical spelling: trimmed, lowercase, inner whitespace turned into '-'
+CREATE TABLE IF NOT EXISTS tags
+(
+    id         serial PRIMARY KEY,
+    slug       VARCHAR(64) NOT NULL UNIQUE CHECK (slug <> ''),
+    created_on TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS question_tags
+(
+    question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+    tag_id      integer NOT NULL REFERENCES tags ON DELETE CASCADE,
+    PRIMARY KEY (question_id, tag_id)
+);
+
+CREATE INDEX question_tags_tag_id_idx ON question_tags (tag_id);
+
+-- The old arrays had no length limit, longer tags are cut down to fit tags.slug.
+-- Tags that only differ past the cut end up as the same one.
+INSERT INTO tags (slug)
+SELECT DISTINCT left(regexp_replace(lower(btrim(tag)), '\s+', '-', 'g'), 64)
+FROM questions, unnest(questions.tags) AS tag
+WHERE btrim(tag) <> ''
+ON CONFLICT (slug) DO NOTHING;
+
+INSERT INTO question_tags (question_id, tag_id)
+SELECT DISTINCT questions.id, tags.id
+FROM questions, unnest(questions.tags) AS tag
+JOIN tags ON tags.slug = left(regexp_replace(lower(btrim(tag)), '\s+', '-', 'g'), 64)
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE questions DROP COLUMN tags;
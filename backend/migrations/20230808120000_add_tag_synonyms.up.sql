@@ -0,0 +1,22 @@
+-- Alternative spellings that resolve to a canonical tag whenever a question is saved
+CREATE TABLE IF NOT EXISTS tag_synonyms
+(
+    id         serial PRIMARY KEY,
+    synonym    VARCHAR(64) NOT NULL UNIQUE CHECK (synonym <> ''),
+    tag_id     integer     NOT NULL REFERENCES tags ON DELETE CASCADE,
+    created_by integer REFERENCES users ON DELETE SET NULL,
+    created_on TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX tag_synonyms_tag_id_idx ON tag_synonyms (tag_id);
+
+-- Audit trail for POST /tags/:slug/merge. The slugs are copied because the source tag is gone.
+CREATE TABLE IF NOT EXISTS tag_merges
+(
+    id             serial PRIMARY KEY,
+    source_slug    VARCHAR(64) NOT NULL,
+    target_slug    VARCHAR(64) NOT NULL,
+    question_count integer     NOT NULL,
+    merged_by      integer REFERENCES users ON DELETE SET NULL,
+    merged_on      TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
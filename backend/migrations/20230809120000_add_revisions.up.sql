@@ -0,0 +1,37 @@
+-- Every saved version of a question or answer, numbered from 1 per post. The newest revision
+-- matches the post itself, so rows are written on create, on edit and on rollback.
+CREATE TABLE IF NOT EXISTS question_revisions
+(
+    id          serial PRIMARY KEY,
+    question_id integer      NOT NULL REFERENCES questions ON DELETE CASCADE,
+    revision    integer      NOT NULL,
+    title       VARCHAR(255) NOT NULL,
+    content     TEXT         NOT NULL,
+    tags        TEXT[]       NOT NULL DEFAULT '{}',
+    editor_id   integer REFERENCES users ON DELETE SET NULL,
+    edited_on   TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    UNIQUE (question_id, revision)
+);
+
+CREATE TABLE IF NOT EXISTS answer_revisions
+(
+    id        serial PRIMARY KEY,
+    answer_id integer     NOT NULL REFERENCES answers ON DELETE CASCADE,
+    revision  integer     NOT NULL,
+    content   TEXT        NOT NULL,
+    editor_id integer REFERENCES users ON DELETE SET NULL,
+    edited_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (answer_id, revision)
+);
+
+-- Existing posts start their history at their current state
+INSERT INTO question_revisions (question_id, revision, title, content, tags, editor_id, edited_on)
+SELECT q.id, 1, q.title, q.content,
+       ARRAY(SELECT t.slug FROM question_tags qt JOIN tags t ON t.id = qt.tag_id
+             WHERE qt.question_id = q.id ORDER BY t.slug),
+       q.author_id, q.created_on
+FROM questions q;
+
+INSERT INTO answer_revisions (answer_id, revision, content, editor_id, edited_on)
+SELECT a.id, 1, a.content, a.author_id, a.created_on
+FROM answers a;
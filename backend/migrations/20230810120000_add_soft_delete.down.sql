@@ -0,0 +1,14 @@
+-- Add down migration script here
+ALTER TABLE comments
+    DROP CONSTRAINT comments_applied_to_question_id_fkey,
+    ADD CONSTRAINT comments_applied_to_question_id_fkey
+        FOREIGN KEY (applied_to_question_id) REFERENCES questions;
+
+ALTER TABLE comments
+    DROP CONSTRAINT comments_applied_to_answer_id_fkey,
+    ADD CONSTRAINT comments_applied_to_answer_id_fkey
+        FOREIGN KEY (applied_to_answer_id) REFERENCES answers;
+
+ALTER TABLE comments DROP COLUMN deleted_at;
+ALTER TABLE answers DROP COLUMN deleted_at;
+ALTER TABLE questions DROP COLUMN deleted_at;
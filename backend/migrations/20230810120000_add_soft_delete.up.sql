@@ -0,0 +1,21 @@
+-- Deleting a post only stamps deleted_at; Store hides stamped rows from every read and the
+-- purge task removes them for good once the retention period has passed. A question's
+-- answers and comments are stamped with the same instant so undelete can restore exactly them.
+ALTER TABLE questions ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE answers ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE comments ADD COLUMN deleted_at TIMESTAMPTZ;
+
+CREATE INDEX questions_deleted_at_idx ON questions (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX answers_deleted_at_idx ON answers (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX comments_deleted_at_idx ON comments (deleted_at) WHERE deleted_at IS NOT NULL;
+
+-- Purging a question or answer takes its comments with it
+ALTER TABLE comments
+    DROP CONSTRAINT comments_applied_to_question_id_fkey,
+    ADD CONSTRAINT comments_applied_to_question_id_fkey
+        FOREIGN KEY (applied_to_question_id) REFERENCES questions ON DELETE CASCADE;
+
+ALTER TABLE comments
+    DROP CONSTRAINT comments_applied_to_answer_id_fkey,
+    ADD CONSTRAINT comments_applied_to_answer_id_fkey
+        FOREIGN KEY (applied_to_answer_id) REFERENCES answers ON DELETE CASCADE;
@@ -0,0 +1,3 @@
+-- Bumped by every edit to a question and sent out as its ETag, so an editor working from a
+-- stale copy gets a 412 instead of silently overwriting someone else's change.
+ALTER TABLE questions ADD COLUMN version integer NOT NULL DEFAULT 1;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE comments DROP CONSTRAINT comments_one_target;
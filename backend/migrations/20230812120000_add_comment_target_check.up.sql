@@ -0,0 +1,10 @@
+-- A comment hangs off exactly one post. Rows naming both keep only the answer, which is how
+-- the thread loader already read them, and rows naming neither could never be reached.
+UPDATE comments SET applied_to_question_id = NULL
+WHERE applied_to_question_id IS NOT NULL AND applied_to_answer_id IS NOT NULL;
+
+DELETE FROM comments WHERE applied_to_question_id IS NULL AND applied_to_answer_id IS NULL;
+
+ALTER TABLE comments
+    ADD CONSTRAINT comments_one_target
+        CHECK (num_nonnulls(applied_to_question_id, applied_to_answer_id) = 1);
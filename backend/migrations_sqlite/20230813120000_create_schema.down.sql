@@ -0,0 +1,12 @@
+DROP TABLE answer_revisions;
+DROP TABLE question_revisions;
+DROP TABLE tag_merges;
+DROP TABLE tag_synonyms;
+DROP TABLE question_tags;
+DROP TABLE tags;
+DROP TABLE votes;
+DROP TABLE comments;
+UPDATE questions SET accepted_answer_id = NULL;
+DROP TABLE answers;
+DROP TABLE questions;
+DROP TABLE users;
@@ -0,0 +1,133 @@
+-- The Postgres schema as of migrations/20230812120000, in one go. The differences:
+--   * TIMESTAMPTZ columns are TEXT holding RFC 3339 UTC timestamps. SqliteStore always binds
+--     them from Rust so they all share one format and compare correctly as text.
+--   * TEXT[] columns are TEXT holding a JSON array of strings.
+--   * serial keys are INTEGER PRIMARY KEY AUTOINCREMENT, so ids are never reused.
+--   * There are no search vectors, SqliteStore::search matches terms in Rust.
+--   * num_nonnulls(a, b) = 1 is spelled (a IS NULL) <> (b IS NULL).
+CREATE TABLE users
+(
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    email         VARCHAR(255) NOT NULL UNIQUE,
+    password_hash TEXT         NOT NULL,
+    role          VARCHAR(16)  NOT NULL DEFAULT 'user'
+        CHECK (role IN ('user', 'moderator', 'admin')),
+    created_on    TEXT         NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+CREATE TABLE questions
+(
+    id                 INTEGER PRIMARY KEY AUTOINCREMENT,
+    title              VARCHAR(255) NOT NULL,
+    content            TEXT         NOT NULL,
+    author_id          INTEGER REFERENCES users ON DELETE SET NULL,
+    accepted_answer_id INTEGER REFERENCES answers ON DELETE SET NULL,
+    version            INTEGER      NOT NULL DEFAULT 1,
+    created_on         TEXT         NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    deleted_at         TEXT
+);
+
+CREATE TABLE answers
+(
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    content     TEXT    NOT NULL,
+    question_id INTEGER REFERENCES questions ON DELETE CASCADE,
+    author_id   INTEGER REFERENCES users ON DELETE SET NULL,
+    created_on  TEXT    NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    deleted_at  TEXT
+);
+
+-- created_on has no time zone here either, matching the Postgres TIMESTAMP column
+CREATE TABLE comments
+(
+    id                     INTEGER PRIMARY KEY AUTOINCREMENT,
+    content                TEXT    NOT NULL,
+    applied_to_question_id INTEGER REFERENCES questions ON DELETE CASCADE,
+    applied_to_answer_id   INTEGER REFERENCES answers ON DELETE CASCADE,
+    author_id              INTEGER REFERENCES users ON DELETE SET NULL,
+    created_on             TEXT    NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now')),
+    deleted_at             TEXT,
+    CONSTRAINT comments_one_target
+        CHECK ((applied_to_question_id IS NULL) <> (applied_to_answer_id IS NULL))
+);
+
+CREATE TABLE votes
+(
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id     INTEGER  NOT NULL REFERENCES users ON DELETE CASCADE,
+    question_id INTEGER REFERENCES questions ON DELETE CASCADE,
+    answer_id   INTEGER REFERENCES answers ON DELETE CASCADE,
+    value       SMALLINT NOT NULL CHECK (value IN (-1, 1)),
+    created_on  TEXT     NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    CHECK ((question_id IS NULL) <> (answer_id IS NULL)),
+    UNIQUE (user_id, question_id),
+    UNIQUE (user_id, answer_id)
+);
+
+CREATE INDEX votes_question_id_idx ON votes (question_id);
+CREATE INDEX votes_answer_id_idx ON votes (answer_id);
+
+CREATE TABLE tags
+(
+    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+    slug       VARCHAR(64) NOT NULL UNIQUE CHECK (slug <> ''),
+    created_on TEXT        NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+CREATE TABLE question_tags
+(
+    question_id INTEGER NOT NULL REFERENCES questions ON DELETE CASCADE,
+    tag_id      INTEGER NOT NULL REFERENCES tags ON DELETE CASCADE,
+    PRIMARY KEY (question_id, tag_id)
+);
+
+CREATE INDEX question_tags_tag_id_idx ON question_tags (tag_id);
+
+CREATE TABLE tag_synonyms
+(
+    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+    synonym    VARCHAR(64) NOT NULL UNIQUE CHECK (synonym <> ''),
+    tag_id     INTEGER     NOT NULL REFERENCES tags ON DELETE CASCADE,
+    created_by INTEGER REFERENCES users ON DELETE SET NULL,
+    created_on TEXT        NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+CREATE INDEX tag_synonyms_tag_id_idx ON tag_synonyms (tag_id);
+
+CREATE TABLE tag_merges
+(
+    id             INTEGER PRIMARY KEY AUTOINCREMENT,
+    source_slug    VARCHAR(64) NOT NULL,
+    target_slug    VARCHAR(64) NOT NULL,
+    question_count INTEGER     NOT NULL,
+    merged_by      INTEGER REFERENCES users ON DELETE SET NULL,
+    merged_on      TEXT        NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+CREATE TABLE question_revisions
+(
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    question_id INTEGER      NOT NULL REFERENCES questions ON DELETE CASCADE,
+    revision    INTEGER      NOT NULL,
+    title       VARCHAR(255) NOT NULL,
+    content     TEXT         NOT NULL,
+    tags        TEXT         NOT NULL DEFAULT '[]',
+    editor_id   INTEGER REFERENCES users ON DELETE SET NULL,
+    edited_on   TEXT         NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    UNIQUE (question_id, revision)
+);
+
+CREATE TABLE answer_revisions
+(
+    id        INTEGER PRIMARY KEY AUTOINCREMENT,
+    answer_id INTEGER NOT NULL REFERENCES answers ON DELETE CASCADE,
+    revision  INTEGER NOT NULL,
+    content   TEXT    NOT NULL,
+    editor_id INTEGER REFERENCES users ON DELETE SET NULL,
+    edited_on TEXT    NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    UNIQUE (answer_id, revision)
+);
+
+CREATE INDEX questions_deleted_at_idx ON questions (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX answers_deleted_at_idx ON answers (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX comments_deleted_at_idx ON comments (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,16 @@
+-- Demo rows for local development, loaded by `backend migrate seed` or at startup with
+-- SEED_DEV_DATA=true. Only ever applied to a database without any questions yet.
+INSERT INTO questions(title, content) VALUES ('Question Title', 'Question Content');
+
+INSERT INTO tags(slug) VALUES ('tag1'), ('tag2') ON CONFLICT (slug) DO NOTHING;
+INSERT INTO question_tags(question_id, tag_id)
+    SELECT questions.id, tags.id FROM questions, tags
+    WHERE questions.title = 'Question Title' AND tags.slug IN ('tag1', 'tag2');
+
+INSERT INTO answers(content, question_id)
+    SELECT 'Answer  Content', id FROM questions WHERE title = 'Question Title';
+
+INSERT INTO comments(content, applied_to_question_id)
+    SELECT 'comment to question 1', id FROM questions WHERE title = 'Question Title';
+INSERT INTO comments(content, applied_to_answer_id)
+    SELECT 'comment to answer 1', id FROM answers WHERE content = 'Answer  Content';
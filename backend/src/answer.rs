@@ -2,13 +2,39 @@ use crate::comment::CommentResult;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::error::FieldError;
 use crate::question::QuestionId;
+use crate::user::UserId;
+use crate::validation::{check_text, Validate, POST_RULES};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Answer {
     pub id: AnswerId,
     pub content: String,
     pub question_id: QuestionId,
+    pub author_id: Option<UserId>,
+    pub created_on: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AnswerDbResult {
+    pub id: i32,
+    pub content: String,
+    pub question_id: i32,
+    pub author_id: Option<i32>,
+    pub created_on: DateTime<Utc>,
+}
+
+impl From<AnswerDbResult> for Answer {
+    fn from(value: AnswerDbResult) -> Self {
+        Answer {
+            id: AnswerId(value.id),
+            content: value.content,
+            question_id: QuestionId(value.question_id),
+            author_id: value.author_id.map(UserId),
+            created_on: value.created_on,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -18,7 +44,9 @@ pub struct AnswerId(pub i32);
 pub struct AnswerResult {
     pub id: i32,
     pub content: String,
+    pub author_id: Option<i32>,
     pub created_on: DateTime<Utc>,
+    pub score: i64,
     pub comments: Vec<CommentResult>,
 }
 
@@ -33,3 +61,24 @@ pub struct CreateAnswer {
     pub content: String,
     pub question_id: i32,
 }
+
+impl Validate for CreateAnswer {
+    fn field_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_text("content", &self.content, POST_RULES, &mut errors);
+        errors
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateAnswer {
+    pub content: String,
+}
+
+impl Validate for UpdateAnswer {
+    fn field_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_text("content", &self.content, POST_RULES, &mut errors);
+        errors
+    }
+}
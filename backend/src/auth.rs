@@ -0,0 +1,174 @@
+use std::sync::OnceLock;
+
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use chrono::{Duration, Utc};
+use http::header::AUTHORIZATION;
+use http::request::Parts;
+use http::HeaderMap;
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::{AppError, AuthError};
+use crate::user::{Role, User, UserId};
+
+const TOKEN_LIFETIME_HOURS: i64 = 24;
+
+// https://github.com/tokio-rs/axum/blob/main/examples/jwt
+pub struct Keys {
+    pub encoding: EncodingKey,
+    pub decoding: DecodingKey,
+}
+
+impl Keys {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+        }
+    }
+}
+
+static KEYS: OnceLock<Keys> = OnceLock::new();
+
+/// Signs and checks tokens with `secret` from now on. Only the first call has any effect.
+pub fn init_keys(secret: &str) {
+    KEYS.get_or_init(|| Keys::new(secret.as_bytes()));
+}
+
+pub fn keys() -> &'static Keys {
+    KEYS.get()
+        .expect("auth::init_keys has to be called before tokens are issued or checked")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: UserId,
+    pub email: String,
+    pub role: Role,
+    pub exp: usize,
+}
+
+impl Claims {
+    pub fn new(user: &User) -> Self {
+        let exp = (Utc::now() + Duration::hours(TOKEN_LIFETIME_HOURS)).timestamp() as usize;
+
+        Claims {
+            sub: user.id,
+            email: user.email.clone(),
+            role: user.role,
+            exp,
+        }
+    }
+}
+
+// What /login hands back to the client
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthBody {
+    pub access_token: String,
+    pub token_type: String,
+}
+
+impl AuthBody {
+    pub fn new(access_token: String) -> Self {
+        Self {
+            access_token,
+            token_type: "Bearer".to_string(),
+        }
+    }
+}
+
+/// bcrypt is slow on purpose, so it runs on the blocking pool rather than stalling the executor
+pub async fn hash_password(password: String) -> Result<String, AppError> {
+    tokio::task::spawn_blocking(move || bcrypt::hash(password, bcrypt::DEFAULT_COST))
+        .await
+        .map_err(|err| AppError::Any(err.into()))?
+        .map_err(|err| AppError::Any(err.into()))
+}
+
+pub async fn verify_password(password: String, password_hash: String) -> Result<bool, AppError> {
+    tokio::task::spawn_blocking(move || bcrypt::verify(password, &password_hash))
+        .await
+        .map_err(|err| AppError::Any(err.into()))?
+        .map_err(|err| AppError::Any(err.into()))
+}
+
+pub fn issue_token(claims: &Claims) -> Result<AuthBody, AppError> {
+    let token = jsonwebtoken::encode(&Header::default(), claims, &keys().encoding)
+        .map_err(|err| AppError::Any(err.into()))?;
+
+    Ok(AuthBody::new(token))
+}
+
+/// The caller behind a valid `Authorization: Bearer <jwt>` header.
+/// Taking this as a handler argument makes the route reject anonymous callers with a 401.
+/// On routes behind `layers::enforce_policy` the role is the one currently stored for the
+/// user, not the one their token was issued with.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub id: UserId,
+    pub email: String,
+    pub role: Role,
+}
+
+impl AuthUser {
+    /// Whether this user may edit or delete a post written by `author_id`
+    pub fn can_modify(&self, author_id: Option<UserId>) -> bool {
+        author_id == Some(self.id) || self.role.is_staff()
+    }
+}
+
+impl From<User> for AuthUser {
+    fn from(user: User) -> Self {
+        AuthUser {
+            id: user.id,
+            email: user.email,
+            role: user.role,
+        }
+    }
+}
+
+impl From<Claims> for AuthUser {
+    fn from(claims: Claims) -> Self {
+        AuthUser {
+            id: claims.sub,
+            email: claims.email,
+            role: claims.role,
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // layers::enforce_policy has already done the work for us on protected routes
+        if let Some(user) = parts.extensions.get::<AuthUser>() {
+            return Ok(user.clone());
+        }
+
+        let token =
+            bearer_token(&parts.headers).ok_or(AppError::Unauthorized(AuthError::MissingToken))?;
+
+        let token_data =
+            jsonwebtoken::decode::<Claims>(token, &keys().decoding, &Validation::default())
+                .map_err(|_| AppError::Unauthorized(AuthError::InvalidToken))?;
+
+        Ok(token_data.claims.into())
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+
+    if scheme.eq_ignore_ascii_case("Bearer") && !token.trim().is_empty() {
+        Some(token.trim())
+    } else {
+        None
+    }
+}
@@ -1,5 +1,8 @@
 use crate::answer::*;
+use crate::error::FieldError;
 use crate::question::*;
+use crate::user::UserId;
+use crate::validation::{check_text, Validate, COMMENT_RULES};
 use chrono::NaiveDateTime;
 use derive_more::Display;
 use serde_derive::{Deserialize, Serialize};
@@ -7,47 +10,99 @@ use serde_derive::{Deserialize, Serialize};
 // This uses the `derive_more` crate to reduce the Display boilerplate (see below)
 #[derive(Clone, Debug, Display, Serialize, Deserialize)]
 #[display(
-    fmt = "id: {}, content: {}, applied_to_question_id: {:?}, applied_to_answer_id: {:?}",
+    fmt = "id: {}, content: {}, applied_to_question_id: {:?}, applied_to_answer_id: {:?}, author_id: {:?}",
     id,
     content,
     applied_to_question_id,
-    applied_to_answer_id
+    applied_to_answer_id,
+    author_id
 )]
 pub struct Comment {
     pub id: CommentId,
     pub content: String,
     pub applied_to_question_id: Option<QuestionId>,
     pub applied_to_answer_id: Option<AnswerId>,
+    pub author_id: Option<UserId>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CommentDbResult {
-    pub id: i32,
+    pub id: CommentId,
     pub content: String,
     pub applied_to_question_id: Option<i32>,
     pub applied_to_answer_id: Option<i32>,
+    pub author_id: Option<i32>,
+    pub created_on: NaiveDateTime,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CommentResult {
     pub id: i32,
     pub content: String,
+    pub author_id: Option<i32>,
     pub created_on: NaiveDateTime,
 }
 
-#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct CommentId(pub u32);
+// Matches the `serial` (i32) primary key of the comments table
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    sqlx::Type,
+    Display,
+    derive_more::Deref,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub struct CommentId(pub i32);
 
-impl From<u32> for CommentId {
-    fn from(value: u32) -> Self {
+impl From<i32> for CommentId {
+    fn from(value: i32) -> Self {
         Self(value)
     }
 }
 
+impl From<CommentId> for i32 {
+    fn from(value: CommentId) -> Self {
+        value.0
+    }
+}
+
+/// The post a comment hangs off, sent as `{ "question": 5 }` or `{ "answer": 7 }`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentTarget {
+    Question(QuestionId),
+    Answer(AnswerId),
+}
+
 // Clients use this to create new requests
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CreateComment {
     pub content: String,
-    pub applied_to_question_id: QuestionId,
-    pub applied_to_answer_id: AnswerId,
+    pub target: CommentTarget,
+}
+
+impl Validate for CreateComment {
+    fn field_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_text("content", &self.content, COMMENT_RULES, &mut errors);
+        errors
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateComment {
+    pub content: String,
+}
+
+impl Validate for UpdateComment {
+    fn field_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_text("content", &self.content, COMMENT_RULES, &mut errors);
+        errors
+    }
 }
@@ -0,0 +1,586 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use http::HeaderValue;
+use serde_derive::Deserialize;
+use tower_http::cors::AllowOrigin;
+use tracing_subscriber::EnvFilter;
+
+/// Read when it exists and CONFIG_FILE doesn't name another file
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Everything the server is started with. Every key of the TOML file is optional, see
+/// config.example.toml, and the environment variables listed there override them.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub auth: AuthConfig,
+    pub cors: CorsConfig,
+    pub log: LogConfig,
+    pub features: FeatureConfig,
+    pub purge: PurgeConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: IpAddr,
+    pub port: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Storage {
+    Postgres,
+    /// Needs a build with the sqlite feature
+    Sqlite,
+    /// Nothing is kept once the server stops
+    Memory,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub storage: Storage,
+    /// Required for Postgres storage
+    pub url: String,
+    /// Kept apart from `url` because DATABASE_URL is what the Postgres queries are checked
+    /// against at build time
+    pub sqlite_url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// How long a request waits for a free connection before it gets a 503
+    pub acquire_timeout_secs: u64,
+    /// Connections unused for this long are closed, 0 keeps them open
+    pub idle_timeout_secs: u64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Signs the login tokens, required
+    pub jwt_secret: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins such as `https://example.com` allowed to call the API from a browser,
+    /// or just `*` for any origin
+    pub allowed_origins: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    /// Which events are logged, in `tracing_subscriber::EnvFilter` syntax
+    pub filter: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureConfig {
+    /// Apply pending Postgres migrations at startup
+    pub migrate_on_start: bool,
+    /// Load the demo rows in seeds/dev.sql into an empty Postgres database at startup
+    pub seed_dev_data: bool,
+    /// Remove soft deleted posts for good once they are past `purge.retention_days`
+    pub purge_deleted: bool,
+}
+
+/// A century, far past any sensible retention but well inside what dates can hold
+pub const MAX_RETENTION_DAYS: i64 = 36_500;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PurgeConfig {
+    /// At most `MAX_RETENTION_DAYS`
+    pub retention_days: i64,
+    pub interval_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 3000,
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            storage: Storage::Postgres,
+            url: String::new(),
+            sqlite_url: "sqlite://backend.db".to_string(),
+            max_connections: 5,
+            min_connections: 0,
+            acquire_timeout_secs: 30,
+            idle_timeout_secs: 600,
+        }
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            allowed_origins: vec!["*".to_string()],
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            format: LogFormat::Text,
+            // axum logs rejections from built-in extractors with the `axum::rejection`
+            // target, at `TRACE` level. `axum::rejection=trace` enables showing those events
+            filter: "backend=trace,tower_http=debug,axum::rejection=trace".to_string(),
+        }
+    }
+}
+
+impl Default for FeatureConfig {
+    fn default() -> Self {
+        FeatureConfig {
+            migrate_on_start: true,
+            seed_dev_data: false,
+            purge_deleted: true,
+        }
+    }
+}
+
+impl Default for PurgeConfig {
+    fn default() -> Self {
+        PurgeConfig {
+            retention_days: 30,
+            interval_secs: 3600,
+        }
+    }
+}
+
+impl FromStr for Storage {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "postgres" => Ok(Storage::Postgres),
+            "sqlite" => Ok(Storage::Sqlite),
+            "memory" => Ok(Storage::Memory),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Why the server refused to start with the configuration it was given
+#[derive(Debug)]
+pub enum ConfigError {
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    /// Every problem found, not just the first
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, source } => {
+                write!(f, "Could not read {}: {}", path.display(), source)
+            }
+            ConfigError::Parse { path, source } => {
+                write!(
+                    f,
+                    "{} is not a valid config file: {}",
+                    path.display(),
+                    source
+                )
+            }
+            ConfigError::Invalid(problems) => {
+                write!(f, "Invalid configuration:")?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Reads the file named by CONFIG_FILE, or config.toml if there is one, then applies the
+    /// environment overrides and validates the result
+    pub fn load() -> Result<Config, ConfigError> {
+        let config = match std::env::var_os("CONFIG_FILE") {
+            // A file named explicitly has to be there
+            Some(path) => Config::from_file(Path::new(&path))?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Config::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => Config::default(),
+        };
+
+        config.with_overrides(|name| std::env::var(name).ok())
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_owned(),
+            source,
+        })?;
+
+        toml::from_str(&text).map_err(|source| ConfigError::Parse {
+            path: path.to_owned(),
+            source,
+        })
+    }
+
+    /// Overrides settings with the variables `lookup` finds, then validates the result.
+    /// The variable names predate the config file, so existing .env files keep working.
+    pub fn with_overrides(
+        mut self,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<Config, ConfigError> {
+        let mut overrides = Overrides {
+            lookup,
+            problems: Vec::new(),
+        };
+
+        overrides.set("API_HOST", "an IP address", &mut self.server.host);
+        overrides.set("API_PORT", "a port number", &mut self.server.port);
+
+        let database = &mut self.database;
+        overrides.set(
+            "STORAGE",
+            "postgres, sqlite or memory",
+            &mut database.storage,
+        );
+        overrides.set("DATABASE_URL", "a URL", &mut database.url);
+        overrides.set("SQLITE_URL", "a URL", &mut database.sqlite_url);
+        overrides.set(
+            "DB_MAX_CONNECTIONS",
+            "a number",
+            &mut database.max_connections,
+        );
+        overrides.set(
+            "DB_MIN_CONNECTIONS",
+            "a number",
+            &mut database.min_connections,
+        );
+        overrides.set(
+            "DB_ACQUIRE_TIMEOUT_SECS",
+            "a number of seconds",
+            &mut database.acquire_timeout_secs,
+        );
+        overrides.set(
+            "DB_IDLE_TIMEOUT_SECS",
+            "a number of seconds",
+            &mut database.idle_timeout_secs,
+        );
+
+        overrides.set("JWT_SECRET", "a string", &mut self.auth.jwt_secret);
+
+        if let Some(origins) = (overrides.lookup)("CORS_ALLOWED_ORIGINS") {
+            self.cors.allowed_origins = origins
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(String::from)
+                .collect();
+        }
+
+        overrides.set("LOG_FORMAT", "text or json", &mut self.log.format);
+        overrides.set("RUST_LOG", "a log filter", &mut self.log.filter);
+
+        let features = &mut self.features;
+        overrides.set(
+            "MIGRATE_ON_START",
+            "true or false",
+            &mut features.migrate_on_start,
+        );
+        overrides.set(
+            "SEED_DEV_DATA",
+            "true or false",
+            &mut features.seed_dev_data,
+        );
+        overrides.set(
+            "PURGE_DELETED",
+            "true or false",
+            &mut features.purge_deleted,
+        );
+
+        overrides.set(
+            "PURGE_RETENTION_DAYS",
+            "a number of days",
+            &mut self.purge.retention_days,
+        );
+        overrides.set(
+            "PURGE_INTERVAL_SECS",
+            "a number of seconds",
+            &mut self.purge.interval_secs,
+        );
+
+        let mut problems = overrides.problems;
+        self.validate(&mut problems);
+
+        if problems.is_empty() {
+            Ok(self)
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        let database = &self.database;
+        if database.storage == Storage::Postgres && database.url.is_empty() {
+            problems.push(
+                "database.url (DATABASE_URL) is required when database.storage is postgres"
+                    .to_string(),
+            );
+        }
+        if database.storage == Storage::Sqlite && !cfg!(feature = "sqlite") {
+            problems.push(
+                "database.storage (STORAGE) is sqlite but this build has no sqlite feature"
+                    .to_string(),
+            );
+        }
+        if database.max_connections == 0 {
+            problems.push(
+                "database.max_connections (DB_MAX_CONNECTIONS) must be at least 1".to_string(),
+            );
+        }
+        if database.min_connections > database.max_connections {
+            problems.push(
+                "database.min_connections (DB_MIN_CONNECTIONS) can't be more than \
+                 database.max_connections"
+                    .to_string(),
+            );
+        }
+        if database.acquire_timeout_secs == 0 {
+            problems.push(
+                "database.acquire_timeout_secs (DB_ACQUIRE_TIMEOUT_SECS) must be at least 1"
+                    .to_string(),
+            );
+        }
+
+        if let Err(problem) = self.cors.allow_origin() {
+            problems.push(problem);
+        }
+
+        if let Err(err) = EnvFilter::try_new(&self.log.filter) {
+            problems.push(format!(
+                "log.filter (RUST_LOG) is not a valid filter: {err}"
+            ));
+        }
+
+        if !(0..=MAX_RETENTION_DAYS).contains(&self.purge.retention_days) {
+            problems.push(format!(
+                "purge.retention_days (PURGE_RETENTION_DAYS) must be between 0 and {MAX_RETENTION_DAYS}"
+            ));
+        }
+        if self.purge.interval_secs == 0 {
+            problems
+                .push("purge.interval_secs (PURGE_INTERVAL_SECS) must be at least 1".to_string());
+        }
+    }
+
+    /// What only serving the API needs, so `backend migrate` works without a JWT secret
+    pub fn check_serving(&self) -> Result<(), ConfigError> {
+        if self.auth.jwt_secret.is_empty() {
+            return Err(ConfigError::Invalid(vec![
+                "auth.jwt_secret (JWT_SECRET) is required".to_string(),
+            ]));
+        }
+
+        Ok(())
+    }
+}
+
+impl ServerConfig {
+    pub fn bind_address(&self) -> SocketAddr {
+        SocketAddr::new(self.host, self.port)
+    }
+}
+
+impl DatabaseConfig {
+    pub fn acquire_timeout(&self) -> Duration {
+        Duration::from_secs(self.acquire_timeout_secs)
+    }
+
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        Some(Duration::from_secs(self.idle_timeout_secs)).filter(|timeout| !timeout.is_zero())
+    }
+}
+
+impl CorsConfig {
+    /// The origins as the CORS layer takes them, or what is wrong with them
+    pub fn allow_origin(&self) -> Result<AllowOrigin, String> {
+        if self.allowed_origins.iter().any(|origin| origin == "*") {
+            if self.allowed_origins.len() > 1 {
+                return Err(
+                    "cors.allowed_origins (CORS_ALLOWED_ORIGINS) can't mix * with other origins"
+                        .to_string(),
+                );
+            }
+            return Ok(AllowOrigin::any());
+        }
+
+        let mut origins = Vec::new();
+        for origin in &self.allowed_origins {
+            // Browsers send a bare scheme and host, so a path or trailing slash never matches
+            let host = origin
+                .strip_prefix("https://")
+                .or_else(|| origin.strip_prefix("http://"));
+            let is_origin = host.is_some_and(|host| !host.is_empty() && !host.contains('/'));
+            match HeaderValue::from_str(origin) {
+                Ok(value) if is_origin => origins.push(value),
+                _ => {
+                    return Err(format!(
+                        "cors.allowed_origins (CORS_ALLOWED_ORIGINS) has {origin:?}, \
+                         expected an origin like https://example.com"
+                    ))
+                }
+            }
+        }
+
+        Ok(AllowOrigin::list(origins))
+    }
+}
+
+/// Applies environment overrides, collecting every value that doesn't parse
+struct Overrides<L> {
+    lookup: L,
+    problems: Vec<String>,
+}
+
+impl<L: Fn(&str) -> Option<String>> Overrides<L> {
+    fn set<T: FromStr>(&mut self, name: &str, expected: &str, field: &mut T) {
+        if let Some(value) = (self.lookup)(name) {
+            match value.parse() {
+                Ok(parsed) => *field = parsed,
+                Err(_) => self
+                    .problems
+                    .push(format!("{name}={value:?} should be {expected}")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn with_env(config: Config, vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        config.with_overrides(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn env_overrides_the_file() {
+        let config: Config = toml::from_str(
+            r#"
+            [server]
+            port = 8080
+
+            [database]
+            url = "postgres://file@localhost/qa"
+            max_connections = 10
+
+            [auth]
+            jwt_secret = "from-file"
+            "#,
+        )
+        .unwrap();
+
+        let config = with_env(config, &[("API_PORT", "9090"), ("JWT_SECRET", "from-env")]).unwrap();
+        assert_eq!(
+            config.server.bind_address(),
+            "127.0.0.1:9090".parse().unwrap()
+        );
+        assert_eq!(config.database.max_connections, 10);
+        assert_eq!(config.auth.jwt_secret, "from-env");
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let err = with_env(
+            Config::default(),
+            &[
+                ("API_PORT", "eighty"),
+                ("DB_MAX_CONNECTIONS", "0"),
+                ("CORS_ALLOWED_ORIGINS", "example.com"),
+                ("PURGE_RETENTION_DAYS", "999999999999999"),
+            ],
+        )
+        .unwrap_err();
+
+        let ConfigError::Invalid(problems) = err else {
+            panic!("expected validation problems, got {err}");
+        };
+        let problems = problems.join("\n");
+        for expected in [
+            "API_PORT",
+            "DATABASE_URL",
+            "DB_MAX_CONNECTIONS",
+            "CORS_ALLOWED_ORIGINS",
+            "PURGE_RETENTION_DAYS",
+        ] {
+            assert!(problems.contains(expected), "{expected} in {problems}");
+        }
+    }
+
+    #[test]
+    fn jwt_secret_is_only_needed_to_serve() {
+        let config = with_env(
+            Config::default(),
+            &[("DATABASE_URL", "postgres://localhost/qa")],
+        )
+        .unwrap();
+        assert!(config.check_serving().is_err());
+
+        let config = with_env(config, &[("JWT_SECRET", "secret")]).unwrap();
+        assert!(config.check_serving().is_ok());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<Config>("[server]\nhots = \"0.0.0.0\"").is_err());
+    }
+}
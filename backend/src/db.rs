@@ -1,38 +1,53 @@
-use axum::Json;
-use std::sync::{Arc, Mutex, RwLock};
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use std::collections::HashMap;
 
 use sqlx::postgres::PgPoolOptions;
-use sqlx::PgPool;
+use sqlx::{Executor, PgPool, Postgres, QueryBuilder, Transaction};
 use tracing::info;
 
-use crate::answer::{Answer, AnswerId, AnswerResult};
-use crate::comment::{CommentDbResult, CommentResult};
-use crate::error::AppError;
-use crate::question::{IntoQuestionId, Question, QuestionId, QuestionResult, UpdateQuestion};
+use crate::answer::{Answer, AnswerDbResult, AnswerId, AnswerResult};
+use crate::auth::AuthUser;
+use crate::comment::{CommentDbResult, CommentId, CommentResult, CommentTarget};
+use crate::config::DatabaseConfig;
+use crate::error::{
+    AnswerError, AppError, CommentError, FieldError, Forbidden, QuestionError, RevisionError,
+    TagError, UserError,
+};
+use crate::etag::EtagCondition;
+use crate::pagination::{Cursor, Page, PageParams};
+use crate::purge::PurgeCounts;
+use crate::question::{
+    IntoQuestionId, Question, QuestionDbResult, QuestionFilter, QuestionId, QuestionResult,
+    QuestionSort, TagMatch, UpdateQuestion,
+};
+use crate::repository::Repository;
+use crate::revision::{AnswerRevision, QuestionRevision, RevisionDiff};
+use crate::search::{SearchDbResult, SearchResult, HEADLINE_MARKERS, HEADLINE_OPTIONS};
+use crate::tag::{normalize_tags, slugify, MergeTag, Tag, TagMerge};
+use crate::user::{Role, User, UserDbResult, UserId};
+use crate::vote::{VoteDirection, VoteSummary, VoteTarget};
 
+/// The Postgres backed `Repository`
 #[derive(Clone)]
 pub struct Store {
     pub conn_pool: PgPool,
-    pub questions: Arc<Mutex<Vec<Question>>>,
-    pub answers: Arc<RwLock<Vec<Answer>>>,
 }
 
-pub async fn new_pool() -> PgPool {
-    let db_url = std::env::var("DATABASE_URL").unwrap();
+pub async fn new_pool(config: &DatabaseConfig) -> Result<PgPool, sqlx::Error> {
     PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&db_url)
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .acquire_timeout(config.acquire_timeout())
+        .idle_timeout(config.idle_timeout())
+        .connect(&config.url)
         .await
-        .unwrap()
 }
 
 impl Store {
     pub fn with_pool(pool: PgPool) -> Self {
-        Self {
-            conn_pool: pool,
-            questions: Default::default(),
-            answers: Default::default(),
-        }
+        Self { conn_pool: pool }
     }
 
     pub async fn test_database(&self) -> Result<(), sqlx::Error> {
@@ -47,161 +62,442 @@ impl Store {
         Ok(())
     }
 
-    pub async fn add_answer(
+    /// Runs `f` in a single transaction that is committed when it returns Ok and rolled back
+    /// when it returns an error, so writes spanning several tables land together or not at all.
+    ///
+    /// ```ignore
+    /// store
+    ///     .transaction(move |tx| {
+    ///         Box::pin(async move {
+    ///             sqlx::query("DELETE FROM votes").execute(&mut *tx).await?;
+    ///             Ok(())
+    ///         })
+    ///     })
+    ///     .await?;
+    /// ```
+    pub async fn transaction<T, F>(&self, f: F) -> Result<T, AppError>
+    where
+        T: Send,
+        F: for<'c> FnOnce(
+                &'c mut Transaction<'static, Postgres>,
+            ) -> BoxFuture<'c, Result<T, AppError>>
+            + Send,
+    {
+        let mut tx = self.conn_pool.begin().await?;
+        // On an error the transaction is dropped, which rolls it back
+        let value = f(&mut tx).await?;
+        tx.commit().await?;
+
+        Ok(value)
+    }
+
+    async fn vote_summary(
+        &mut self,
+        target: VoteTarget,
+        user_id: UserId,
+    ) -> Result<VoteSummary, AppError> {
+        let (score, vote) = match target {
+            VoteTarget::Question(question_id) => {
+                let row = sqlx::query!(
+                    r#"
+    SELECT COALESCE(SUM(value), 0) as "score!",
+           MAX(value) FILTER (WHERE user_id = $2) as vote
+    FROM votes WHERE question_id = $1
+    "#,
+                    question_id.0,
+                    user_id.0,
+                )
+                .fetch_one(&self.conn_pool)
+                .await?;
+                (row.score, row.vote)
+            }
+            VoteTarget::Answer(answer_id) => {
+                let row = sqlx::query!(
+                    r#"
+    SELECT COALESCE(SUM(value), 0) as "score!",
+           MAX(value) FILTER (WHERE user_id = $2) as vote
+    FROM votes WHERE answer_id = $1
+    "#,
+                    answer_id.0,
+                    user_id.0,
+                )
+                .fetch_one(&self.conn_pool)
+                .await?;
+                (row.score, row.vote)
+            }
+        };
+
+        Ok(VoteSummary {
+            score,
+            vote: vote.and_then(VoteDirection::from_value),
+        })
+    }
+}
+
+#[async_trait]
+impl Repository for Store {
+    async fn add_answer(
         &mut self,
         content: String,
         question_id: i32,
+        author_id: UserId,
     ) -> Result<Answer, AppError> {
-        let res = sqlx::query!(
+        // Deleted questions take no new answers
+        self.get_question_by_id(question_id).await?;
+
+        let res = self
+            .transaction(move |tx| {
+                Box::pin(async move {
+                    let res = sqlx::query_as!(
+                        AnswerDbResult,
+                        r#"
+    INSERT INTO answers (content, question_id, author_id)
+    VALUES ($1, $2, $3)
+    RETURNING id, content, question_id as "question_id!", author_id, created_on
+    "#,
+                        content,
+                        question_id,
+                        author_id.0,
+                    )
+                    .fetch_one(&mut *tx)
+                    .await?;
+
+                    record_answer_revision(tx, res.id, Some(author_id)).await?;
+                    Ok(res)
+                })
+            })
+            .await?;
+
+        Ok(res.into())
+    }
+
+    async fn get_answers_for_question(
+        &mut self,
+        question_id: QuestionId,
+    ) -> Result<Vec<Answer>, AppError> {
+        // 404 for an unknown question rather than an empty list
+        self.get_question_by_id(question_id).await?;
+
+        let rows = sqlx::query_as!(
+            AnswerDbResult,
             r#"
-    INSERT INTO answers (content, question_id)
-    VALUES ($1, $2)
-    RETURNING *
+    SELECT id, content, question_id as "question_id!", author_id, created_on
+    FROM answers
+    WHERE question_id = $1 AND deleted_at IS NULL
+    ORDER BY created_on, id
     "#,
-            content,
-            question_id,
+            question_id.0,
         )
-        .fetch_one(&self.conn_pool)
+        .fetch_all(&self.conn_pool)
         .await?;
 
-        let answer = Answer {
-            id: AnswerId(res.id),
-            content: res.content,
-            question_id: QuestionId(res.question_id.unwrap()),
-        };
-
-        Ok(answer)
+        Ok(rows.into_iter().map(Answer::from).collect())
     }
 
-    pub async fn get_all_questions(&mut self) -> Result<Vec<Question>, AppError> {
-        let rows = sqlx::query!(
+    async fn get_answer_by_id(&mut self, answer_id: AnswerId) -> Result<Answer, AppError> {
+        let row = sqlx::query_as!(
+            AnswerDbResult,
             r#"
-SELECT * FROM questions
-"#
+    SELECT id, content, question_id as "question_id!", author_id, created_on
+    FROM answers
+    WHERE id = $1 AND deleted_at IS NULL
+    "#,
+            answer_id.0,
         )
-        .fetch_all(&self.conn_pool)
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::Answer(AnswerError::InvalidId))?;
+
+        Ok(row.into())
+    }
+
+    async fn update_answer(
+        &mut self,
+        answer_id: AnswerId,
+        content: String,
+        user: &AuthUser,
+    ) -> Result<Answer, AppError> {
+        let existing = self.get_answer_by_id(answer_id).await?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let editor_id = user.id;
+        let row = self
+            .transaction(move |tx| {
+                Box::pin(async move { apply_answer_edit(tx, answer_id, &content, editor_id).await })
+            })
+            .await?;
+
+        Ok(row.into())
+    }
+
+    async fn delete_answer(
+        &mut self,
+        answer_id: AnswerId,
+        user: &AuthUser,
+    ) -> Result<(), AppError> {
+        let existing = self.get_answer_by_id(answer_id).await?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        self.transaction(move |tx| {
+            Box::pin(async move {
+                sqlx::query!(
+                    r#"
+    UPDATE answers SET deleted_at = NOW() WHERE id = $1
+    "#,
+                    answer_id.0,
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                sqlx::query!(
+                    r#"
+    UPDATE comments SET deleted_at = NOW()
+    WHERE applied_to_answer_id = $1 AND deleted_at IS NULL
+    "#,
+                    answer_id.0,
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                // A deleted answer can't stay the accepted one
+                sqlx::query!(
+                    r#"
+    UPDATE questions SET accepted_answer_id = NULL WHERE accepted_answer_id = $1
+    "#,
+                    answer_id.0,
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                Ok(())
+            })
+        })
         .await?;
 
-        let questions: Vec<_> = rows
+        Ok(())
+    }
+
+    /// One page of questions, filtered and sorted as asked for. Pages are addressed either by
+    /// `offset` or, for the plain date orderings, by the opaque keyset cursor of the last row
+    /// of the previous page, which stays fast however deep the client pages.
+    async fn get_all_questions(
+        &mut self,
+        params: &PageParams,
+        filter: &QuestionFilter,
+    ) -> Result<Page<Question>, AppError> {
+        let cursor = match &params.cursor {
+            Some(cursor) => Some(Cursor::decode(cursor).ok_or_else(|| {
+                AppError::Validation(vec![FieldError::new("cursor", "is not a valid cursor")])
+            })?),
+            None => None,
+        };
+        let limit = params.limit();
+        // Keyset pages start right after the cursor, so the offset only applies without one
+        let offset = if cursor.is_some() { 0 } else { params.offset() };
+
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT q.id, q.title, q.content, q.author_id, q.created_on, \
+             ARRAY(SELECT t.slug FROM question_tags qt JOIN tags t ON t.id = qt.tag_id \
+                   WHERE qt.question_id = q.id ORDER BY t.slug) AS tags, \
+             COALESCE((SELECT SUM(v.value) FROM votes v WHERE v.question_id = q.id), 0) AS score, \
+             q.version \
+             FROM questions q WHERE q.deleted_at IS NULL",
+        );
+        push_question_filters(&mut query, filter);
+
+        if let Some(cursor) = cursor {
+            query.push(if filter.sort == QuestionSort::Oldest {
+                " AND (q.created_on, q.id) > ("
+            } else {
+                " AND (q.created_on, q.id) < ("
+            });
+            query.push_bind(cursor.created_on);
+            query.push(", ");
+            query.push_bind(cursor.id);
+            query.push(")");
+        }
+
+        query.push(match filter.sort {
+            QuestionSort::Newest => " ORDER BY q.created_on DESC, q.id DESC",
+            QuestionSort::Oldest => " ORDER BY q.created_on ASC, q.id ASC",
+            QuestionSort::MostAnswers => {
+                " ORDER BY (SELECT COUNT(*) FROM answers a \
+                  WHERE a.question_id = q.id AND a.deleted_at IS NULL) DESC, \
+                 q.created_on DESC, q.id DESC"
+            }
+            QuestionSort::Votes => " ORDER BY score DESC, q.created_on DESC, q.id DESC",
+            QuestionSort::Unanswered => {
+                " ORDER BY EXISTS (SELECT 1 FROM answers a WHERE a.question_id = q.id AND a.deleted_at IS NULL), \
+                 q.created_on DESC, q.id DESC"
+            }
+        });
+
+        // Fetch one extra row to find out whether there is a next page
+        query.push(" OFFSET ");
+        query.push_bind(offset);
+        query.push(" LIMIT ");
+        query.push_bind(limit + 1);
+
+        let mut questions: Vec<Question> = query
+            .build_query_as::<QuestionDbResult>()
+            .fetch_all(&self.conn_pool)
+            .await?
             .into_iter()
-            .map(|row| {
-                Question {
-                    id: row.id.into(), // Assuming you have a From<u32> for QuestionId
-                    title: row.title,
-                    content: row.content,
-                    tags: row.tags,
-                }
-            })
+            .map(Question::from)
             .collect();
 
-        Ok(questions)
+        let has_more = questions.len() as i64 > limit;
+        questions.truncate(limit as usize);
+
+        let next_cursor = match questions.last() {
+            Some(last) if has_more && filter.sort.supports_cursor() => Some(
+                Cursor {
+                    created_on: last.created_on,
+                    id: last.id.0,
+                }
+                .encode(),
+            ),
+            _ => None,
+        };
+
+        let mut count = QueryBuilder::<Postgres>::new(
+            "SELECT COUNT(*) FROM questions q WHERE q.deleted_at IS NULL",
+        );
+        push_question_filters(&mut count, filter);
+        let (total,): (i64,) = count.build_query_as().fetch_one(&self.conn_pool).await?;
+
+        Ok(Page {
+            items: questions,
+            next_cursor,
+            total,
+        })
     }
 
-    pub async fn get_question_by_id<T: IntoQuestionId>(
+    async fn get_question_by_id<T: IntoQuestionId + Send>(
         &mut self,
         id: T,
     ) -> Result<Question, AppError> {
         let id = id.into_question_id();
 
-        let row = sqlx::query!(
+        let question = sqlx::query_as!(
+            Question,
             r#"
-    SELECT * FROM questions WHERE id = $1
+    SELECT id as "id: QuestionId", title, content, author_id as "author_id: UserId", created_on,
+           ARRAY(SELECT t.slug FROM question_tags qt JOIN tags t ON t.id = qt.tag_id
+                  WHERE qt.question_id = questions.id ORDER BY t.slug) as tags,
+           (SELECT COALESCE(SUM(v.value), 0) FROM votes v WHERE v.question_id = questions.id) as "score!",
+           version
+    FROM questions WHERE id = $1 AND deleted_at IS NULL
     "#,
             id.0,
         )
-        .fetch_one(&self.conn_pool)
-        .await?;
-
-        let question = Question {
-            id: row.id.into(), // Assuming you have a From<u32> for QuestionId
-            title: row.title,
-            content: row.content,
-            tags: row.tags,
-        };
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::Question(QuestionError::InvalidId))?;
 
         Ok(question)
     }
 
-    pub async fn get_question_comments(&mut self, question_id: i32) -> QuestionResult {
-        // return the Question (from its ID, given by the user/frontend like get_question_by_id)
-        // Answers given to the Question
-        // and Comments to both the Question and any Answers.
-
+    /// Loads a whole question thread: the question, its own comments, its answers and
+    /// each answer's comments. Always four queries no matter how many answers there are.
+    async fn get_question_comments(
+        &mut self,
+        question_id: i32,
+    ) -> Result<QuestionResult, AppError> {
         let q_row = sqlx::query!(
             r#"
-                select q.id, q.title, q.content, q.tags, q.created_on
+                select q.id, q.title, q.content, q.author_id, q.created_on,
+                       q.accepted_answer_id,
+                       array(select t.slug from question_tags qt join tags t on t.id = qt.tag_id
+                             where qt.question_id = q.id order by t.slug) as tags,
+                       coalesce((select sum(v.value) from votes v where v.question_id = q.id), 0)
+                           as "score!"
                 from questions q
-                where q.id = $1
+                where q.id = $1 and q.deleted_at is null
             "#,
             question_id,
         )
-        .fetch_one(&self.conn_pool)
-        .await
-        .unwrap();
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::Question(QuestionError::InvalidId))?;
 
         let c_rows = sqlx::query!(
             r#"
-                select c.id, c.content, c.created_on
-                from questions q, comments c
-                where q.id = c.applied_to_question_id
-                and q.id = $1
+                select c.id, c.content, c.author_id, c.created_on
+                from comments c
+                where c.applied_to_question_id = $1
                 and c.applied_to_answer_id is NULL
+                and c.deleted_at is NULL
                 order by c.created_on desc
             "#,
             question_id,
         )
         .fetch_all(&self.conn_pool)
-        .await
-        .unwrap();
+        .await?;
 
         let a_rows = sqlx::query!(
             r#"
-                select a.id, a.content, a.created_on
+                select a.id, a.content, a.author_id, a.created_on,
+                       coalesce((select sum(v.value) from votes v where v.answer_id = a.id), 0)
+                           as "score!"
                 from answers a
-                where a.id = $1
-                order by a.created_on desc
+                where a.question_id = $1 and a.deleted_at is null
+                order by coalesce(a.id = $2, false) desc, "score!" desc, a.created_on desc, a.id desc
             "#,
             question_id,
+            q_row.accepted_answer_id,
         )
         .fetch_all(&self.conn_pool)
-        .await
-        .unwrap();
+        .await?;
+
+        let answer_ids: Vec<i32> = a_rows.iter().map(|row| row.id).collect();
 
+        // One batched lookup for every answer's comments, grouped by answer below
         let ac_rows = sqlx::query!(
             r#"
-                select c.id, c.content, c.created_on
-                from answers a, comments c
-                where a.id = c.applied_to_answer_id
-                and a.id = $1
-                and c.applied_to_question_id is NULL
+                select c.id, c.content, c.author_id, c.created_on,
+                       c.applied_to_answer_id as "applied_to_answer_id!"
+                from comments c
+                where c.applied_to_answer_id = ANY($1) and c.deleted_at is null
                 order by c.created_on desc
             "#,
-            question_id,
+            &answer_ids,
         )
         .fetch_all(&self.conn_pool)
-        .await
-        .unwrap();
+        .await?;
 
-        let answer_comments: Vec<CommentResult> = ac_rows
-            .into_iter()
-            .map(|row1| CommentResult {
-                id: row1.id,
-                content: row1.content,
-                created_on: row1.created_on,
-            })
-            .collect();
+        let mut answer_comments: HashMap<i32, Vec<CommentResult>> = HashMap::new();
+        for row in ac_rows {
+            answer_comments
+                .entry(row.applied_to_answer_id)
+                .or_default()
+                .push(CommentResult {
+                    id: row.id,
+                    content: row.content,
+                    author_id: row.author_id,
+                    created_on: row.created_on,
+                });
+        }
 
-        QuestionResult {
+        Ok(QuestionResult {
             id: q_row.id,
             title: q_row.title,
             content: q_row.content,
             tags: q_row.tags,
+            author_id: q_row.author_id,
             created_on: q_row.created_on,
+            score: q_row.score,
+            accepted_answer_id: q_row.accepted_answer_id,
             comments: c_rows
                 .into_iter()
                 .map(|row| CommentResult {
                     id: row.id,
                     content: row.content,
+                    author_id: row.author_id,
                     created_on: row.created_on,
                 })
                 .collect(),
@@ -210,134 +506,1264 @@ SELECT * FROM questions
                 .map(|row| AnswerResult {
                     id: row.id,
                     content: row.content,
+                    author_id: row.author_id,
                     created_on: row.created_on,
-                    comments: answer_comments.clone(),
+                    score: row.score,
+                    comments: answer_comments.remove(&row.id).unwrap_or_default(),
                 })
                 .collect(),
-        }
+        })
     }
 
-    pub async fn add_question(
+    async fn add_question(
         &mut self,
         title: String,
         content: String,
         tags: Option<Vec<String>>,
-    ) -> Result<Json<Question>, AppError> {
-        let res = sqlx::query!(
-            r#"INSERT INTO "questions"(title, content, tags)
+        author_id: UserId,
+    ) -> Result<Question, AppError> {
+        let question_id = self
+            .transaction(move |tx| {
+                Box::pin(async move {
+                    let question_id = sqlx::query_scalar!(
+                        r#"INSERT INTO "questions"(title, content, author_id)
            VALUES ($1, $2, $3)
-           RETURNING *
+           RETURNING id
         "#,
-            title,
-            content,
-            tags.as_deref()
+                        title,
+                        content,
+                        author_id.0,
+                    )
+                    .fetch_one(&mut *tx)
+                    .await?;
+
+                    set_question_tags(tx, question_id, &tags.unwrap_or_default()).await?;
+                    record_question_revision(tx, question_id, Some(author_id)).await?;
+                    Ok(question_id)
+                })
+            })
+            .await?;
+
+        self.get_question_by_id(QuestionId(question_id)).await
+    }
+
+    async fn update_question(
+        &mut self,
+        new_question: UpdateQuestion,
+        if_match: Option<&EtagCondition>,
+        user: &AuthUser,
+    ) -> Result<Question, AppError> {
+        let existing = self.get_question_by_id(new_question.id).await?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let if_match = if_match.cloned();
+        let editor_id = user.id;
+        self.transaction(move |tx| {
+            Box::pin(async move {
+                let question = apply_question_edit(
+                    tx,
+                    new_question.id,
+                    &new_question.title,
+                    &new_question.content,
+                    &new_question.tags.unwrap_or_default(),
+                    editor_id,
+                )
+                .await?;
+
+                // The UPDATE holds the row lock until commit, so the version it replaced is
+                // the one a concurrent edit would have to have seen. A mismatch rolls it back.
+                if let Some(if_match) = if_match {
+                    if !if_match.matches(question.version - 1) {
+                        return Err(AppError::Question(QuestionError::VersionMismatch));
+                    }
+                }
+
+                Ok(question)
+            })
+        })
+        .await
+    }
+
+    async fn get_question_revisions(
+        &mut self,
+        question_id: QuestionId,
+    ) -> Result<Vec<QuestionRevision>, AppError> {
+        self.get_question_by_id(question_id).await?;
+
+        let revisions = sqlx::query_as!(
+            QuestionRevision,
+            r#"
+    SELECT revision, title, content, tags, editor_id, edited_on
+    FROM question_revisions
+    WHERE question_id = $1
+    ORDER BY revision
+    "#,
+            question_id.0,
         )
-        .fetch_one(&self.conn_pool)
+        .fetch_all(&self.conn_pool)
         .await?;
 
-        let new_question = Question {
-            id: QuestionId(res.id),
-            title: res.title,
-            content: res.content,
-            tags: res.tags,
-        };
+        Ok(revisions)
+    }
+
+    async fn get_question_revision(
+        &mut self,
+        question_id: QuestionId,
+        revision: i32,
+    ) -> Result<QuestionRevision, AppError> {
+        let revision = sqlx::query_as!(
+            QuestionRevision,
+            r#"
+    SELECT revision, title, content, tags, editor_id, edited_on
+    FROM question_revisions
+    WHERE question_id = $1 AND revision = $2
+    "#,
+            question_id.0,
+            revision,
+        )
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::Revision(RevisionError::NotFound))?;
 
-        Ok(Json(new_question))
+        Ok(revision)
     }
 
-    pub async fn update_question(
+    async fn diff_question_revisions(
         &mut self,
-        new_question: UpdateQuestion,
+        question_id: QuestionId,
+        from: i32,
+        to: i32,
+    ) -> Result<RevisionDiff, AppError> {
+        self.get_question_by_id(question_id).await?;
+
+        let from = self.get_question_revision(question_id, from).await?;
+        let to = self.get_question_revision(question_id, to).await?;
+
+        Ok(RevisionDiff::between_questions(&from, &to))
+    }
+
+    /// Restores an earlier revision. The rollback is itself saved as a new revision,
+    /// so nothing in the history is ever lost.
+    async fn rollback_question(
+        &mut self,
+        question_id: QuestionId,
+        revision: i32,
+        user: &AuthUser,
     ) -> Result<Question, AppError> {
-        sqlx::query!(
+        let existing = self.get_question_by_id(question_id).await?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let revision = self.get_question_revision(question_id, revision).await?;
+
+        let editor_id = user.id;
+        self.transaction(move |tx| {
+            Box::pin(async move {
+                apply_question_edit(
+                    tx,
+                    question_id,
+                    &revision.title,
+                    &revision.content,
+                    &revision.tags,
+                    editor_id,
+                )
+                .await
+            })
+        })
+        .await
+    }
+
+    async fn get_answer_revisions(
+        &mut self,
+        answer_id: AnswerId,
+    ) -> Result<Vec<AnswerRevision>, AppError> {
+        self.get_answer_by_id(answer_id).await?;
+
+        let revisions = sqlx::query_as!(
+            AnswerRevision,
             r#"
-    UPDATE questions
-    SET title = $1, content = $2, tags = $3
-    WHERE id = $4
+    SELECT revision, content, editor_id, edited_on
+    FROM answer_revisions
+    WHERE answer_id = $1
+    ORDER BY revision
     "#,
-            new_question.title,
-            new_question.content,
-            new_question.tags.as_deref(),
-            new_question.id.0,
+            answer_id.0,
         )
-        .execute(&self.conn_pool)
+        .fetch_all(&self.conn_pool)
         .await?;
 
-        let row = sqlx::query!(
+        Ok(revisions)
+    }
+
+    async fn get_answer_revision(
+        &mut self,
+        answer_id: AnswerId,
+        revision: i32,
+    ) -> Result<AnswerRevision, AppError> {
+        let revision = sqlx::query_as!(
+            AnswerRevision,
             r#"
-SELECT title, content, id, tags FROM questions WHERE id = $1
-"#,
-            new_question.id.0,
+    SELECT revision, content, editor_id, edited_on
+    FROM answer_revisions
+    WHERE answer_id = $1 AND revision = $2
+    "#,
+            answer_id.0,
+            revision,
         )
-        .fetch_one(&self.conn_pool)
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::Revision(RevisionError::NotFound))?;
+
+        Ok(revision)
+    }
+
+    async fn diff_answer_revisions(
+        &mut self,
+        answer_id: AnswerId,
+        from: i32,
+        to: i32,
+    ) -> Result<RevisionDiff, AppError> {
+        self.get_answer_by_id(answer_id).await?;
+
+        let from = self.get_answer_revision(answer_id, from).await?;
+        let to = self.get_answer_revision(answer_id, to).await?;
+
+        Ok(RevisionDiff::between_answers(&from, &to))
+    }
+
+    async fn rollback_answer(
+        &mut self,
+        answer_id: AnswerId,
+        revision: i32,
+        user: &AuthUser,
+    ) -> Result<Answer, AppError> {
+        let existing = self.get_answer_by_id(answer_id).await?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let revision = self.get_answer_revision(answer_id, revision).await?;
+
+        let editor_id = user.id;
+        let row = self
+            .transaction(move |tx| {
+                Box::pin(async move {
+                    apply_answer_edit(tx, answer_id, &revision.content, editor_id).await
+                })
+            })
+            .await?;
+
+        Ok(row.into())
+    }
+
+    async fn get_tags(&mut self) -> Result<Vec<Tag>, AppError> {
+        let tags = sqlx::query_as!(
+            Tag,
+            r#"
+    SELECT t.slug, COUNT(qt.question_id) as "question_count!"
+    FROM tags t
+    LEFT JOIN (question_tags qt JOIN questions q ON q.id = qt.question_id AND q.deleted_at IS NULL)
+        ON qt.tag_id = t.id
+    GROUP BY t.id
+    ORDER BY COUNT(qt.question_id) DESC, t.slug
+    "#,
+        )
+        .fetch_all(&self.conn_pool)
         .await?;
 
-        let question = Question {
-            title: row.title,
-            content: row.content,
-            id: QuestionId(row.id),
-            tags: row.tags,
-        };
+        Ok(tags)
+    }
 
-        Ok(question)
+    async fn get_tag(&mut self, slug: &str) -> Result<Tag, AppError> {
+        let tag = sqlx::query_as!(
+            Tag,
+            r#"
+    SELECT t.slug, COUNT(qt.question_id) as "question_count!"
+    FROM tags t
+    LEFT JOIN (question_tags qt JOIN questions q ON q.id = qt.question_id AND q.deleted_at IS NULL)
+        ON qt.tag_id = t.id
+    WHERE t.slug = $1 OR t.id = (SELECT tag_id FROM tag_synonyms WHERE synonym = $1)
+    GROUP BY t.id
+    "#,
+            slug,
+        )
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::Tag(TagError::NotFound))?;
+
+        Ok(tag)
+    }
+
+    /// Folds the tag `source` into `merge.into`: its questions are retagged, it becomes a
+    /// synonym of the target and the merge is logged against `user`, all in one transaction.
+    /// `source` need not exist yet, which just registers it as a synonym up front.
+    async fn merge_tags(
+        &mut self,
+        source: &str,
+        merge: MergeTag,
+        user: &AuthUser,
+    ) -> Result<TagMerge, AppError> {
+        let source = slugify(source);
+        let target = self.get_tag(&slugify(&merge.into)).await?;
+        if source.is_empty() || source == target.slug {
+            return Err(AppError::Validation(vec![FieldError::new(
+                "into",
+                "must name a different tag than the one being merged",
+            )]));
+        }
+
+        let merged_by = user.id;
+        let merged = self
+            .transaction(move |tx| {
+                Box::pin(async move {
+                    let target_id =
+                        sqlx::query_scalar!("SELECT id FROM tags WHERE slug = $1", target.slug)
+                            .fetch_one(&mut *tx)
+                            .await?;
+
+                    let source_id =
+                        sqlx::query_scalar!("SELECT id FROM tags WHERE slug = $1", source)
+                            .fetch_optional(&mut *tx)
+                            .await?;
+
+                    let mut retagged = Vec::new();
+                    if let Some(source_id) = source_id {
+                        retagged = sqlx::query_scalar!(
+                            r#"
+    SELECT question_id FROM question_tags WHERE tag_id = $1 ORDER BY question_id
+    "#,
+                            source_id,
+                        )
+                        .fetch_all(&mut *tx)
+                        .await?;
+                        for question_id in &retagged {
+                            save_first_question_revision(tx, *question_id).await?;
+                        }
+
+                        sqlx::query!(
+                            r#"
+    INSERT INTO question_tags (question_id, tag_id)
+    SELECT question_id, $2 FROM question_tags WHERE tag_id = $1
+    ON CONFLICT DO NOTHING
+    "#,
+                            source_id,
+                            target_id,
+                        )
+                        .execute(&mut *tx)
+                        .await?;
+
+                        // Synonyms of the old tag now point at the new one
+                        sqlx::query!(
+                            r#"
+    UPDATE tag_synonyms SET tag_id = $2 WHERE tag_id = $1
+    "#,
+                            source_id,
+                            target_id,
+                        )
+                        .execute(&mut *tx)
+                        .await?;
+
+                        // Takes the source's question_tags rows with it
+                        sqlx::query!("DELETE FROM tags WHERE id = $1", source_id)
+                            .execute(&mut *tx)
+                            .await?;
+
+                        // Retagging changes the questions like an edit would
+                        sqlx::query!(
+                            r#"
+    UPDATE questions SET version = version + 1 WHERE id = ANY($1)
+    "#,
+                            &retagged,
+                        )
+                        .execute(&mut *tx)
+                        .await?;
+                        for question_id in &retagged {
+                            record_question_revision(tx, *question_id, Some(merged_by)).await?;
+                        }
+                    }
+
+                    sqlx::query!(
+                        r#"
+    INSERT INTO tag_synonyms (synonym, tag_id, created_by)
+    VALUES ($1, $2, $3)
+    ON CONFLICT (synonym) DO UPDATE SET tag_id = EXCLUDED.tag_id, created_by = EXCLUDED.created_by
+    "#,
+                        source,
+                        target_id,
+                        merged_by.0,
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+
+                    let merged = sqlx::query_as!(
+                        TagMerge,
+                        r#"
+    INSERT INTO tag_merges (source_slug, target_slug, question_count, merged_by)
+    VALUES ($1, $2, $3, $4)
+    RETURNING source_slug, target_slug, question_count, merged_by, merged_on
+    "#,
+                        source,
+                        target.slug,
+                        retagged.len() as i32,
+                        merged_by.0,
+                    )
+                    .fetch_one(&mut *tx)
+                    .await?;
+
+                    Ok(merged)
+                })
+            })
+            .await?;
+
+        Ok(merged)
     }
 
-    pub async fn delete_question(&mut self, question_id: i32) -> Result<(), AppError> {
+    async fn delete_question(&mut self, question_id: i32, user: &AuthUser) -> Result<(), AppError> {
         let question_id = question_id.into_question_id();
         println!("DELETE - Question id is {}", &question_id);
+
+        let existing = self.get_question_by_id(question_id).await?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        // NOW() is fixed for the whole transaction, so the question and everything under it
+        // share one deleted_at. That is how undelete_question tells them apart from answers
+        // and comments that had been deleted on their own before.
+        self.transaction(move |tx| {
+            Box::pin(async move {
+                sqlx::query!(
+                    r#"
+    UPDATE questions SET deleted_at = NOW() WHERE id = $1
+    "#,
+                    question_id.0,
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                sqlx::query!(
+                    r#"
+    UPDATE comments SET deleted_at = NOW()
+    WHERE deleted_at IS NULL
+      AND (applied_to_question_id = $1
+           OR applied_to_answer_id IN (SELECT id FROM answers WHERE question_id = $1))
+    "#,
+                    question_id.0,
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                sqlx::query!(
+                    r#"
+    UPDATE answers SET deleted_at = NOW() WHERE question_id = $1 AND deleted_at IS NULL
+    "#,
+                    question_id.0,
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                Ok(())
+            })
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Brings back a deleted question together with the answers and comments that were
+    /// deleted along with it. Restoring a question that isn't deleted is a no-op.
+    async fn undelete_question(&mut self, question_id: QuestionId) -> Result<Question, AppError> {
+        let deleted_at = sqlx::query_scalar!(
+            r#"
+    SELECT deleted_at FROM questions WHERE id = $1
+    "#,
+            question_id.0,
+        )
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::Question(QuestionError::InvalidId))?;
+
+        if let Some(deleted_at) = deleted_at {
+            self.transaction(move |tx| {
+                Box::pin(async move {
+                    sqlx::query!(
+                        r#"
+    UPDATE comments SET deleted_at = NULL
+    WHERE deleted_at = $2
+      AND (applied_to_question_id = $1
+           OR applied_to_answer_id IN (SELECT id FROM answers WHERE question_id = $1))
+    "#,
+                        question_id.0,
+                        deleted_at,
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+
+                    sqlx::query!(
+                        r#"
+    UPDATE answers SET deleted_at = NULL WHERE question_id = $1 AND deleted_at = $2
+    "#,
+                        question_id.0,
+                        deleted_at,
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+
+                    sqlx::query!(
+                        r#"
+    UPDATE questions SET deleted_at = NULL WHERE id = $1
+    "#,
+                        question_id.0,
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+
+                    Ok(())
+                })
+            })
+            .await?;
+        }
+
+        self.get_question_by_id(question_id).await
+    }
+
+    /// Removes for good every post that was soft deleted before `cutoff`. Deleting a question
+    /// cascades to its answers and comments, so those are only counted when they went alone.
+    async fn purge_deleted(&mut self, cutoff: DateTime<Utc>) -> Result<PurgeCounts, AppError> {
+        self.transaction(move |tx| {
+            Box::pin(async move {
+                let questions = sqlx::query!("DELETE FROM questions WHERE deleted_at < $1", cutoff)
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected();
+
+                let answers = sqlx::query!("DELETE FROM answers WHERE deleted_at < $1", cutoff)
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected();
+
+                let comments = sqlx::query!("DELETE FROM comments WHERE deleted_at < $1", cutoff)
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected();
+
+                Ok(PurgeCounts {
+                    questions,
+                    answers,
+                    comments,
+                })
+            })
+        })
+        .await
+    }
+
+    async fn accept_answer(
+        &mut self,
+        question_id: QuestionId,
+        answer_id: AnswerId,
+        user: &AuthUser,
+    ) -> Result<QuestionResult, AppError> {
+        let question = self.get_question_by_id(question_id).await?;
+        if question.author_id != Some(user.id) {
+            return Err(AppError::Forbidden(Forbidden::not_question_author()));
+        }
+
+        let answer = self.get_answer_by_id(answer_id).await?;
+        if answer.question_id != question_id {
+            return Err(AppError::Answer(AnswerError::NotOnQuestion));
+        }
+
         sqlx::query!(
             r#"
-    DELETE FROM questions WHERE id = $1
+    UPDATE questions SET accepted_answer_id = $1 WHERE id = $2
     "#,
+            answer_id.0,
             question_id.0,
         )
         .execute(&self.conn_pool)
-        .await
-        .unwrap();
+        .await?;
 
-        Ok(())
+        self.get_question_comments(question_id.0).await
     }
 
-    pub async fn add_comment(
+    async fn unaccept_answer(
+        &mut self,
+        question_id: QuestionId,
+        user: &AuthUser,
+    ) -> Result<QuestionResult, AppError> {
+        let question = self.get_question_by_id(question_id).await?;
+        if question.author_id != Some(user.id) {
+            return Err(AppError::Forbidden(Forbidden::not_question_author()));
+        }
+
+        sqlx::query!(
+            r#"
+    UPDATE questions SET accepted_answer_id = NULL WHERE id = $1
+    "#,
+            question_id.0,
+        )
+        .execute(&self.conn_pool)
+        .await?;
+
+        self.get_question_comments(question_id.0).await
+    }
+
+    async fn cast_vote(
+        &mut self,
+        target: VoteTarget,
+        user_id: UserId,
+        direction: VoteDirection,
+    ) -> Result<VoteSummary, AppError> {
+        match target {
+            VoteTarget::Question(question_id) => {
+                self.get_question_by_id(question_id).await?;
+                sqlx::query!(
+                    r#"
+    INSERT INTO votes (user_id, question_id, value)
+    VALUES ($1, $2, $3)
+    ON CONFLICT (user_id, question_id) DO UPDATE SET value = EXCLUDED.value, created_on = NOW()
+    "#,
+                    user_id.0,
+                    question_id.0,
+                    direction.value(),
+                )
+                .execute(&self.conn_pool)
+                .await?;
+            }
+            VoteTarget::Answer(answer_id) => {
+                self.get_answer_by_id(answer_id).await?;
+                sqlx::query!(
+                    r#"
+    INSERT INTO votes (user_id, answer_id, value)
+    VALUES ($1, $2, $3)
+    ON CONFLICT (user_id, answer_id) DO UPDATE SET value = EXCLUDED.value, created_on = NOW()
+    "#,
+                    user_id.0,
+                    answer_id.0,
+                    direction.value(),
+                )
+                .execute(&self.conn_pool)
+                .await?;
+            }
+        }
+
+        self.vote_summary(target, user_id).await
+    }
+
+    async fn retract_vote(
+        &mut self,
+        target: VoteTarget,
+        user_id: UserId,
+    ) -> Result<VoteSummary, AppError> {
+        match target {
+            VoteTarget::Question(question_id) => {
+                self.get_question_by_id(question_id).await?;
+                sqlx::query!(
+                    r#"
+    DELETE FROM votes WHERE user_id = $1 AND question_id = $2
+    "#,
+                    user_id.0,
+                    question_id.0,
+                )
+                .execute(&self.conn_pool)
+                .await?;
+            }
+            VoteTarget::Answer(answer_id) => {
+                self.get_answer_by_id(answer_id).await?;
+                sqlx::query!(
+                    r#"
+    DELETE FROM votes WHERE user_id = $1 AND answer_id = $2
+    "#,
+                    user_id.0,
+                    answer_id.0,
+                )
+                .execute(&self.conn_pool)
+                .await?;
+            }
+        }
+
+        self.vote_summary(target, user_id).await
+    }
+
+    /// Comments on `target`, which has to exist and not be deleted
+    async fn add_comment(
         &mut self,
         content: String,
-        applied_to_question_id: Option<QuestionId>,
-        applied_to_answer_id: Option<AnswerId>,
+        target: CommentTarget,
+        author_id: UserId,
     ) -> Result<CommentDbResult, AppError> {
-        let mut result: CommentDbResult = CommentDbResult {
-            id: 0,
-            content: "".to_string(),
-            applied_to_question_id: None,
-            applied_to_answer_id: None,
+        let (question_id, answer_id) = match target {
+            CommentTarget::Question(question_id) => {
+                self.get_question_by_id(question_id).await?;
+                (Some(question_id.0), None)
+            }
+            CommentTarget::Answer(answer_id) => {
+                self.get_answer_by_id(answer_id).await?;
+                (None, Some(answer_id.0))
+            }
         };
 
-        if applied_to_question_id.unwrap().0 > 0 {
-            result = sqlx::query_as!(
-                CommentDbResult,
-                r#"INSERT INTO "comments"(content, applied_to_question_id)
-                   VALUES ($1, $2)
-                   RETURNING id, content, applied_to_question_id, applied_to_answer_id
-                "#,
-                content,
-                applied_to_question_id.unwrap().0 as i32
-            )
-            .fetch_one(&self.conn_pool)
-            .await?;
-        } else if applied_to_answer_id.unwrap().0 > 0 {
-            result = sqlx::query_as!(
-                CommentDbResult,
-                r#"INSERT INTO "comments"(content, applied_to_answer_id)
-                   VALUES ($1, $2)
-                   RETURNING id, content, applied_to_question_id, applied_to_answer_id
-                "#,
-                content,
-                applied_to_answer_id.unwrap().0 as i32
-            )
-            .fetch_one(&self.conn_pool)
-            .await?;
+        let row = sqlx::query_as!(
+            CommentDbResult,
+            r#"
+    INSERT INTO comments (content, applied_to_question_id, applied_to_answer_id, author_id)
+    VALUES ($1, $2, $3, $4)
+    RETURNING id as "id: CommentId", content, applied_to_question_id, applied_to_answer_id,
+              author_id, created_on
+    "#,
+            content,
+            question_id,
+            answer_id,
+            author_id.0,
+        )
+        .fetch_one(&self.conn_pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    async fn get_comments_for_question(
+        &mut self,
+        question_id: QuestionId,
+    ) -> Result<Vec<CommentDbResult>, AppError> {
+        // 404 for an unknown question rather than an empty list
+        self.get_question_by_id(question_id).await?;
+
+        let rows = sqlx::query_as!(
+            CommentDbResult,
+            r#"
+    SELECT id as "id: CommentId", content, applied_to_question_id, applied_to_answer_id,
+           author_id, created_on
+    FROM comments
+    WHERE applied_to_question_id = $1 AND applied_to_answer_id IS NULL AND deleted_at IS NULL
+    ORDER BY created_on, id
+    "#,
+            question_id.0,
+        )
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    async fn get_comments_for_answer(
+        &mut self,
+        answer_id: AnswerId,
+    ) -> Result<Vec<CommentDbResult>, AppError> {
+        self.get_answer_by_id(answer_id).await?;
+
+        let rows = sqlx::query_as!(
+            CommentDbResult,
+            r#"
+    SELECT id as "id: CommentId", content, applied_to_question_id, applied_to_answer_id,
+           author_id, created_on
+    FROM comments
+    WHERE applied_to_answer_id = $1 AND deleted_at IS NULL
+    ORDER BY created_on, id
+    "#,
+            answer_id.0,
+        )
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    async fn get_comment_by_id(
+        &mut self,
+        comment_id: CommentId,
+    ) -> Result<CommentDbResult, AppError> {
+        let row = sqlx::query_as!(
+            CommentDbResult,
+            r#"
+    SELECT id as "id: CommentId", content, applied_to_question_id, applied_to_answer_id,
+           author_id, created_on
+    FROM comments
+    WHERE id = $1 AND deleted_at IS NULL
+    "#,
+            comment_id.0,
+        )
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::Comment(CommentError::InvalidId))?;
+
+        Ok(row)
+    }
+
+    async fn update_comment(
+        &mut self,
+        comment_id: CommentId,
+        content: String,
+        user: &AuthUser,
+    ) -> Result<CommentDbResult, AppError> {
+        let existing = self.get_comment_by_id(comment_id).await?;
+        if !user.can_modify(existing.author_id.map(UserId)) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let row = sqlx::query_as!(
+            CommentDbResult,
+            r#"
+    UPDATE comments SET content = $1 WHERE id = $2
+    RETURNING id as "id: CommentId", content, applied_to_question_id, applied_to_answer_id,
+              author_id, created_on
+    "#,
+            content,
+            comment_id.0,
+        )
+        .fetch_one(&self.conn_pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    async fn delete_comment(
+        &mut self,
+        comment_id: CommentId,
+        user: &AuthUser,
+    ) -> Result<(), AppError> {
+        let existing = self.get_comment_by_id(comment_id).await?;
+        if !user.can_modify(existing.author_id.map(UserId)) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        sqlx::query!(
+            r#"
+    UPDATE comments SET deleted_at = NOW() WHERE id = $1
+    "#,
+            comment_id.0,
+        )
+        .execute(&self.conn_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Ranked full-text matches across questions, answers and comments. Snippets are only
+    /// built for the rows on the requested page since `ts_headline` re-parses the whole text.
+    async fn search(
+        &mut self,
+        terms: &str,
+        params: &PageParams,
+    ) -> Result<Page<SearchResult>, AppError> {
+        let rows = sqlx::query_as!(
+            SearchDbResult,
+            r#"
+    WITH query AS (SELECT websearch_to_tsquery('english', $1) AS tsq),
+    hits AS (
+        SELECT 'question' AS entity_type, q.id, q.id AS question_id, q.title AS question_title,
+               q.title || ' ' || q.content AS body, ts_rank(q.search_vector, query.tsq) AS rank
+        FROM questions q, query
+        WHERE q.search_vector @@ query.tsq AND q.deleted_at IS NULL
+        UNION ALL
+        SELECT 'answer', a.id, q.id, q.title, a.content, ts_rank(a.search_vector, query.tsq)
+        FROM answers a
+        JOIN questions q ON q.id = a.question_id, query
+        WHERE a.search_vector @@ query.tsq AND a.deleted_at IS NULL
+        UNION ALL
+        SELECT 'comment', c.id, q.id, q.title, c.content, ts_rank(c.search_vector, query.tsq)
+        FROM comments c
+        LEFT JOIN answers a ON a.id = c.applied_to_answer_id
+        JOIN questions q ON q.id = COALESCE(c.applied_to_question_id, a.question_id), query
+        WHERE c.search_vector @@ query.tsq AND c.deleted_at IS NULL
+    )
+    SELECT hits.entity_type as "entity_type!", hits.id as "id!", hits.question_id as "question_id!",
+           hits.question_title as "question_title!",
+           ts_headline('english', translate(hits.body, $4, ''), query.tsq, $5) as "snippet!",
+           hits.rank as "rank!"
+    FROM hits, query
+    ORDER BY hits.rank DESC, hits.entity_type, hits.id
+    OFFSET $2 LIMIT $3
+    "#,
+            terms,
+            params.offset(),
+            params.limit(),
+            HEADLINE_MARKERS,
+            HEADLINE_OPTIONS,
+        )
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        let total = sqlx::query_scalar!(
+            r#"
+    WITH query AS (SELECT websearch_to_tsquery('english', $1) AS tsq)
+    SELECT
+        (SELECT COUNT(*) FROM questions q, query
+         WHERE q.search_vector @@ query.tsq AND q.deleted_at IS NULL) +
+        (SELECT COUNT(*) FROM answers a, query
+         WHERE a.search_vector @@ query.tsq AND a.deleted_at IS NULL) +
+        (SELECT COUNT(*) FROM comments c, query
+         WHERE c.search_vector @@ query.tsq AND c.deleted_at IS NULL)
+        as "total!"
+    "#,
+            terms,
+        )
+        .fetch_one(&self.conn_pool)
+        .await?;
+
+        Ok(Page {
+            items: rows.into_iter().map(SearchResult::from).collect(),
+            next_cursor: None,
+            total,
+        })
+    }
+
+    async fn add_user(&mut self, email: String, password_hash: String) -> Result<User, AppError> {
+        let row = sqlx::query_as!(
+            UserDbResult,
+            r#"INSERT INTO "users"(email, password_hash)
+           VALUES ($1, $2)
+           ON CONFLICT (email) DO NOTHING
+           RETURNING id, email, password_hash, role, created_on
+        "#,
+            email,
+            password_hash,
+        )
+        .fetch_optional(&self.conn_pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(row.into()),
+            None => Err(AppError::User(UserError::EmailTaken)),
         }
+    }
 
-        Ok(result)
+    async fn get_user_by_email(&mut self, email: &str) -> Result<Option<UserDbResult>, AppError> {
+        let row = sqlx::query_as!(
+            UserDbResult,
+            r#"
+    SELECT id, email, password_hash, role, created_on FROM users WHERE email = $1
+    "#,
+            email,
+        )
+        .fetch_optional(&self.conn_pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    async fn get_user_by_id(&mut self, user_id: UserId) -> Result<Option<User>, AppError> {
+        let row = sqlx::query_as!(
+            UserDbResult,
+            r#"
+    SELECT id, email, password_hash, role, created_on FROM users WHERE id = $1
+    "#,
+            user_id.0,
+        )
+        .fetch_optional(&self.conn_pool)
+        .await?;
+
+        Ok(row.map(User::from))
+    }
+
+    async fn get_all_users(&mut self) -> Result<Vec<User>, AppError> {
+        let rows = sqlx::query_as!(
+            UserDbResult,
+            r#"
+    SELECT id, email, password_hash, role, created_on FROM users ORDER BY id
+    "#
+        )
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        Ok(rows.into_iter().map(User::from).collect())
+    }
+
+    async fn set_user_role(&mut self, user_id: UserId, role: Role) -> Result<User, AppError> {
+        let row = sqlx::query_as!(
+            UserDbResult,
+            r#"
+    UPDATE users SET role = $1 WHERE id = $2
+    RETURNING id, email, password_hash, role, created_on
+    "#,
+            role.as_str(),
+            user_id.0,
+        )
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::User(UserError::NotFound))?;
+
+        Ok(row.into())
+    }
+
+    async fn delete_user(&mut self, user_id: UserId) -> Result<(), AppError> {
+        let result = sqlx::query!(
+            r#"
+    DELETE FROM users WHERE id = $1
+    "#,
+            user_id.0,
+        )
+        .execute(&self.conn_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::User(UserError::NotFound));
+        }
+
+        Ok(())
+    }
+}
+
+/// Saves an edit to a question along with the revision recording it, returning the question
+/// as it now stands
+async fn apply_question_edit(
+    tx: &mut Transaction<'_, Postgres>,
+    question_id: QuestionId,
+    title: &str,
+    content: &str,
+    tags: &[String],
+    editor_id: UserId,
+) -> Result<Question, AppError> {
+    save_first_question_revision(tx, question_id.0).await?;
+
+    // Taking the row lock before the tags are touched keeps concurrent edits from mixing tags
+    let mut question = sqlx::query_as!(
+        Question,
+        r#"
+    UPDATE questions
+    SET title = $1, content = $2, version = version + 1
+    WHERE id = $3 AND deleted_at IS NULL
+    RETURNING id as "id: QuestionId", title, content, author_id as "author_id: UserId",
+              created_on, NULL::text[] as tags,
+              (SELECT COALESCE(SUM(v.value), 0) FROM votes v WHERE v.question_id = questions.id) as "score!",
+              version
+    "#,
+        title,
+        content,
+        question_id.0,
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::Question(QuestionError::InvalidId))?;
+
+    let mut slugs = set_question_tags(&mut *tx, question_id.0, tags).await?;
+    slugs.sort();
+    question.tags = Some(slugs);
+
+    record_question_revision(tx, question_id.0, Some(editor_id)).await?;
+
+    Ok(question)
+}
+
+/// Rows that predate revisions get their current state saved before it is overwritten
+async fn save_first_question_revision(
+    tx: &mut Transaction<'_, Postgres>,
+    question_id: i32,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+    INSERT INTO question_revisions (question_id, revision, title, content, tags, editor_id, edited_on)
+    SELECT q.id, 1, q.title, q.content,
+           ARRAY(SELECT t.slug FROM question_tags qt JOIN tags t ON t.id = qt.tag_id
+                 WHERE qt.question_id = q.id ORDER BY t.slug),
+           q.author_id, q.created_on
+    FROM questions q
+    WHERE q.id = $1 AND NOT EXISTS (SELECT 1 FROM question_revisions WHERE question_id = $1)
+    "#,
+        question_id,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Snapshots the question as it now stands under the next revision number
+async fn record_question_revision(
+    tx: &mut Transaction<'_, Postgres>,
+    question_id: i32,
+    editor_id: Option<UserId>,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+    INSERT INTO question_revisions (question_id, revision, title, content, tags, editor_id)
+    SELECT q.id,
+           COALESCE((SELECT MAX(revision) FROM question_revisions WHERE question_id = q.id), 0) + 1,
+           q.title, q.content,
+           ARRAY(SELECT t.slug FROM question_tags qt JOIN tags t ON t.id = qt.tag_id
+                 WHERE qt.question_id = q.id ORDER BY t.slug),
+           $2
+    FROM questions q
+    WHERE q.id = $1
+    "#,
+        question_id,
+        editor_id.map(|id| id.0),
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Saves an edit to an answer along with the revision recording it
+async fn apply_answer_edit(
+    tx: &mut Transaction<'_, Postgres>,
+    answer_id: AnswerId,
+    content: &str,
+    editor_id: UserId,
+) -> Result<AnswerDbResult, AppError> {
+    // Rows that predate revisions get their current state saved before it is overwritten
+    sqlx::query!(
+        r#"
+    INSERT INTO answer_revisions (answer_id, revision, content, editor_id, edited_on)
+    SELECT a.id, 1, a.content, a.author_id, a.created_on
+    FROM answers a
+    WHERE a.id = $1 AND NOT EXISTS (SELECT 1 FROM answer_revisions WHERE answer_id = $1)
+    "#,
+        answer_id.0,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let row = sqlx::query_as!(
+        AnswerDbResult,
+        r#"
+    UPDATE answers SET content = $1 WHERE id = $2
+    RETURNING id, content, question_id as "question_id!", author_id, created_on
+    "#,
+        content,
+        answer_id.0,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    record_answer_revision(tx, answer_id.0, Some(editor_id)).await?;
+
+    Ok(row)
+}
+
+/// Snapshots the answer as it now stands under the next revision number
+async fn record_answer_revision(
+    tx: &mut Transaction<'_, Postgres>,
+    answer_id: i32,
+    editor_id: Option<UserId>,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+    INSERT INTO answer_revisions (answer_id, revision, content, editor_id)
+    SELECT a.id,
+           COALESCE((SELECT MAX(revision) FROM answer_revisions WHERE answer_id = a.id), 0) + 1,
+           a.content, $2
+    FROM answers a
+    WHERE a.id = $1
+    "#,
+        answer_id,
+        editor_id.map(|id| id.0),
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Points a question at exactly the given tags, creating any tag that doesn't exist yet.
+/// Returns the slugs it was given after normalizing and resolving synonyms.
+async fn set_question_tags(
+    tx: &mut Transaction<'_, Postgres>,
+    question_id: i32,
+    tags: &[String],
+) -> Result<Vec<String>, AppError> {
+    let slugs = resolve_synonyms(&mut *tx, &normalize_tags(tags)).await?;
+
+    sqlx::query!(
+        r#"
+    DELETE FROM question_tags WHERE question_id = $1
+    "#,
+        question_id,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+    INSERT INTO tags (slug) SELECT * FROM UNNEST($1::text[]) ON CONFLICT (slug) DO NOTHING
+    "#,
+        &slugs,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+    INSERT INTO question_tags (question_id, tag_id)
+    SELECT $1, id FROM tags WHERE slug = ANY($2)
+    "#,
+        question_id,
+        &slugs,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(slugs)
+}
+
+/// Swaps every slug that is a registered synonym for the tag it stands for, so `js` is saved
+/// as `javascript`. Two spellings of the same tag collapse into one.
+async fn resolve_synonyms<'c, E>(executor: E, slugs: &[String]) -> Result<Vec<String>, AppError>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let resolved = sqlx::query_scalar!(
+        r#"
+    SELECT COALESCE(t.slug, s.slug) as "slug!"
+    FROM UNNEST($1::text[]) WITH ORDINALITY AS s(slug, position)
+    LEFT JOIN tag_synonyms ts ON ts.synonym = s.slug
+    LEFT JOIN tags t ON t.id = ts.tag_id
+    ORDER BY s.position
+    "#,
+        slugs,
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(normalize_tags(&resolved))
+}
+
+/// The WHERE conditions shared by the question listing and its total count
+fn push_question_filters(query: &mut QueryBuilder<Postgres>, filter: &QuestionFilter) {
+    if !filter.tags.is_empty() {
+        let tagged = "SELECT COUNT(*) FROM question_tags qt JOIN tags t ON t.id = qt.tag_id \
+                      WHERE qt.question_id = q.id AND t.slug = ANY(";
+        query.push(" AND (");
+        query.push(tagged);
+        query.push_bind(filter.tags.clone());
+        match filter.tag_match {
+            TagMatch::All => {
+                query.push(")) = ");
+                query.push_bind(filter.tags.len() as i64);
+            }
+            TagMatch::Any => {
+                query.push(")) > 0");
+            }
+        }
+    }
+
+    if let Some(author) = filter.author {
+        query.push(" AND q.author_id = ");
+        query.push_bind(author.0);
+    }
+
+    if let Some(created_after) = filter.created_after {
+        query.push(" AND q.created_on > ");
+        query.push_bind(created_after);
+    }
+
+    if let Some(created_before) = filter.created_before {
+        query.push(" AND q.created_on < ");
+        query.push_bind(created_before);
+    }
+
+    match filter.has_answers {
+        Some(true) => {
+            query.push(" AND EXISTS (SELECT 1 FROM answers a WHERE a.question_id = q.id AND a.deleted_at IS NULL)");
+        }
+        Some(false) => {
+            query.push(" AND NOT EXISTS (SELECT 1 FROM answers a WHERE a.question_id = q.id AND a.deleted_at IS NULL)");
+        }
+        None => {}
     }
 }
 
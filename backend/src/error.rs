@@ -1,37 +1,344 @@
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
-use http::StatusCode;
+use http::header::{CONTENT_TYPE, RETRY_AFTER};
+use http::{HeaderValue, StatusCode};
+use serde_derive::Serialize;
 use serde_json::json;
 use sqlx::Error;
+use tracing::{error, warn};
+
+use crate::user::Role;
 
 #[derive(Debug)]
 pub enum AppError {
     Question(QuestionError),
+    Answer(AnswerError),
+    Comment(CommentError),
+    Tag(TagError),
+    Revision(RevisionError),
+    User(UserError),
+    Unauthorized(AuthError),
+    Forbidden(Forbidden),
+    Validation(Vec<FieldError>),
+    InvalidPath(PathRejection),
+    InvalidQuery(QueryRejection),
+    InvalidBody(JsonRejection),
+    RouteNotFound,
     Database(Error),
-    #[allow(dead_code)]
     Any(anyhow::Error),
 }
 
+// The Display text of these is sent to clients as the problem `detail`
+
 #[derive(derive_more::Display, Debug)]
 pub enum QuestionError {
+    #[display(fmt = "No question with that id")]
     InvalidId,
+    #[display(fmt = "The question has changed since the version named in If-Match")]
+    VersionMismatch,
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let (status, error_message) = match self {
+#[derive(derive_more::Display, Debug)]
+pub enum AnswerError {
+    #[display(fmt = "No answer with that id")]
+    InvalidId,
+    #[display(fmt = "The answer belongs to a different question")]
+    NotOnQuestion,
+}
+
+#[derive(derive_more::Display, Debug)]
+pub enum CommentError {
+    #[display(fmt = "No comment with that id")]
+    InvalidId,
+}
+
+#[derive(derive_more::Display, Debug)]
+pub enum TagError {
+    #[display(fmt = "No tag with that name")]
+    NotFound,
+}
+
+#[derive(derive_more::Display, Debug)]
+pub enum RevisionError {
+    #[display(fmt = "No revision with that number")]
+    NotFound,
+}
+
+#[derive(derive_more::Display, Debug)]
+pub enum UserError {
+    #[display(fmt = "Wrong email or password")]
+    InvalidCredentials,
+    #[display(fmt = "That email is already registered")]
+    EmailTaken,
+    #[display(fmt = "No user with that id")]
+    NotFound,
+}
+
+#[derive(derive_more::Display, Debug)]
+pub enum AuthError {
+    #[display(fmt = "Log in and send the token as `Authorization: Bearer <token>`")]
+    MissingToken,
+    #[display(fmt = "The token is invalid or has expired")]
+    InvalidToken,
+}
+
+/// Why a logged in user was turned away, sent back to the client as-is
+#[derive(Debug, Serialize)]
+pub struct Forbidden {
+    pub reason: ForbiddenReason,
+    pub allowed_roles: Vec<Role>,
+}
+
+#[derive(derive_more::Display, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForbiddenReason {
+    #[display(fmt = "Your role may not do this")]
+    InsufficientRole,
+    #[display(fmt = "Only the author or staff may change this")]
+    NotAuthor,
+    #[display(fmt = "Only the author of the question may do this")]
+    NotQuestionAuthor,
+}
+
+impl Forbidden {
+    pub fn insufficient_role(allowed_roles: &[Role]) -> Self {
+        Forbidden {
+            reason: ForbiddenReason::InsufficientRole,
+            allowed_roles: allowed_roles.to_vec(),
+        }
+    }
+
+    // Authors can always change their own posts, otherwise it takes staff
+    pub fn not_author() -> Self {
+        Forbidden {
+            reason: ForbiddenReason::NotAuthor,
+            allowed_roles: vec![Role::Moderator, Role::Admin],
+        }
+    }
+
+    // Only the person who asked gets to decide which answer solved it, whatever their role
+    pub fn not_question_author() -> Self {
+        Forbidden {
+            reason: ForbiddenReason::NotQuestionAuthor,
+            allowed_roles: vec![],
+        }
+    }
+}
+
+/// One rejected input value, every offending field is reported at once
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Seconds a client is asked to wait before retrying when the database is unreachable
+const RETRY_AFTER_SECS: u64 = 5;
+
+/// An RFC 7807 problem details body. `code` is stable and meant for programs to match on,
+/// `title` and `detail` are for people. Variant specific members, like the offending
+/// `fields` of a validation error, sit next to them.
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: &'static str,
+    pub title: String,
+    pub status: u16,
+    pub code: &'static str,
+    pub detail: String,
+    #[serde(flatten)]
+    pub extensions: serde_json::Map<String, serde_json::Value>,
+}
+
+impl AppError {
+    /// The status and stable `code` sent for this error, plus a detail safe to show clients
+    fn classify(&self) -> (StatusCode, &'static str, String) {
+        match self {
             AppError::Question(err) => match err {
-                QuestionError::InvalidId => (StatusCode::NOT_FOUND, err.to_string()),
+                QuestionError::InvalidId => {
+                    (StatusCode::NOT_FOUND, "question_not_found", err.to_string())
+                }
+                QuestionError::VersionMismatch => (
+                    StatusCode::PRECONDITION_FAILED,
+                    "version_mismatch",
+                    err.to_string(),
+                ),
+            },
+            AppError::Answer(err) => match err {
+                AnswerError::InvalidId => {
+                    (StatusCode::NOT_FOUND, "answer_not_found", err.to_string())
+                }
+                AnswerError::NotOnQuestion => (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "answer_not_on_question",
+                    err.to_string(),
+                ),
+            },
+            AppError::Comment(err) => match err {
+                CommentError::InvalidId => {
+                    (StatusCode::NOT_FOUND, "comment_not_found", err.to_string())
+                }
             },
-            AppError::Database(err) => (StatusCode::SERVICE_UNAVAILABLE, err.to_string()),
-            AppError::Any(err) => {
-                let message = format!("Internal server error! {}", err);
-                (StatusCode::INTERNAL_SERVER_ERROR, message)
+            AppError::Tag(err) => match err {
+                TagError::NotFound => (StatusCode::NOT_FOUND, "tag_not_found", err.to_string()),
+            },
+            AppError::Revision(err) => match err {
+                RevisionError::NotFound => {
+                    (StatusCode::NOT_FOUND, "revision_not_found", err.to_string())
+                }
+            },
+            AppError::User(err) => match err {
+                UserError::InvalidCredentials => (
+                    StatusCode::UNAUTHORIZED,
+                    "invalid_credentials",
+                    err.to_string(),
+                ),
+                UserError::EmailTaken => (StatusCode::CONFLICT, "email_taken", err.to_string()),
+                UserError::NotFound => (StatusCode::NOT_FOUND, "user_not_found", err.to_string()),
+            },
+            AppError::Unauthorized(err) => match err {
+                AuthError::MissingToken => {
+                    (StatusCode::UNAUTHORIZED, "missing_token", err.to_string())
+                }
+                AuthError::InvalidToken => {
+                    (StatusCode::UNAUTHORIZED, "invalid_token", err.to_string())
+                }
+            },
+            AppError::Forbidden(forbidden) => (
+                StatusCode::FORBIDDEN,
+                "forbidden",
+                forbidden.reason.to_string(),
+            ),
+            AppError::Validation(_) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "validation_failed",
+                "Some fields are invalid, see `fields`".to_string(),
+            ),
+            AppError::InvalidPath(rejection) => {
+                (rejection.status(), "invalid_path", rejection.body_text())
+            }
+            AppError::InvalidQuery(rejection) => {
+                (rejection.status(), "invalid_query", rejection.body_text())
+            }
+            AppError::InvalidBody(rejection) => {
+                (rejection.status(), "invalid_body", rejection.body_text())
+            }
+            AppError::RouteNotFound => (
+                StatusCode::NOT_FOUND,
+                "route_not_found",
+                "The requested page could not be found".to_string(),
+            ),
+            AppError::Database(err) => classify_database_error(err),
+            AppError::Any(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Something went wrong on our end".to_string(),
+            ),
+        }
+    }
+}
+
+/// Maps what the database reported onto what the client did wrong, if anything.
+/// Postgres error codes are listed at https://www.postgresql.org/docs/current/errcodes-appendix.html
+/// and SQLite's extended result codes at https://www.sqlite.org/rescode.html
+fn classify_database_error(err: &Error) -> (StatusCode, &'static str, String) {
+    match err {
+        Error::RowNotFound => (
+            StatusCode::NOT_FOUND,
+            "not_found",
+            "The requested resource does not exist".to_string(),
+        ),
+        Error::Database(db_err) => match db_err.code().as_deref() {
+            Some("23505" | "2067" | "1555") => (
+                StatusCode::CONFLICT,
+                "conflict",
+                "This conflicts with an existing record".to_string(),
+            ),
+            // Every foreign key cascades or nulls out on delete, so only inserts and
+            // updates pointing at a missing row can trip one
+            Some("23503" | "787") => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "invalid_reference",
+                "This refers to a record that does not exist".to_string(),
+            ),
+            Some("23502" | "23514" | "22001" | "1299" | "275") => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "constraint_violation",
+                "A value breaks one of the rules for this record".to_string(),
+            ),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                "Something went wrong on our end".to_string(),
+            ),
+        },
+        Error::PoolTimedOut | Error::PoolClosed | Error::Io(_) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "service_unavailable",
+            "The database is unavailable, try again shortly".to_string(),
+        ),
+        _ => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            "Something went wrong on our end".to_string(),
+        ),
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let (status, code, detail) = self.classify();
+
+        // Clients only ever get `detail`, the underlying error stays in the logs
+        if status.is_server_error() {
+            error!(code, "{:?}", self);
+        } else if let AppError::Database(err) = &self {
+            warn!(code, "{:?}", err);
+        }
+
+        let mut extensions = serde_json::Map::new();
+        match self {
+            AppError::Forbidden(forbidden) => {
+                extensions.insert("reason".into(), json!(forbidden.reason));
+                extensions.insert("allowed_roles".into(), json!(forbidden.allowed_roles));
+            }
+            AppError::Validation(fields) => {
+                extensions.insert("fields".into(), json!(fields));
             }
+            _ => {}
+        }
+
+        let problem = Problem {
+            problem_type: "about:blank",
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            code,
+            detail,
+            extensions,
         };
 
-        let body = Json(json!({ "error": error_message }));
-        (status, body).into_response()
+        let mut response = (status, Json(problem)).into_response();
+        response.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+        if status == StatusCode::SERVICE_UNAVAILABLE {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, RETRY_AFTER_SECS.into());
+        }
+
+        response
     }
 }
 
@@ -40,3 +347,43 @@ impl From<Error> for AppError {
         AppError::Database(value)
     }
 }
+
+impl From<PathRejection> for AppError {
+    fn from(value: PathRejection) -> Self {
+        AppError::InvalidPath(value)
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(value: QueryRejection) -> Self {
+        AppError::InvalidQuery(value)
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(value: JsonRejection) -> Self {
+        AppError::InvalidBody(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_rows_are_not_found() {
+        let response = AppError::Database(Error::RowNotFound).into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/problem+json");
+    }
+
+    #[test]
+    fn pool_timeouts_ask_clients_to_retry() {
+        let response = AppError::Database(Error::PoolTimedOut).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            response.headers()[RETRY_AFTER],
+            RETRY_AFTER_SECS.to_string()
+        );
+    }
+}
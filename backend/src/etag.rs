@@ -0,0 +1,111 @@
+use http::{HeaderMap, HeaderName, HeaderValue};
+
+/// The ETag sent for a question at `version` whose JSON is `body`, as `"<version>-<hash>"`.
+/// Votes, accepted answers and tag merges change the body without an edit bumping the
+/// version, so the hash is what `If-None-Match` goes by. `If-Match` only looks at the version,
+/// so a vote coming in doesn't turn away someone's edit.
+pub fn question_etag(version: i32, body: &[u8]) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{}-{:016x}\"", version, fnv1a(body)))
+        .expect("a quoted number and hex digits are a valid header")
+}
+
+// FNV-1a, which unlike std's hasher gives the same tag across builds and restarts
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The version a strong tag from `question_etag` was made for. A bare `"<version>"` is taken
+/// too, so clients can name a version without having fetched it.
+fn tag_version(tag: &str) -> Option<i32> {
+    let opaque = tag.strip_prefix('"')?.strip_suffix('"')?;
+    let version = opaque
+        .split_once('-')
+        .map_or(opaque, |(version, _)| version);
+    version.parse().ok()
+}
+
+/// A parsed `If-Match` or `If-None-Match` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EtagCondition {
+    /// `*`, met by any current version
+    Any,
+    /// The listed entity tags, as sent
+    Tags(Vec<String>),
+}
+
+impl EtagCondition {
+    /// The condition in header `name`, or None when the client didn't send one. The header
+    /// may be repeated or hold a comma separated list.
+    pub fn from_headers(headers: &HeaderMap, name: HeaderName) -> Option<Self> {
+        let mut tags = Vec::new();
+        for value in headers.get_all(name) {
+            // Anything unreadable is kept as a tag that can never match
+            let value = value.to_str().unwrap_or("\0");
+            for tag in value
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+            {
+                if tag == "*" {
+                    return Some(EtagCondition::Any);
+                }
+                tags.push(tag.to_string());
+            }
+        }
+
+        if tags.is_empty() {
+            None
+        } else {
+            Some(EtagCondition::Tags(tags))
+        }
+    }
+
+    /// Whether a strong tag names `version`, for `If-Match`. Weak tags never match.
+    pub fn matches(&self, version: i32) -> bool {
+        match self {
+            EtagCondition::Any => true,
+            EtagCondition::Tags(tags) => tags.iter().any(|tag| tag_version(tag) == Some(version)),
+        }
+    }
+
+    /// Weak comparison against the current `etag`, as `If-None-Match` requires,
+    /// so `W/"3-..."` matches `"3-..."` too
+    pub fn matches_weak(&self, etag: &HeaderValue) -> bool {
+        match self {
+            EtagCondition::Any => true,
+            EtagCondition::Tags(tags) => {
+                tags.iter().any(|tag| etag == tag.trim_start_matches("W/"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::header::IF_MATCH;
+
+    fn condition(value: &str) -> Option<EtagCondition> {
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_MATCH, HeaderValue::from_str(value).unwrap());
+        EtagCondition::from_headers(&headers, IF_MATCH)
+    }
+
+    #[test]
+    fn parses_lists_and_wildcards() {
+        assert_eq!(
+            EtagCondition::from_headers(&HeaderMap::new(), IF_MATCH),
+            None
+        );
+        assert_eq!(condition("*"), Some(EtagCondition::Any));
+
+        let etag = question_etag(2, b"{}");
+        let listed = condition(&format!("\"1\", W/{}", etag.to_str().unwrap())).unwrap();
+        assert!(listed.matches(1));
+        assert!(!listed.matches(2));
+        assert!(listed.matches_weak(&etag));
+        assert!(!listed.matches_weak(&question_etag(2, b"{\"score\":1}")));
+    }
+}
@@ -0,0 +1,30 @@
+use axum::response::{IntoResponse, Response};
+use axum_macros::{FromRequest, FromRequestParts};
+use serde::Serialize;
+
+use crate::error::AppError;
+
+// axum's own extractors answer bad input with a plain text body. These wrap them so the
+// client gets the same problem+json, with a stable `code`, as for every other error.
+
+/// `axum::extract::Path`, rejecting with `AppError::InvalidPath`
+#[derive(Debug, FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(AppError))]
+pub struct Path<T>(pub T);
+
+/// `axum::extract::Query`, rejecting with `AppError::InvalidQuery`
+#[derive(Debug, FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(AppError))]
+pub struct Query<T>(pub T);
+
+/// `axum::Json`, rejecting with `AppError::InvalidBody`. Bodies that have a `Validate` impl
+/// go through `validation::ValidJson` instead.
+#[derive(Debug, FromRequest)]
+#[from_request(via(axum::Json), rejection(AppError))]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
@@ -1,13 +1,26 @@
-use axum::extract::{Path, Query, State};
-use axum::Json;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use http::header::{CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH, LINK};
+use http::{HeaderMap, HeaderValue, StatusCode};
 
-use crate::answer::{Answer, CreateAnswer};
-use crate::comment::{CommentDbResult, CreateComment};
-use crate::db::Store;
-use crate::error::AppError;
+use crate::answer::{Answer, AnswerId, CreateAnswer, UpdateAnswer};
+use crate::auth::{hash_password, issue_token, verify_password, AuthBody, AuthUser, Claims};
+use crate::comment::{CommentDbResult, CommentId, CreateComment, UpdateComment};
+use crate::error::{AppError, UserError};
+use crate::etag::{question_etag, EtagCondition};
+use crate::extract::{Json, Path, Query};
+use crate::pagination::{link_header, Page};
 use crate::question::{
-    CreateQuestion, GetQuestionById, Question, QuestionId, QuestionResult, UpdateQuestion,
+    CreateQuestion, GetQuestionById, Question, QuestionId, QuestionListQuery, QuestionResult,
+    TagMatch, UpdateQuestion,
 };
+use crate::repository::Repository;
+use crate::revision::{AnswerRevision, DiffQuery, QuestionRevision, RevisionDiff};
+use crate::search::{SearchQuery, SearchResult};
+use crate::tag::{check_source_slug, slugify, MergeTag, Tag, TagMerge};
+use crate::user::{normalize_email, CreateUser, LoginUser, UpdateRole, User, UserId};
+use crate::validation::ValidJson;
+use crate::vote::{CastVote, VoteSummary, VoteTarget};
 
 #[allow(dead_code)]
 pub async fn root() -> String {
@@ -15,80 +28,469 @@ pub async fn root() -> String {
 }
 
 // CRUD create - read - update - delete
-pub async fn get_questions(
-    State(mut am_database): State<Store>,
-) -> Result<Json<Vec<Question>>, AppError> {
-    let all_questions = am_database.get_all_questions().await?;
+pub async fn get_questions<S: Repository>(
+    State(mut am_database): State<S>,
+    Query(query): Query<QuestionListQuery>, // localhost:3000/questions?sort=oldest&limit=10
+) -> Result<(HeaderMap, Json<Page<Question>>), AppError> {
+    let (params, filter) = query.parse()?;
+    let page = am_database.get_all_questions(&params, &filter).await?;
 
-    Ok(Json(all_questions))
+    let mut headers = HeaderMap::new();
+    if let Some(link) = link_header("/questions", &query.filter_query_string(), &params, &page) {
+        headers.insert(LINK, link);
+    }
+
+    Ok((headers, Json(page)))
+}
+
+pub async fn get_tags<S: Repository>(
+    State(mut am_database): State<S>,
+) -> Result<Json<Vec<Tag>>, AppError> {
+    let tags = am_database.get_tags().await?;
+    Ok(Json(tags))
+}
+
+pub async fn get_tag_questions<S: Repository>(
+    State(mut am_database): State<S>,
+    Path(slug): Path<String>, // localhost:3000/tags/rust/questions?sort=votes
+    Query(query): Query<QuestionListQuery>,
+) -> Result<(HeaderMap, Json<Page<Question>>), AppError> {
+    let tag = am_database.get_tag(&slugify(&slug)).await?;
+
+    // Any `tag` in the query string is ignored, the path decides
+    let (params, mut filter) = query.parse()?;
+    filter.tags = vec![tag.slug.clone()];
+    filter.tag_match = TagMatch::All;
+    let page = am_database.get_all_questions(&params, &filter).await?;
+
+    let query = QuestionListQuery {
+        tag: None,
+        tag_match: None,
+        ..query
+    };
+    let path = format!("/tags/{}/questions", tag.slug);
+    let mut headers = HeaderMap::new();
+    if let Some(link) = link_header(&path, &query.filter_query_string(), &params, &page) {
+        headers.insert(LINK, link);
+    }
+
+    Ok((headers, Json(page)))
 }
 
-pub async fn get_question_by_id(
-    State(mut am_database): State<Store>,
+pub async fn merge_tag<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    Path(slug): Path<String>, // localhost:3000/tags/js/merge
+    ValidJson(merge): ValidJson<MergeTag>,
+) -> Result<Json<TagMerge>, AppError> {
+    check_source_slug(&slug)?;
+    let merged = am_database.merge_tags(&slug, merge, &user).await?;
+    Ok(Json(merged))
+}
+
+pub async fn search<S: Repository>(
+    State(mut am_database): State<S>,
+    Query(query): Query<SearchQuery>, // localhost:3000/search?q=borrow+checker
+) -> Result<(HeaderMap, Json<Page<SearchResult>>), AppError> {
+    let (terms, params) = query.parse()?;
+    let page = am_database.search(&terms, &params).await?;
+
+    let mut headers = HeaderMap::new();
+    if let Some(link) = link_header("/search", &query.filter_query_string(), &params, &page) {
+        headers.insert(LINK, link);
+    }
+
+    Ok((headers, Json(page)))
+}
+
+pub async fn get_question_by_id<S: Repository>(
+    State(mut am_database): State<S>,
     Path(query): Path<i32>, // localhost:3000/question/5
-) -> Result<Json<Question>, AppError> {
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
     let question = am_database.get_question_by_id(QuestionId(query)).await?;
-    Ok(Json(question))
+    let (etag, body) = question_with_etag(&question)?;
+
+    // The client's copy is still current
+    if let Some(if_none_match) = EtagCondition::from_headers(&headers, IF_NONE_MATCH) {
+        if if_none_match.matches_weak(&etag) {
+            return Ok((StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response());
+        }
+    }
+
+    Ok(([(ETAG, etag), (CONTENT_TYPE, json_content_type())], body).into_response())
+}
+
+/// `question` as JSON along with its ETag, which is computed over that exact body
+fn question_with_etag(question: &Question) -> Result<(HeaderValue, Vec<u8>), AppError> {
+    let body = serde_json::to_vec(question).map_err(|err| AppError::Any(err.into()))?;
+    Ok((question_etag(question.version, &body), body))
 }
 
-pub async fn get_question_comments(
-    State(mut am_database): State<Store>,
+fn json_content_type() -> HeaderValue {
+    HeaderValue::from_static(mime::APPLICATION_JSON.as_ref())
+}
+
+pub async fn get_question_comments<S: Repository>(
+    State(mut am_database): State<S>,
     Path(query): Path<i32>, // localhost:3000/question_comments/5
 ) -> Result<Json<QuestionResult>, AppError> {
-    let question = am_database.get_question_comments(query).await;
+    let question = am_database.get_question_comments(query).await?;
     Ok(Json(question))
 }
 
-pub async fn create_question(
-    State(mut am_database): State<Store>,
-    Json(question): Json<CreateQuestion>,
+pub async fn create_question<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    ValidJson(question): ValidJson<CreateQuestion>,
 ) -> Result<Json<()>, AppError> {
     let _ = am_database
-        .add_question(question.title, question.content, question.tags)
+        .add_question(question.title, question.content, question.tags, user.id)
         .await?;
 
     Ok(Json(()))
 }
 
-pub async fn update_question(
-    State(mut am_database): State<Store>,
-    Json(question): Json<UpdateQuestion>,
-) -> Result<Json<Question>, AppError> {
-    let updated_question = am_database.update_question(question).await?;
-    Ok(Json(updated_question))
+pub async fn update_question<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    headers: HeaderMap,
+    ValidJson(question): ValidJson<UpdateQuestion>,
+) -> Result<Response, AppError> {
+    let if_match = EtagCondition::from_headers(&headers, IF_MATCH);
+    let updated_question = am_database
+        .update_question(question, if_match.as_ref(), &user)
+        .await?;
+
+    let (etag, body) = question_with_etag(&updated_question)?;
+    Ok(([(ETAG, etag), (CONTENT_TYPE, json_content_type())], body).into_response())
 }
 
-pub async fn delete_question(
-    State(mut am_database): State<Store>,
+pub async fn delete_question<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
     Query(query): Query<GetQuestionById>,
 ) -> Result<(), AppError> {
-    am_database.delete_question(query.question_id).await?;
+    am_database
+        .delete_question(query.question_id, &user)
+        .await?;
 
     Ok(())
 }
 
-pub async fn create_answer(
-    State(mut am_database): State<Store>,
-    Json(answer): Json<CreateAnswer>,
+pub async fn undelete_question<S: Repository>(
+    State(mut am_database): State<S>,
+    Path(question_id): Path<i32>, // localhost:3000/question/5/undelete
+) -> Result<Json<Question>, AppError> {
+    let question = am_database
+        .undelete_question(QuestionId(question_id))
+        .await?;
+    Ok(Json(question))
+}
+
+pub async fn create_answer<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    ValidJson(answer): ValidJson<CreateAnswer>,
 ) -> Result<Json<Answer>, AppError> {
     dbg!("GOT CREATE ANSWER:");
     dbg!(&answer);
     let new_answer = am_database
-        .add_answer(answer.content, answer.question_id)
+        .add_answer(answer.content, answer.question_id, user.id)
         .await?;
     Ok(Json(new_answer))
 }
 
-pub async fn create_comment(
-    State(mut am_database): State<Store>,
-    Json(comment): Json<CreateComment>,
+pub async fn get_question_answers<S: Repository>(
+    State(mut am_database): State<S>,
+    Path(question_id): Path<i32>, // localhost:3000/question/5/answers
+) -> Result<Json<Vec<Answer>>, AppError> {
+    let answers = am_database
+        .get_answers_for_question(QuestionId(question_id))
+        .await?;
+    Ok(Json(answers))
+}
+
+pub async fn get_answer_by_id<S: Repository>(
+    State(mut am_database): State<S>,
+    Path(answer_id): Path<i32>, // localhost:3000/answer/5
+) -> Result<Json<Answer>, AppError> {
+    let answer = am_database.get_answer_by_id(AnswerId(answer_id)).await?;
+    Ok(Json(answer))
+}
+
+pub async fn update_answer<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    Path(answer_id): Path<i32>,
+    ValidJson(answer): ValidJson<UpdateAnswer>,
+) -> Result<Json<Answer>, AppError> {
+    let updated_answer = am_database
+        .update_answer(AnswerId(answer_id), answer.content, &user)
+        .await?;
+    Ok(Json(updated_answer))
+}
+
+pub async fn delete_answer<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    Path(answer_id): Path<i32>,
+) -> Result<(), AppError> {
+    am_database
+        .delete_answer(AnswerId(answer_id), &user)
+        .await?;
+    Ok(())
+}
+
+pub async fn accept_answer<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    Path((question_id, answer_id)): Path<(i32, i32)>, // localhost:3000/question/5/accept/7
+) -> Result<Json<QuestionResult>, AppError> {
+    let thread = am_database
+        .accept_answer(QuestionId(question_id), AnswerId(answer_id), &user)
+        .await?;
+    Ok(Json(thread))
+}
+
+pub async fn unaccept_answer<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    Path(question_id): Path<i32>, // localhost:3000/question/5/accept
+) -> Result<Json<QuestionResult>, AppError> {
+    let thread = am_database
+        .unaccept_answer(QuestionId(question_id), &user)
+        .await?;
+    Ok(Json(thread))
+}
+
+pub async fn get_question_revisions<S: Repository>(
+    State(mut am_database): State<S>,
+    Path(question_id): Path<i32>, // localhost:3000/question/5/revisions
+) -> Result<Json<Vec<QuestionRevision>>, AppError> {
+    let revisions = am_database
+        .get_question_revisions(QuestionId(question_id))
+        .await?;
+    Ok(Json(revisions))
+}
+
+pub async fn diff_question_revisions<S: Repository>(
+    State(mut am_database): State<S>,
+    Path(question_id): Path<i32>,
+    Query(diff): Query<DiffQuery>, // localhost:3000/question/5/revisions/diff?from=1&to=3
+) -> Result<Json<RevisionDiff>, AppError> {
+    let diff = am_database
+        .diff_question_revisions(QuestionId(question_id), diff.from, diff.to)
+        .await?;
+    Ok(Json(diff))
+}
+
+pub async fn rollback_question<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    Path((question_id, revision)): Path<(i32, i32)>, // localhost:3000/question/5/revisions/2/rollback
+) -> Result<Json<Question>, AppError> {
+    let question = am_database
+        .rollback_question(QuestionId(question_id), revision, &user)
+        .await?;
+    Ok(Json(question))
+}
+
+pub async fn get_answer_revisions<S: Repository>(
+    State(mut am_database): State<S>,
+    Path(answer_id): Path<i32>, // localhost:3000/answer/5/revisions
+) -> Result<Json<Vec<AnswerRevision>>, AppError> {
+    let revisions = am_database
+        .get_answer_revisions(AnswerId(answer_id))
+        .await?;
+    Ok(Json(revisions))
+}
+
+pub async fn diff_answer_revisions<S: Repository>(
+    State(mut am_database): State<S>,
+    Path(answer_id): Path<i32>,
+    Query(diff): Query<DiffQuery>, // localhost:3000/answer/5/revisions/diff?from=1&to=3
+) -> Result<Json<RevisionDiff>, AppError> {
+    let diff = am_database
+        .diff_answer_revisions(AnswerId(answer_id), diff.from, diff.to)
+        .await?;
+    Ok(Json(diff))
+}
+
+pub async fn rollback_answer<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    Path((answer_id, revision)): Path<(i32, i32)>, // localhost:3000/answer/5/revisions/2/rollback
+) -> Result<Json<Answer>, AppError> {
+    let answer = am_database
+        .rollback_answer(AnswerId(answer_id), revision, &user)
+        .await?;
+    Ok(Json(answer))
+}
+
+pub async fn vote_on_question<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    Path(question_id): Path<i32>, // localhost:3000/question/5/vote
+    Json(vote): Json<CastVote>,
+) -> Result<Json<VoteSummary>, AppError> {
+    let summary = am_database
+        .cast_vote(
+            VoteTarget::Question(QuestionId(question_id)),
+            user.id,
+            vote.direction,
+        )
+        .await?;
+    Ok(Json(summary))
+}
+
+pub async fn retract_question_vote<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    Path(question_id): Path<i32>,
+) -> Result<Json<VoteSummary>, AppError> {
+    let summary = am_database
+        .retract_vote(VoteTarget::Question(QuestionId(question_id)), user.id)
+        .await?;
+    Ok(Json(summary))
+}
+
+pub async fn vote_on_answer<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    Path(answer_id): Path<i32>, // localhost:3000/answer/5/vote
+    Json(vote): Json<CastVote>,
+) -> Result<Json<VoteSummary>, AppError> {
+    let summary = am_database
+        .cast_vote(
+            VoteTarget::Answer(AnswerId(answer_id)),
+            user.id,
+            vote.direction,
+        )
+        .await?;
+    Ok(Json(summary))
+}
+
+pub async fn retract_answer_vote<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    Path(answer_id): Path<i32>,
+) -> Result<Json<VoteSummary>, AppError> {
+    let summary = am_database
+        .retract_vote(VoteTarget::Answer(AnswerId(answer_id)), user.id)
+        .await?;
+    Ok(Json(summary))
+}
+
+pub async fn create_comment<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    ValidJson(comment): ValidJson<CreateComment>,
 ) -> Result<Json<CommentDbResult>, AppError> {
     let result = am_database
-        .add_comment(
-            comment.content,
-            Some(comment.applied_to_question_id),
-            Some(comment.applied_to_answer_id),
-        )
+        .add_comment(comment.content, comment.target, user.id)
         .await?;
     Ok(Json(result))
 }
+
+pub async fn get_question_comment_list<S: Repository>(
+    State(mut am_database): State<S>,
+    Path(question_id): Path<i32>, // localhost:3000/question/5/comments
+) -> Result<Json<Vec<CommentDbResult>>, AppError> {
+    let comments = am_database
+        .get_comments_for_question(QuestionId(question_id))
+        .await?;
+    Ok(Json(comments))
+}
+
+pub async fn get_answer_comments<S: Repository>(
+    State(mut am_database): State<S>,
+    Path(answer_id): Path<i32>, // localhost:3000/answer/5/comments
+) -> Result<Json<Vec<CommentDbResult>>, AppError> {
+    let comments = am_database
+        .get_comments_for_answer(AnswerId(answer_id))
+        .await?;
+    Ok(Json(comments))
+}
+
+pub async fn update_comment<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    Path(comment_id): Path<i32>,
+    ValidJson(comment): ValidJson<UpdateComment>,
+) -> Result<Json<CommentDbResult>, AppError> {
+    let updated_comment = am_database
+        .update_comment(CommentId(comment_id), comment.content, &user)
+        .await?;
+    Ok(Json(updated_comment))
+}
+
+pub async fn delete_comment<S: Repository>(
+    State(mut am_database): State<S>,
+    user: AuthUser,
+    Path(comment_id): Path<i32>,
+) -> Result<(), AppError> {
+    am_database
+        .delete_comment(CommentId(comment_id), &user)
+        .await?;
+    Ok(())
+}
+
+pub async fn register<S: Repository>(
+    State(mut am_database): State<S>,
+    ValidJson(credentials): ValidJson<CreateUser>,
+) -> Result<Json<User>, AppError> {
+    let password_hash = hash_password(credentials.password).await?;
+
+    let user = am_database
+        .add_user(normalize_email(&credentials.email), password_hash)
+        .await?;
+
+    Ok(Json(user))
+}
+
+pub async fn login<S: Repository>(
+    State(mut am_database): State<S>,
+    Json(credentials): Json<LoginUser>,
+) -> Result<Json<AuthBody>, AppError> {
+    let existing = am_database
+        .get_user_by_email(&normalize_email(&credentials.email))
+        .await?
+        .ok_or(AppError::User(UserError::InvalidCredentials))?;
+
+    let is_valid = verify_password(credentials.password, existing.password_hash.clone()).await?;
+    if !is_valid {
+        return Err(AppError::User(UserError::InvalidCredentials));
+    }
+
+    let user: User = existing.into();
+    let token = issue_token(&Claims::new(&user))?;
+
+    Ok(Json(token))
+}
+
+pub async fn get_users<S: Repository>(
+    State(mut am_database): State<S>,
+) -> Result<Json<Vec<User>>, AppError> {
+    let users = am_database.get_all_users().await?;
+    Ok(Json(users))
+}
+
+pub async fn update_user_role<S: Repository>(
+    State(mut am_database): State<S>,
+    Path(user_id): Path<i32>, // localhost:3000/admin/users/5/role
+    Json(update): Json<UpdateRole>,
+) -> Result<Json<User>, AppError> {
+    let user = am_database
+        .set_user_role(UserId(user_id), update.role)
+        .await?;
+    Ok(Json(user))
+}
+
+pub async fn delete_user<S: Repository>(
+    State(mut am_database): State<S>,
+    Path(user_id): Path<i32>, // localhost:3000/admin/users/5
+) -> Result<(), AppError> {
+    am_database.delete_user(UserId(user_id)).await?;
+    Ok(())
+}
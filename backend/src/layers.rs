@@ -1,15 +1,43 @@
-use http::Method;
+use axum::extract::{FromRequestParts, MatchedPath, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use http::request::Parts;
+use http::{Method, Request};
 use tower_http::classify::{ServerErrorsAsFailures, SharedClassifier};
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::trace::TraceLayer;
 
-pub fn get_layers() -> (
+use crate::auth::AuthUser;
+use crate::error::{AppError, AuthError, Forbidden};
+use crate::repository::Repository;
+use crate::user::Role;
+
+const ANY_ROLE: &[Role] = &[Role::User, Role::Moderator, Role::Admin];
+const STAFF_ONLY: &[Role] = &[Role::Moderator, Role::Admin];
+const ADMIN_ONLY: &[Role] = &[Role::Admin];
+
+/// Who may call a route: anyone at all, or a logged in user holding one of the listed roles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Public,
+    Roles(&'static [Role]),
+}
+
+pub fn get_layers(
+    allow_origin: AllowOrigin,
+) -> (
     CorsLayer,
     TraceLayer<SharedClassifier<ServerErrorsAsFailures>>,
 ) {
     let cors_layer = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_headers([http::header::CONTENT_TYPE])
+        .allow_origin(allow_origin)
+        .allow_headers([
+            http::header::CONTENT_TYPE,
+            http::header::AUTHORIZATION,
+            http::header::IF_MATCH,
+            http::header::IF_NONE_MATCH,
+        ])
+        .expose_headers([http::header::ETAG])
         .allow_methods([
             Method::GET,
             Method::POST,
@@ -22,3 +50,87 @@ pub fn get_layers() -> (
 
     (cors_layer, trace_layer)
 }
+
+/// The policy table for every route registered in routes::app, keyed on the route *pattern*
+/// (`/question/:question_id`, not `/question/5`). Anything not listed here is admin only,
+/// so forgetting to add a new route fails closed.
+pub fn route_policy(method: &Method, path: &str) -> Access {
+    match (method.as_str(), path) {
+        (_, path) if path.starts_with("/admin/") => Access::Roles(ADMIN_ONLY),
+        ("GET", _) | (_, "/*_") => Access::Public,
+        ("POST", "/register" | "/login") => Access::Public,
+        ("POST" | "PUT" | "DELETE", "/question") => Access::Roles(ANY_ROLE),
+        ("POST", "/answer" | "/comment") => Access::Roles(ANY_ROLE),
+        ("PUT" | "DELETE", "/answer/:answer_id" | "/comment/:comment_id") => {
+            Access::Roles(ANY_ROLE)
+        }
+        ("POST" | "DELETE", "/question/:question_id/vote" | "/answer/:answer_id/vote") => {
+            Access::Roles(ANY_ROLE)
+        }
+        ("POST", "/question/:question_id/accept/:answer_id") => Access::Roles(ANY_ROLE),
+        ("POST", "/tags/:slug/merge" | "/question/:question_id/undelete") => {
+            Access::Roles(STAFF_ONLY)
+        }
+        (
+            "POST",
+            "/question/:question_id/revisions/:revision/rollback"
+            | "/answer/:answer_id/revisions/:revision/rollback",
+        ) => Access::Roles(ANY_ROLE),
+        ("DELETE", "/question/:question_id/accept") => Access::Roles(ANY_ROLE),
+        _ => Access::Roles(ADMIN_ONLY),
+    }
+}
+
+/// Applies `route_policy` before the handler runs. Anonymous callers on a protected route
+/// get a 401, logged in users without an allowed role get a 403. The validated user is stashed
+/// in the request extensions so handlers extracting `AuthUser` don't decode the token again.
+///
+/// Roles are checked against the store rather than the token, so demoting or deleting a user
+/// takes effect on their next request instead of when their token expires.
+pub async fn enforce_policy<S: Repository, B>(
+    State(db): State<S>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let (mut parts, body) = request.into_parts();
+
+    match authorize(&mut parts, db).await {
+        Ok(Some(user)) => {
+            parts.extensions.insert(user);
+        }
+        Ok(None) => {}
+        Err(err) => return err.into_response(),
+    }
+
+    next.run(Request::from_parts(parts, body)).await
+}
+
+async fn authorize<S: Repository>(
+    parts: &mut Parts,
+    mut db: S,
+) -> Result<Option<AuthUser>, AppError> {
+    let access = match parts.extensions.get::<MatchedPath>() {
+        Some(path) => route_policy(&parts.method, path.as_str()),
+        None => Access::Roles(ADMIN_ONLY),
+    };
+
+    let allowed_roles = match access {
+        Access::Public => return Ok(None),
+        Access::Roles(roles) => roles,
+    };
+
+    let claimed = AuthUser::from_request_parts(parts, &()).await?;
+    // A deleted user's token is as good as a forged one
+    let user: AuthUser = db
+        .get_user_by_id(claimed.id)
+        .await?
+        .ok_or(AppError::Unauthorized(AuthError::InvalidToken))?
+        .into();
+    if !allowed_roles.contains(&user.role) {
+        return Err(AppError::Forbidden(Forbidden::insufficient_role(
+            allowed_roles,
+        )));
+    }
+
+    Ok(Some(user))
+}
@@ -1,59 +1,121 @@
-use std::net::{IpAddr, SocketAddr};
-use std::str::FromStr;
-
-use dotenvy::dotenv;
+use anyhow::Context;
+use sqlx::PgPool;
 use tracing::info;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
 
-use crate::db::new_pool;
+use crate::config::{Config, FeatureConfig, LogConfig, LogFormat, Storage};
+use crate::db::{new_pool, Store};
+use crate::memory::MemoryStore;
+use crate::purge::spawn_purge_task;
+use crate::repository::Repository;
 
 pub mod answer;
+pub mod auth;
 pub mod comment;
+pub mod config;
 pub mod db;
 pub mod error;
+pub mod etag;
+pub mod extract;
 pub mod handlers;
 pub mod layers;
+pub mod memory;
+pub mod migrations;
+pub mod pagination;
+pub mod purge;
 pub mod question;
+pub mod repository;
+pub mod revision;
 pub mod routes;
+pub mod search;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod tag;
+pub mod user;
+pub mod validation;
+pub mod vote;
+
+/// Serves the API as `config` describes, until the server fails
+pub async fn run_backend(config: Config) -> anyhow::Result<()> {
+    config.check_serving()?;
+    init_logging(&config.log);
+    auth::init_keys(&config.auth.jwt_secret);
 
-pub async fn run_backend() {
-    dotenv().ok();
-    init_logging();
+    match config.database.storage {
+        Storage::Memory => serve(MemoryStore::default(), &config).await,
+        Storage::Postgres => {
+            let pool = new_pool(&config.database)
+                .await
+                .context("Could not connect to Postgres at database.url")?;
+            prepare_database(&pool, &config.features).await?;
+            serve(Store::with_pool(pool), &config).await
+        }
+        #[cfg(feature = "sqlite")]
+        Storage::Sqlite => {
+            let pool = sqlite::new_sqlite_pool(&config.database)
+                .await
+                .context("Could not open the SQLite database at database.sqlite_url")?;
+            if config.features.migrate_on_start {
+                sqlite::migrate_up(&pool)
+                    .await
+                    .context("Could not apply the SQLite migrations")?;
+                info!("Database schema is up to date");
+            }
+            serve(sqlite::SqliteStore::with_pool(pool), &config).await
+        }
+        #[cfg(not(feature = "sqlite"))]
+        Storage::Sqlite => anyhow::bail!("This build has no sqlite feature"),
+    }
+}
 
-    let addr = get_host_from_env();
+async fn serve<S: Repository>(store: S, config: &Config) -> anyhow::Result<()> {
+    if config.features.purge_deleted {
+        spawn_purge_task(store.clone(), (&config.purge).into());
+    }
 
-    let app = routes::app(new_pool().await).await;
+    let allow_origin = config.cors.allow_origin().map_err(anyhow::Error::msg)?;
+    let app = routes::app_with_cors(store, allow_origin).await;
 
-    info!("Listening...");
+    let addr = config.server.bind_address();
+    let server =
+        axum::Server::try_bind(&addr).with_context(|| format!("Could not listen on {addr}"))?;
+    info!("Listening on {addr}");
 
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+    server.serve(app.into_make_service()).await?;
+    Ok(())
 }
 
-fn get_host_from_env() -> SocketAddr {
-    let host = std::env::var("API_HOST").unwrap();
-    let api_host = IpAddr::from_str(&host).unwrap();
-    let api_port: u16 = std::env::var("API_PORT")
-        .expect("Could not find API_PORT in .env file")
-        .parse()
-        .expect("Can't create a u16 from the given API_PORT string");
+/// Applies pending migrations and loads the demo rows, as far as `features` asks for them
+async fn prepare_database(pool: &PgPool, features: &FeatureConfig) -> anyhow::Result<()> {
+    if features.migrate_on_start {
+        migrations::migrate_up(pool)
+            .await
+            .context("Could not apply the database migrations")?;
+        info!("Database schema is up to date");
+    }
 
-    SocketAddr::from((api_host, api_port))
+    if features.seed_dev_data
+        && migrations::seed_dev_data(pool)
+            .await
+            .context("Could not load the demo rows")?
+    {
+        info!("Loaded the demo rows from seeds/dev.sql");
+    }
+
+    Ok(())
 }
 
-fn init_logging() {
+fn init_logging(config: &LogConfig) {
     // https://github.com/tokio-rs/axum/blob/main/examples/tracing-aka-logging
+    // Config::load has already checked that the filter parses
+    let filter = EnvFilter::try_new(&config.filter).unwrap_or_default();
+    let json = config.format == LogFormat::Json;
+
     tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
-                // axum logs rejections from built-in extractors with the `axum::rejection`
-                // target, at `TRACE` level. `axum::rejection=trace` enables showing those events
-                "backend=trace,tower_http=debug,axum::rejection=trace".into()
-            }),
-        )
-        .with(tracing_subscriber::fmt::layer())
+        .with(filter)
+        .with(json.then(|| tracing_subscriber::fmt::layer().json()))
+        .with((!json).then(tracing_subscriber::fmt::layer))
         .init();
 }
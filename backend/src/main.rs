@@ -1,6 +1,86 @@
+use std::process::ExitCode;
+
+use anyhow::{bail, Context};
+use backend::config::{Config, Storage};
+use backend::db::new_pool;
+use backend::migrations::{migrate_down, migrate_up, migration_status, seed_dev_data};
 use backend::run_backend;
+use dotenvy::dotenv;
+
+const USAGE: &str = "usage: backend [migrate up|down|status|seed]";
 
 #[tokio::main]
-async fn main() {
-    run_backend().await;
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    if !matches!(args.as_slice(), [] | ["migrate", _]) {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    }
+
+    dotenv().ok();
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = match args.as_slice() {
+        ["migrate", command] => migrate(&config, command).await,
+        _ => run_backend(config).await,
+    };
+
+    if let Err(err) = result {
+        eprintln!("{err:#}");
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// `up` applies pending migrations, `down` reverts the latest one, `status` lists them all
+/// and `seed` loads the demo rows into an empty database
+async fn migrate(config: &Config, command: &str) -> anyhow::Result<()> {
+    if config.database.storage != Storage::Postgres {
+        bail!("backend migrate only manages the Postgres schema, STORAGE is not postgres");
+    }
+    let pool = new_pool(&config.database)
+        .await
+        .context("Could not connect to Postgres at database.url")?;
+
+    match command {
+        "up" => {
+            migrate_up(&pool).await?;
+            println!("Database schema is up to date");
+        }
+        "down" => match migrate_down(&pool).await? {
+            Some(version) => println!("Reverted {version}"),
+            None => println!("No applied migrations to revert"),
+        },
+        "status" => {
+            for migration in migration_status(&pool).await? {
+                let state = if migration.applied {
+                    "applied"
+                } else {
+                    "pending"
+                };
+                println!(
+                    "{} {:<7} {}",
+                    migration.version, state, migration.description
+                );
+            }
+        }
+        "seed" => {
+            if seed_dev_data(&pool).await? {
+                println!("Loaded the demo rows from seeds/dev.sql");
+            } else {
+                println!("The database already has questions, not seeding");
+            }
+        }
+        _ => bail!(USAGE),
+    }
+
+    Ok(())
 }
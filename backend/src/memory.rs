@@ -0,0 +1,1314 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockWriteGuard};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::answer::{Answer, AnswerId, AnswerResult};
+use crate::auth::AuthUser;
+use crate::comment::{CommentDbResult, CommentId, CommentResult, CommentTarget};
+use crate::error::{
+    AnswerError, AppError, CommentError, FieldError, Forbidden, QuestionError, RevisionError,
+    TagError, UserError,
+};
+use crate::etag::EtagCondition;
+use crate::pagination::{self, Cursor, Page, PageParams};
+use crate::purge::PurgeCounts;
+use crate::question::{
+    IntoQuestionId, Question, QuestionFilter, QuestionId, QuestionResult, QuestionSort, TagMatch,
+    UpdateQuestion,
+};
+use crate::repository::Repository;
+use crate::revision::{AnswerRevision, QuestionRevision, RevisionDiff};
+use crate::search::{SearchEntity, SearchResult, TermSearch};
+use crate::tag::{normalize_tags, slugify, MergeTag, Tag, TagMerge};
+use crate::user::{Role, User, UserDbResult, UserId};
+use crate::vote::{VoteDirection, VoteSummary, VoteTarget};
+
+/// A `Repository` kept entirely in process memory, so the API can run without a database,
+/// e.g. for front-end work and demos. It starts out empty and is gone when the process exits.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    pub questions: Arc<Mutex<Vec<Question>>>,
+    pub answers: Arc<RwLock<Vec<Answer>>>,
+    tables: Arc<Mutex<Tables>>,
+}
+
+/// Everything besides the questions and answers themselves
+#[derive(Default)]
+struct Tables {
+    next_question_id: i32,
+    next_answer_id: i32,
+    next_comment_id: i32,
+    next_user_id: i32,
+    deleted_questions: HashMap<QuestionId, DateTime<Utc>>,
+    deleted_answers: HashMap<AnswerId, DateTime<Utc>>,
+    accepted_answers: HashMap<QuestionId, AnswerId>,
+    comments: Vec<StoredComment>,
+    votes: Vec<StoredVote>,
+    /// Every known tag slug, including ones no question carries any more
+    tags: BTreeSet<String>,
+    /// Synonym slug to the slug it stands for
+    synonyms: HashMap<String, String>,
+    merges: Vec<TagMerge>,
+    question_revisions: HashMap<QuestionId, Vec<QuestionRevision>>,
+    answer_revisions: HashMap<AnswerId, Vec<AnswerRevision>>,
+    users: Vec<UserDbResult>,
+}
+
+struct StoredComment {
+    comment: CommentDbResult,
+    deleted_at: Option<DateTime<Utc>>,
+}
+
+struct StoredVote {
+    user_id: UserId,
+    target: VoteTarget,
+    direction: VoteDirection,
+}
+
+fn next_id(counter: &mut i32) -> i32 {
+    *counter += 1;
+    *counter
+}
+
+impl MemoryStore {
+    /// Locks all of the store for one operation, always in the same order
+    fn lock(&self) -> Db<'_> {
+        Db {
+            questions: self
+                .questions
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+            answers: self.answers.write().unwrap_or_else(PoisonError::into_inner),
+            tables: self.tables.lock().unwrap_or_else(PoisonError::into_inner),
+        }
+    }
+}
+
+/// The locked store. Every operation runs start to finish on one of these, which makes it
+/// as atomic as a transaction would be.
+struct Db<'a> {
+    questions: MutexGuard<'a, Vec<Question>>,
+    answers: RwLockWriteGuard<'a, Vec<Answer>>,
+    tables: MutexGuard<'a, Tables>,
+}
+
+impl Db<'_> {
+    fn live_question(&self, id: QuestionId) -> Result<Question, AppError> {
+        self.questions
+            .iter()
+            .find(|question| question.id == id)
+            .filter(|question| !self.tables.deleted_questions.contains_key(&question.id))
+            .map(|question| self.with_score(question))
+            .ok_or(AppError::Question(QuestionError::InvalidId))
+    }
+
+    fn live_answer(&self, id: AnswerId) -> Result<Answer, AppError> {
+        self.answers
+            .iter()
+            .find(|answer| answer.id == id)
+            .filter(|answer| !self.tables.deleted_answers.contains_key(&answer.id))
+            .cloned()
+            .ok_or(AppError::Answer(AnswerError::InvalidId))
+    }
+
+    fn live_answers(&self, question_id: QuestionId) -> Vec<Answer> {
+        let mut answers: Vec<Answer> = self
+            .answers
+            .iter()
+            .filter(|answer| answer.question_id == question_id)
+            .filter(|answer| !self.tables.deleted_answers.contains_key(&answer.id))
+            .cloned()
+            .collect();
+        answers.sort_by_key(|answer| (answer.created_on, answer.id.0));
+        answers
+    }
+
+    fn live_comment(&self, id: CommentId) -> Result<&StoredComment, AppError> {
+        self.tables
+            .comments
+            .iter()
+            .find(|stored| stored.comment.id == id && stored.deleted_at.is_none())
+            .ok_or(AppError::Comment(CommentError::InvalidId))
+    }
+
+    fn live_comments(&self, target: CommentTarget) -> Vec<CommentDbResult> {
+        let (question_id, answer_id) = match target {
+            CommentTarget::Question(id) => (Some(id.0), None),
+            CommentTarget::Answer(id) => (None, Some(id.0)),
+        };
+
+        let mut comments: Vec<CommentDbResult> = self
+            .tables
+            .comments
+            .iter()
+            .filter(|stored| stored.deleted_at.is_none())
+            .map(|stored| &stored.comment)
+            .filter(|comment| {
+                comment.applied_to_question_id == question_id
+                    && comment.applied_to_answer_id == answer_id
+            })
+            .cloned()
+            .collect();
+        comments.sort_by_key(|comment| (comment.created_on, comment.id.0));
+        comments
+    }
+
+    fn score(&self, target: VoteTarget) -> i64 {
+        self.tables
+            .votes
+            .iter()
+            .filter(|vote| vote.target == target)
+            .map(|vote| vote.direction.value() as i64)
+            .sum()
+    }
+
+    fn with_score(&self, question: &Question) -> Question {
+        Question {
+            score: self.score(VoteTarget::Question(question.id)),
+            ..question.clone()
+        }
+    }
+
+    fn question_mut(&mut self, id: QuestionId) -> &mut Question {
+        self.questions
+            .iter_mut()
+            .find(|question| question.id == id)
+            .expect("question was looked up before")
+    }
+
+    fn answer_mut(&mut self, id: AnswerId) -> &mut Answer {
+        self.answers
+            .iter_mut()
+            .find(|answer| answer.id == id)
+            .expect("answer was looked up before")
+    }
+
+    fn resolve_tags(&mut self, tags: &[String]) -> Vec<String> {
+        let resolved: Vec<String> = normalize_tags(tags)
+            .into_iter()
+            .map(|slug| self.tables.synonyms.get(&slug).cloned().unwrap_or(slug))
+            .collect();
+        let mut slugs = normalize_tags(&resolved);
+        slugs.sort();
+        self.tables.tags.extend(slugs.iter().cloned());
+        slugs
+    }
+
+    fn record_question_revision(&mut self, question_id: QuestionId, editor_id: UserId) {
+        let question = self.question_mut(question_id).clone();
+        let revisions = self
+            .tables
+            .question_revisions
+            .entry(question_id)
+            .or_default();
+        revisions.push(QuestionRevision {
+            revision: revisions.len() as i32 + 1,
+            title: question.title,
+            content: question.content,
+            tags: question.tags.unwrap_or_default(),
+            editor_id: Some(editor_id.0),
+            edited_on: pagination::now(),
+        });
+    }
+
+    fn record_answer_revision(&mut self, answer_id: AnswerId, editor_id: UserId) {
+        let content = self.answer_mut(answer_id).content.clone();
+        let revisions = self.tables.answer_revisions.entry(answer_id).or_default();
+        revisions.push(AnswerRevision {
+            revision: revisions.len() as i32 + 1,
+            content,
+            editor_id: Some(editor_id.0),
+            edited_on: pagination::now(),
+        });
+    }
+
+    fn apply_question_edit(
+        &mut self,
+        question_id: QuestionId,
+        title: &str,
+        content: &str,
+        tags: &[String],
+        editor_id: UserId,
+    ) -> Result<Question, AppError> {
+        let tags = self.resolve_tags(tags);
+        let question = self.question_mut(question_id);
+        question.title = title.to_string();
+        question.content = content.to_string();
+        question.tags = Some(tags);
+        question.version += 1;
+
+        self.record_question_revision(question_id, editor_id);
+        self.live_question(question_id)
+    }
+
+    fn apply_answer_edit(
+        &mut self,
+        answer_id: AnswerId,
+        content: &str,
+        editor_id: UserId,
+    ) -> Result<Answer, AppError> {
+        self.answer_mut(answer_id).content = content.to_string();
+        self.record_answer_revision(answer_id, editor_id);
+        self.live_answer(answer_id)
+    }
+
+    fn question_revision(
+        &self,
+        question_id: QuestionId,
+        revision: i32,
+    ) -> Result<QuestionRevision, AppError> {
+        self.tables
+            .question_revisions
+            .get(&question_id)
+            .and_then(|revisions| revisions.iter().find(|r| r.revision == revision))
+            .cloned()
+            .ok_or(AppError::Revision(RevisionError::NotFound))
+    }
+
+    fn answer_revision(
+        &self,
+        answer_id: AnswerId,
+        revision: i32,
+    ) -> Result<AnswerRevision, AppError> {
+        self.tables
+            .answer_revisions
+            .get(&answer_id)
+            .and_then(|revisions| revisions.iter().find(|r| r.revision == revision))
+            .cloned()
+            .ok_or(AppError::Revision(RevisionError::NotFound))
+    }
+
+    fn thread(&self, question_id: QuestionId) -> Result<QuestionResult, AppError> {
+        let question = self.live_question(question_id)?;
+        let accepted = self.tables.accepted_answers.get(&question_id).copied();
+
+        let to_results = |comments: Vec<CommentDbResult>| -> Vec<CommentResult> {
+            let mut comments: Vec<CommentResult> = comments
+                .into_iter()
+                .map(|comment| CommentResult {
+                    id: comment.id.0,
+                    content: comment.content,
+                    author_id: comment.author_id,
+                    created_on: comment.created_on,
+                })
+                .collect();
+            comments.sort_by_key(|comment| Reverse(comment.created_on));
+            comments
+        };
+
+        let mut answers: Vec<AnswerResult> = self
+            .live_answers(question_id)
+            .into_iter()
+            .map(|answer| AnswerResult {
+                id: answer.id.0,
+                content: answer.content,
+                author_id: answer.author_id.map(|id| id.0),
+                created_on: answer.created_on,
+                score: self.score(VoteTarget::Answer(answer.id)),
+                comments: to_results(self.live_comments(CommentTarget::Answer(answer.id))),
+            })
+            .collect();
+        answers.sort_by_key(|answer| {
+            (
+                Reverse(Some(AnswerId(answer.id)) == accepted),
+                Reverse(answer.score),
+                Reverse(answer.created_on),
+                Reverse(answer.id),
+            )
+        });
+
+        Ok(QuestionResult {
+            id: question.id.0,
+            title: question.title,
+            content: question.content,
+            tags: question.tags,
+            author_id: question.author_id.map(|id| id.0),
+            created_on: question.created_on,
+            score: question.score,
+            accepted_answer_id: accepted.map(|id| id.0),
+            comments: to_results(self.live_comments(CommentTarget::Question(question_id))),
+            answers,
+        })
+    }
+
+    fn tag(&self, slug: &str) -> Result<Tag, AppError> {
+        let slug = match self.tables.synonyms.get(slug) {
+            Some(target) if !self.tables.tags.contains(slug) => target.as_str(),
+            _ => slug,
+        };
+        if !self.tables.tags.contains(slug) {
+            return Err(AppError::Tag(TagError::NotFound));
+        }
+
+        Ok(Tag {
+            slug: slug.to_string(),
+            question_count: self.tag_count(slug),
+        })
+    }
+
+    fn tag_count(&self, slug: &str) -> i64 {
+        self.questions
+            .iter()
+            .filter(|question| !self.tables.deleted_questions.contains_key(&question.id))
+            .filter(|question| carries(question, slug))
+            .count() as i64
+    }
+
+    fn vote_summary(&self, target: VoteTarget, user_id: UserId) -> VoteSummary {
+        VoteSummary {
+            score: self.score(target),
+            vote: self
+                .tables
+                .votes
+                .iter()
+                .find(|vote| vote.target == target && vote.user_id == user_id)
+                .map(|vote| vote.direction),
+        }
+    }
+
+    fn check_vote_target(&self, target: VoteTarget) -> Result<(), AppError> {
+        match target {
+            VoteTarget::Question(question_id) => self.live_question(question_id).map(|_| ()),
+            VoteTarget::Answer(answer_id) => self.live_answer(answer_id).map(|_| ()),
+        }
+    }
+
+    /// Drops a question, its answers and everything hanging off them, as the cascades would
+    fn remove_question(&mut self, question_id: QuestionId) {
+        let answer_ids: Vec<AnswerId> = self
+            .answers
+            .iter()
+            .filter(|answer| answer.question_id == question_id)
+            .map(|answer| answer.id)
+            .collect();
+        for answer_id in answer_ids {
+            self.remove_answer(answer_id);
+        }
+
+        self.questions.retain(|question| question.id != question_id);
+        let tables = &mut *self.tables;
+        tables.deleted_questions.remove(&question_id);
+        tables.accepted_answers.remove(&question_id);
+        tables.question_revisions.remove(&question_id);
+        tables
+            .comments
+            .retain(|stored| stored.comment.applied_to_question_id != Some(question_id.0));
+        tables
+            .votes
+            .retain(|vote| vote.target != VoteTarget::Question(question_id));
+    }
+
+    fn remove_answer(&mut self, answer_id: AnswerId) {
+        self.answers.retain(|answer| answer.id != answer_id);
+        let tables = &mut *self.tables;
+        tables.deleted_answers.remove(&answer_id);
+        tables
+            .accepted_answers
+            .retain(|_, accepted| *accepted != answer_id);
+        tables.answer_revisions.remove(&answer_id);
+        tables
+            .comments
+            .retain(|stored| stored.comment.applied_to_answer_id != Some(answer_id.0));
+        tables
+            .votes
+            .retain(|vote| vote.target != VoteTarget::Answer(answer_id));
+    }
+}
+
+fn carries(question: &Question, slug: &str) -> bool {
+    question
+        .tags
+        .as_ref()
+        .is_some_and(|tags| tags.iter().any(|tag| tag == slug))
+}
+
+#[async_trait]
+impl Repository for MemoryStore {
+    async fn add_answer(
+        &mut self,
+        content: String,
+        question_id: i32,
+        author_id: UserId,
+    ) -> Result<Answer, AppError> {
+        let mut db = self.lock();
+        let question = db.live_question(QuestionId(question_id))?;
+
+        let answer = Answer {
+            id: AnswerId(next_id(&mut db.tables.next_answer_id)),
+            content,
+            question_id: question.id,
+            author_id: Some(author_id),
+            created_on: pagination::now(),
+        };
+        db.answers.push(answer.clone());
+        db.record_answer_revision(answer.id, author_id);
+
+        Ok(answer)
+    }
+
+    async fn get_answers_for_question(
+        &mut self,
+        question_id: QuestionId,
+    ) -> Result<Vec<Answer>, AppError> {
+        let db = self.lock();
+        db.live_question(question_id)?;
+        Ok(db.live_answers(question_id))
+    }
+
+    async fn get_answer_by_id(&mut self, answer_id: AnswerId) -> Result<Answer, AppError> {
+        self.lock().live_answer(answer_id)
+    }
+
+    async fn update_answer(
+        &mut self,
+        answer_id: AnswerId,
+        content: String,
+        user: &AuthUser,
+    ) -> Result<Answer, AppError> {
+        let mut db = self.lock();
+        let existing = db.live_answer(answer_id)?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        db.apply_answer_edit(answer_id, &content, user.id)
+    }
+
+    async fn delete_answer(
+        &mut self,
+        answer_id: AnswerId,
+        user: &AuthUser,
+    ) -> Result<(), AppError> {
+        let mut db = self.lock();
+        let existing = db.live_answer(answer_id)?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let now = pagination::now();
+        let tables = &mut *db.tables;
+        tables.deleted_answers.insert(answer_id, now);
+        for stored in tables.comments.iter_mut() {
+            if stored.comment.applied_to_answer_id == Some(answer_id.0) {
+                stored.deleted_at.get_or_insert(now);
+            }
+        }
+        tables
+            .accepted_answers
+            .retain(|_, accepted| *accepted != answer_id);
+
+        Ok(())
+    }
+
+    async fn get_all_questions(
+        &mut self,
+        params: &PageParams,
+        filter: &QuestionFilter,
+    ) -> Result<Page<Question>, AppError> {
+        let cursor = match &params.cursor {
+            Some(cursor) => Some(Cursor::decode(cursor).ok_or_else(|| {
+                AppError::Validation(vec![FieldError::new("cursor", "is not a valid cursor")])
+            })?),
+            None => None,
+        };
+        let limit = params.limit() as usize;
+        let offset = if cursor.is_some() {
+            0
+        } else {
+            params.offset() as usize
+        };
+
+        let db = self.lock();
+        let answer_count = |question: &Question| db.live_answers(question.id).len();
+
+        let mut questions: Vec<Question> = db
+            .questions
+            .iter()
+            .filter(|question| !db.tables.deleted_questions.contains_key(&question.id))
+            .filter(|question| {
+                let carried = filter
+                    .tags
+                    .iter()
+                    .filter(|tag| carries(question, tag))
+                    .count();
+                match filter.tag_match {
+                    _ if filter.tags.is_empty() => true,
+                    TagMatch::All => carried == filter.tags.len(),
+                    TagMatch::Any => carried > 0,
+                }
+            })
+            .filter(|question| {
+                filter
+                    .author
+                    .is_none_or(|author| question.author_id == Some(author))
+            })
+            .filter(|question| {
+                filter
+                    .created_after
+                    .is_none_or(|after| question.created_on > after)
+            })
+            .filter(|question| {
+                filter
+                    .created_before
+                    .is_none_or(|before| question.created_on < before)
+            })
+            .filter(|question| {
+                filter
+                    .has_answers
+                    .is_none_or(|has_answers| (answer_count(question) > 0) == has_answers)
+            })
+            .map(|question| db.with_score(question))
+            .collect();
+        let total = questions.len() as i64;
+
+        let newest = |question: &Question| Reverse((question.created_on, question.id.0));
+        match filter.sort {
+            QuestionSort::Newest => questions.sort_by_key(newest),
+            QuestionSort::Oldest => {
+                questions.sort_by_key(|question| (question.created_on, question.id.0))
+            }
+            QuestionSort::MostAnswers => questions
+                .sort_by_key(|question| (Reverse(answer_count(question)), newest(question))),
+            QuestionSort::Unanswered => {
+                questions.sort_by_key(|question| (answer_count(question) > 0, newest(question)))
+            }
+            QuestionSort::Votes => {
+                questions.sort_by_key(|question| (Reverse(question.score), newest(question)))
+            }
+        }
+
+        if let Some(cursor) = cursor {
+            let position = (cursor.created_on, cursor.id);
+            questions.retain(|question| {
+                let key = (question.created_on, question.id.0);
+                if filter.sort == QuestionSort::Oldest {
+                    key > position
+                } else {
+                    key < position
+                }
+            });
+        }
+
+        let mut questions: Vec<Question> =
+            questions.into_iter().skip(offset).take(limit + 1).collect();
+        let has_more = questions.len() > limit;
+        questions.truncate(limit);
+
+        let next_cursor = match questions.last() {
+            Some(last) if has_more && filter.sort.supports_cursor() => Some(
+                Cursor {
+                    created_on: last.created_on,
+                    id: last.id.0,
+                }
+                .encode(),
+            ),
+            _ => None,
+        };
+
+        Ok(Page {
+            items: questions,
+            next_cursor,
+            total,
+        })
+    }
+
+    async fn get_question_by_id<T: IntoQuestionId + Send>(
+        &mut self,
+        id: T,
+    ) -> Result<Question, AppError> {
+        self.lock().live_question(id.into_question_id())
+    }
+
+    async fn get_question_comments(
+        &mut self,
+        question_id: i32,
+    ) -> Result<QuestionResult, AppError> {
+        self.lock().thread(QuestionId(question_id))
+    }
+
+    async fn add_question(
+        &mut self,
+        title: String,
+        content: String,
+        tags: Option<Vec<String>>,
+        author_id: UserId,
+    ) -> Result<Question, AppError> {
+        let mut db = self.lock();
+        let tags = db.resolve_tags(&tags.unwrap_or_default());
+
+        let question = Question {
+            id: QuestionId(next_id(&mut db.tables.next_question_id)),
+            title,
+            content,
+            tags: Some(tags),
+            author_id: Some(author_id),
+            created_on: pagination::now(),
+            score: 0,
+            version: 1,
+        };
+        db.questions.push(question.clone());
+        db.record_question_revision(question.id, author_id);
+
+        Ok(question)
+    }
+
+    async fn update_question(
+        &mut self,
+        new_question: UpdateQuestion,
+        if_match: Option<&EtagCondition>,
+        user: &AuthUser,
+    ) -> Result<Question, AppError> {
+        let mut db = self.lock();
+        let existing = db.live_question(new_question.id)?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        if let Some(if_match) = if_match {
+            if !if_match.matches(existing.version) {
+                return Err(AppError::Question(QuestionError::VersionMismatch));
+            }
+        }
+
+        db.apply_question_edit(
+            new_question.id,
+            &new_question.title,
+            &new_question.content,
+            &new_question.tags.unwrap_or_default(),
+            user.id,
+        )
+    }
+
+    async fn get_question_revisions(
+        &mut self,
+        question_id: QuestionId,
+    ) -> Result<Vec<QuestionRevision>, AppError> {
+        let db = self.lock();
+        db.live_question(question_id)?;
+        Ok(db
+            .tables
+            .question_revisions
+            .get(&question_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn get_question_revision(
+        &mut self,
+        question_id: QuestionId,
+        revision: i32,
+    ) -> Result<QuestionRevision, AppError> {
+        self.lock().question_revision(question_id, revision)
+    }
+
+    async fn diff_question_revisions(
+        &mut self,
+        question_id: QuestionId,
+        from: i32,
+        to: i32,
+    ) -> Result<RevisionDiff, AppError> {
+        let db = self.lock();
+        db.live_question(question_id)?;
+
+        let from = db.question_revision(question_id, from)?;
+        let to = db.question_revision(question_id, to)?;
+
+        Ok(RevisionDiff::between_questions(&from, &to))
+    }
+
+    async fn rollback_question(
+        &mut self,
+        question_id: QuestionId,
+        revision: i32,
+        user: &AuthUser,
+    ) -> Result<Question, AppError> {
+        let mut db = self.lock();
+        let existing = db.live_question(question_id)?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let revision = db.question_revision(question_id, revision)?;
+        db.apply_question_edit(
+            question_id,
+            &revision.title,
+            &revision.content,
+            &revision.tags,
+            user.id,
+        )
+    }
+
+    async fn get_answer_revisions(
+        &mut self,
+        answer_id: AnswerId,
+    ) -> Result<Vec<AnswerRevision>, AppError> {
+        let db = self.lock();
+        db.live_answer(answer_id)?;
+        Ok(db
+            .tables
+            .answer_revisions
+            .get(&answer_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn get_answer_revision(
+        &mut self,
+        answer_id: AnswerId,
+        revision: i32,
+    ) -> Result<AnswerRevision, AppError> {
+        self.lock().answer_revision(answer_id, revision)
+    }
+
+    async fn diff_answer_revisions(
+        &mut self,
+        answer_id: AnswerId,
+        from: i32,
+        to: i32,
+    ) -> Result<RevisionDiff, AppError> {
+        let db = self.lock();
+        db.live_answer(answer_id)?;
+
+        let from = db.answer_revision(answer_id, from)?;
+        let to = db.answer_revision(answer_id, to)?;
+
+        Ok(RevisionDiff::between_answers(&from, &to))
+    }
+
+    async fn rollback_answer(
+        &mut self,
+        answer_id: AnswerId,
+        revision: i32,
+        user: &AuthUser,
+    ) -> Result<Answer, AppError> {
+        let mut db = self.lock();
+        let existing = db.live_answer(answer_id)?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let revision = db.answer_revision(answer_id, revision)?;
+        db.apply_answer_edit(answer_id, &revision.content, user.id)
+    }
+
+    async fn get_tags(&mut self) -> Result<Vec<Tag>, AppError> {
+        let db = self.lock();
+        let mut tags: Vec<Tag> = db
+            .tables
+            .tags
+            .iter()
+            .map(|slug| Tag {
+                slug: slug.clone(),
+                question_count: db.tag_count(slug),
+            })
+            .collect();
+        // The set is already in slug order and the sort is stable
+        tags.sort_by_key(|tag| Reverse(tag.question_count));
+
+        Ok(tags)
+    }
+
+    async fn get_tag(&mut self, slug: &str) -> Result<Tag, AppError> {
+        self.lock().tag(slug)
+    }
+
+    async fn merge_tags(
+        &mut self,
+        source: &str,
+        merge: MergeTag,
+        user: &AuthUser,
+    ) -> Result<TagMerge, AppError> {
+        let mut db = self.lock();
+        let source = slugify(source);
+        let target = db.tag(&slugify(&merge.into))?;
+        if source.is_empty() || source == target.slug {
+            return Err(AppError::Validation(vec![FieldError::new(
+                "into",
+                "must name a different tag than the one being merged",
+            )]));
+        }
+
+        let mut retagged = Vec::new();
+        if db.tables.tags.remove(&source) {
+            for question in db.questions.iter_mut() {
+                if let Some(tags) = question.tags.as_mut() {
+                    if tags.contains(&source) {
+                        tags.retain(|tag| *tag != source);
+                        if !tags.contains(&target.slug) {
+                            tags.push(target.slug.clone());
+                            tags.sort();
+                        }
+                        // Retagging changes the question like an edit would
+                        question.version += 1;
+                        retagged.push(question.id);
+                    }
+                }
+            }
+            for question_id in &retagged {
+                db.record_question_revision(*question_id, user.id);
+            }
+
+            // Synonyms of the old tag now point at the new one
+            for synonym_of in db.tables.synonyms.values_mut() {
+                if *synonym_of == source {
+                    *synonym_of = target.slug.clone();
+                }
+            }
+        }
+
+        db.tables
+            .synonyms
+            .insert(source.clone(), target.slug.clone());
+
+        let merged = TagMerge {
+            source_slug: source,
+            target_slug: target.slug,
+            question_count: retagged.len() as i32,
+            merged_by: Some(user.id.0),
+            merged_on: pagination::now(),
+        };
+        db.tables.merges.push(merged.clone());
+
+        Ok(merged)
+    }
+
+    async fn delete_question(&mut self, question_id: i32, user: &AuthUser) -> Result<(), AppError> {
+        let question_id = QuestionId(question_id);
+        let mut db = self.lock();
+        let existing = db.live_question(question_id)?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        // One timestamp for the question and everything under it, see undelete_question
+        let now = pagination::now();
+        let answer_ids: Vec<AnswerId> = db
+            .answers
+            .iter()
+            .filter(|answer| answer.question_id == question_id)
+            .map(|answer| answer.id)
+            .collect();
+
+        let tables = &mut *db.tables;
+        tables.deleted_questions.insert(question_id, now);
+        for answer_id in &answer_ids {
+            tables.deleted_answers.entry(*answer_id).or_insert(now);
+        }
+        for stored in tables.comments.iter_mut() {
+            let on_question = stored.comment.applied_to_question_id == Some(question_id.0);
+            let on_answer = stored
+                .comment
+                .applied_to_answer_id
+                .is_some_and(|id| answer_ids.contains(&AnswerId(id)));
+            if on_question || on_answer {
+                stored.deleted_at.get_or_insert(now);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn undelete_question(&mut self, question_id: QuestionId) -> Result<Question, AppError> {
+        let mut db = self.lock();
+        if !db
+            .questions
+            .iter()
+            .any(|question| question.id == question_id)
+        {
+            return Err(AppError::Question(QuestionError::InvalidId));
+        }
+
+        if let Some(deleted_at) = db.tables.deleted_questions.remove(&question_id) {
+            let answer_ids: Vec<AnswerId> = db
+                .answers
+                .iter()
+                .filter(|answer| answer.question_id == question_id)
+                .map(|answer| answer.id)
+                .collect();
+
+            let tables = &mut *db.tables;
+            tables
+                .deleted_answers
+                .retain(|id, at| !(answer_ids.contains(id) && *at == deleted_at));
+            for stored in tables.comments.iter_mut() {
+                let on_question = stored.comment.applied_to_question_id == Some(question_id.0);
+                let on_answer = stored
+                    .comment
+                    .applied_to_answer_id
+                    .is_some_and(|id| answer_ids.contains(&AnswerId(id)));
+                if (on_question || on_answer) && stored.deleted_at == Some(deleted_at) {
+                    stored.deleted_at = None;
+                }
+            }
+        }
+
+        db.live_question(question_id)
+    }
+
+    async fn purge_deleted(&mut self, cutoff: DateTime<Utc>) -> Result<PurgeCounts, AppError> {
+        let mut db = self.lock();
+
+        let questions: Vec<QuestionId> = db
+            .tables
+            .deleted_questions
+            .iter()
+            .filter(|(_, deleted_at)| **deleted_at < cutoff)
+            .map(|(id, _)| *id)
+            .collect();
+        for question_id in &questions {
+            db.remove_question(*question_id);
+        }
+
+        let answers: Vec<AnswerId> = db
+            .tables
+            .deleted_answers
+            .iter()
+            .filter(|(_, deleted_at)| **deleted_at < cutoff)
+            .map(|(id, _)| *id)
+            .collect();
+        for answer_id in &answers {
+            db.remove_answer(*answer_id);
+        }
+
+        let before = db.tables.comments.len();
+        db.tables.comments.retain(|stored| {
+            stored
+                .deleted_at
+                .is_none_or(|deleted_at| deleted_at >= cutoff)
+        });
+        let comments = before - db.tables.comments.len();
+
+        Ok(PurgeCounts {
+            questions: questions.len() as u64,
+            answers: answers.len() as u64,
+            comments: comments as u64,
+        })
+    }
+
+    async fn accept_answer(
+        &mut self,
+        question_id: QuestionId,
+        answer_id: AnswerId,
+        user: &AuthUser,
+    ) -> Result<QuestionResult, AppError> {
+        let mut db = self.lock();
+        let question = db.live_question(question_id)?;
+        if question.author_id != Some(user.id) {
+            return Err(AppError::Forbidden(Forbidden::not_question_author()));
+        }
+
+        let answer = db.live_answer(answer_id)?;
+        if answer.question_id != question_id {
+            return Err(AppError::Answer(AnswerError::NotOnQuestion));
+        }
+
+        db.tables.accepted_answers.insert(question_id, answer_id);
+        db.thread(question_id)
+    }
+
+    async fn unaccept_answer(
+        &mut self,
+        question_id: QuestionId,
+        user: &AuthUser,
+    ) -> Result<QuestionResult, AppError> {
+        let mut db = self.lock();
+        let question = db.live_question(question_id)?;
+        if question.author_id != Some(user.id) {
+            return Err(AppError::Forbidden(Forbidden::not_question_author()));
+        }
+
+        db.tables.accepted_answers.remove(&question_id);
+        db.thread(question_id)
+    }
+
+    async fn cast_vote(
+        &mut self,
+        target: VoteTarget,
+        user_id: UserId,
+        direction: VoteDirection,
+    ) -> Result<VoteSummary, AppError> {
+        let mut db = self.lock();
+        db.check_vote_target(target)?;
+
+        let votes = &mut db.tables.votes;
+        match votes
+            .iter_mut()
+            .find(|vote| vote.target == target && vote.user_id == user_id)
+        {
+            Some(vote) => vote.direction = direction,
+            None => votes.push(StoredVote {
+                user_id,
+                target,
+                direction,
+            }),
+        }
+
+        Ok(db.vote_summary(target, user_id))
+    }
+
+    async fn retract_vote(
+        &mut self,
+        target: VoteTarget,
+        user_id: UserId,
+    ) -> Result<VoteSummary, AppError> {
+        let mut db = self.lock();
+        db.check_vote_target(target)?;
+
+        db.tables
+            .votes
+            .retain(|vote| !(vote.target == target && vote.user_id == user_id));
+
+        Ok(db.vote_summary(target, user_id))
+    }
+
+    async fn add_comment(
+        &mut self,
+        content: String,
+        target: CommentTarget,
+        author_id: UserId,
+    ) -> Result<CommentDbResult, AppError> {
+        let mut db = self.lock();
+        let (question_id, answer_id) = match target {
+            CommentTarget::Question(question_id) => {
+                db.live_question(question_id)?;
+                (Some(question_id.0), None)
+            }
+            CommentTarget::Answer(answer_id) => {
+                db.live_answer(answer_id)?;
+                (None, Some(answer_id.0))
+            }
+        };
+
+        let comment = CommentDbResult {
+            id: CommentId(next_id(&mut db.tables.next_comment_id)),
+            content,
+            applied_to_question_id: question_id,
+            applied_to_answer_id: answer_id,
+            author_id: Some(author_id.0),
+            created_on: pagination::now().naive_utc(),
+        };
+        db.tables.comments.push(StoredComment {
+            comment: comment.clone(),
+            deleted_at: None,
+        });
+
+        Ok(comment)
+    }
+
+    async fn get_comments_for_question(
+        &mut self,
+        question_id: QuestionId,
+    ) -> Result<Vec<CommentDbResult>, AppError> {
+        let db = self.lock();
+        db.live_question(question_id)?;
+        Ok(db.live_comments(CommentTarget::Question(question_id)))
+    }
+
+    async fn get_comments_for_answer(
+        &mut self,
+        answer_id: AnswerId,
+    ) -> Result<Vec<CommentDbResult>, AppError> {
+        let db = self.lock();
+        db.live_answer(answer_id)?;
+        Ok(db.live_comments(CommentTarget::Answer(answer_id)))
+    }
+
+    async fn get_comment_by_id(
+        &mut self,
+        comment_id: CommentId,
+    ) -> Result<CommentDbResult, AppError> {
+        Ok(self.lock().live_comment(comment_id)?.comment.clone())
+    }
+
+    async fn update_comment(
+        &mut self,
+        comment_id: CommentId,
+        content: String,
+        user: &AuthUser,
+    ) -> Result<CommentDbResult, AppError> {
+        let mut db = self.lock();
+        let existing = db.live_comment(comment_id)?;
+        if !user.can_modify(existing.comment.author_id.map(UserId)) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let stored = db
+            .tables
+            .comments
+            .iter_mut()
+            .find(|stored| stored.comment.id == comment_id)
+            .expect("comment was looked up before");
+        stored.comment.content = content;
+
+        Ok(stored.comment.clone())
+    }
+
+    async fn delete_comment(
+        &mut self,
+        comment_id: CommentId,
+        user: &AuthUser,
+    ) -> Result<(), AppError> {
+        let mut db = self.lock();
+        let existing = db.live_comment(comment_id)?;
+        if !user.can_modify(existing.comment.author_id.map(UserId)) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let stored = db
+            .tables
+            .comments
+            .iter_mut()
+            .find(|stored| stored.comment.id == comment_id)
+            .expect("comment was looked up before");
+        stored.deleted_at = Some(pagination::now());
+
+        Ok(())
+    }
+
+    async fn search(
+        &mut self,
+        terms: &str,
+        params: &PageParams,
+    ) -> Result<Page<SearchResult>, AppError> {
+        let search = TermSearch::parse(terms);
+        let db = self.lock();
+
+        let mut hits: Vec<(SearchEntity, i32, Question, String)> = Vec::new();
+        for question in db.questions.iter() {
+            if let Ok(question) = db.live_question(question.id) {
+                let body = format!("{} {}", question.title, question.content);
+                hits.push((SearchEntity::Question, question.id.0, question, body));
+            }
+        }
+        for answer in db.answers.iter() {
+            if let (Ok(answer), Ok(question)) = (
+                db.live_answer(answer.id),
+                db.live_question(answer.question_id),
+            ) {
+                hits.push((SearchEntity::Answer, answer.id.0, question, answer.content));
+            }
+        }
+        for stored in db
+            .tables
+            .comments
+            .iter()
+            .filter(|stored| stored.deleted_at.is_none())
+        {
+            let comment = &stored.comment;
+            let question_id = match (comment.applied_to_question_id, comment.applied_to_answer_id) {
+                (Some(question_id), _) => Ok(QuestionId(question_id)),
+                (None, Some(answer_id)) => db
+                    .live_answer(AnswerId(answer_id))
+                    .map(|answer| answer.question_id),
+                (None, None) => continue,
+            };
+            if let Ok(question) = question_id.and_then(|id| db.live_question(id)) {
+                hits.push((
+                    SearchEntity::Comment,
+                    comment.id.0,
+                    question,
+                    comment.content.clone(),
+                ));
+            }
+        }
+
+        let results = hits
+            .into_iter()
+            .filter_map(|(entity_type, id, question, body)| {
+                search.hit(entity_type, id, question.id.0, question.title, &body)
+            })
+            .collect();
+
+        Ok(search.page(results, params))
+    }
+
+    async fn add_user(&mut self, email: String, password_hash: String) -> Result<User, AppError> {
+        let mut db = self.lock();
+        if db.tables.users.iter().any(|user| user.email == email) {
+            return Err(AppError::User(UserError::EmailTaken));
+        }
+
+        let user = UserDbResult {
+            id: next_id(&mut db.tables.next_user_id),
+            email,
+            password_hash,
+            role: Role::User.as_str().to_string(),
+            created_on: pagination::now(),
+        };
+        db.tables.users.push(user.clone());
+
+        Ok(user.into())
+    }
+
+    async fn get_user_by_email(&mut self, email: &str) -> Result<Option<UserDbResult>, AppError> {
+        let db = self.lock();
+        Ok(db
+            .tables
+            .users
+            .iter()
+            .find(|user| user.email == email)
+            .cloned())
+    }
+
+    async fn get_user_by_id(&mut self, user_id: UserId) -> Result<Option<User>, AppError> {
+        let db = self.lock();
+        Ok(db
+            .tables
+            .users
+            .iter()
+            .find(|user| user.id == user_id.0)
+            .cloned()
+            .map(User::from))
+    }
+
+    async fn get_all_users(&mut self) -> Result<Vec<User>, AppError> {
+        let db = self.lock();
+        Ok(db.tables.users.iter().cloned().map(User::from).collect())
+    }
+
+    async fn set_user_role(&mut self, user_id: UserId, role: Role) -> Result<User, AppError> {
+        let mut db = self.lock();
+        let user = db
+            .tables
+            .users
+            .iter_mut()
+            .find(|user| user.id == user_id.0)
+            .ok_or(AppError::User(UserError::NotFound))?;
+        user.role = role.as_str().to_string();
+
+        Ok(user.clone().into())
+    }
+
+    async fn delete_user(&mut self, user_id: UserId) -> Result<(), AppError> {
+        let mut db = self.lock();
+        let before = db.tables.users.len();
+        db.tables.users.retain(|user| user.id != user_id.0);
+        if db.tables.users.len() == before {
+            return Err(AppError::User(UserError::NotFound));
+        }
+
+        // What the foreign keys do in Postgres: posts stay without an author, votes go
+        for question in db.questions.iter_mut() {
+            if question.author_id == Some(user_id) {
+                question.author_id = None;
+            }
+        }
+        for answer in db.answers.iter_mut() {
+            if answer.author_id == Some(user_id) {
+                answer.author_id = None;
+            }
+        }
+        let tables = &mut *db.tables;
+        for stored in tables.comments.iter_mut() {
+            if stored.comment.author_id == Some(user_id.0) {
+                stored.comment.author_id = None;
+            }
+        }
+        tables.votes.retain(|vote| vote.user_id != user_id);
+
+        Ok(())
+    }
+}
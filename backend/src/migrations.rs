@@ -0,0 +1,88 @@
+use std::collections::HashSet;
+
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::{Executor, PgPool};
+
+/// Everything in backend/migrations, compiled into the binary. Databases created before the
+/// demo rows moved to seeds/dev.sql still list those seed migrations as applied, so applied
+/// versions that no longer exist here are not an error.
+pub static MIGRATOR: Migrator = Migrator {
+    ignore_missing: true,
+    ..sqlx::migrate!("./migrations")
+};
+
+const DEV_SEED: &str = include_str!("../seeds/dev.sql");
+
+/// Where one migration stands in a database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+/// Applies every pending migration
+pub async fn migrate_up(pool: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+/// Reverts the most recently applied migration and returns its version,
+/// or None when there was nothing to revert
+pub async fn migrate_down(pool: &PgPool) -> Result<Option<i64>, MigrateError> {
+    let mut applied: Vec<i64> = migration_status(pool)
+        .await?
+        .into_iter()
+        .filter(|migration| migration.applied)
+        .map(|migration| migration.version)
+        .collect();
+
+    let Some(latest) = applied.pop() else {
+        return Ok(None);
+    };
+    // Reverts every applied migration newer than the target, which is just `latest`
+    MIGRATOR
+        .undo(pool, applied.last().copied().unwrap_or(0))
+        .await?;
+
+    Ok(Some(latest))
+}
+
+/// Every known migration in version order, and whether it has been applied
+pub async fn migration_status(pool: &PgPool) -> Result<Vec<MigrationStatus>, MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied: HashSet<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+
+    Ok(MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied.contains(&migration.version),
+        })
+        .collect())
+}
+
+/// Loads the demo rows in seeds/dev.sql. A database that already has questions is left alone,
+/// the return value says whether anything was loaded.
+pub async fn seed_dev_data(pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let (has_questions,): (bool,) = sqlx::query_as("SELECT EXISTS (SELECT 1 FROM questions)")
+        .fetch_one(&mut tx)
+        .await?;
+    if has_questions {
+        return Ok(false);
+    }
+
+    tx.execute(DEV_SEED).await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
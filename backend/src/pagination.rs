@@ -0,0 +1,182 @@
+use std::ops::RangeInclusive;
+
+use chrono::{DateTime, SubsecRound, TimeZone, Utc};
+use http::HeaderValue;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::FieldError;
+
+pub const DEFAULT_LIMIT: i64 = 20;
+pub const MAX_LIMIT: i64 = 100;
+/// Far deeper than anyone pages by hand, past this `cursor` is the way to go
+pub const MAX_OFFSET: i64 = 1_000_000;
+
+/// What a client may ask for, anything outside is rejected rather than quietly adjusted
+pub const LIMIT_RANGE: RangeInclusive<i64> = 1..=MAX_LIMIT;
+pub const OFFSET_RANGE: RangeInclusive<i64> = 0..=MAX_OFFSET;
+
+/// Query string accepted by paginated listings, e.g. `/questions?limit=10&cursor=...`.
+/// `cursor` (keyset) and `offset` are alternatives, the cursor wins when both are given.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PageParams {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+impl PageParams {
+    // Query strings are held to LIMIT_RANGE and OFFSET_RANGE by `parse_page_number`,
+    // the clamping only covers params built in code
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset.unwrap_or(0).max(0)
+    }
+}
+
+/// Reads a `limit` or `offset` taken from the query string as text, so a bad value is reported
+/// alongside the listing's other field errors instead of axum's plain 400.
+pub fn parse_page_number(
+    field: &str,
+    value: &Option<String>,
+    range: RangeInclusive<i64>,
+    errors: &mut Vec<FieldError>,
+) -> Option<i64> {
+    match value.as_ref()?.parse::<i64>() {
+        Ok(number) if !range.contains(&number) => {
+            errors.push(FieldError::new(
+                field,
+                format!("must be between {} and {}", range.start(), range.end()),
+            ));
+            None
+        }
+        Ok(number) => Some(number),
+        Err(_) => {
+            errors.push(FieldError::new(field, "must be a number"));
+            None
+        }
+    }
+}
+
+/// The envelope every paginated listing is wrapped in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub total: i64,
+}
+
+/// Position of the last row of a page in `(created_on, id)` order.
+/// Clients only ever see it as an opaque string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_on: DateTime<Utc>,
+    pub id: i32,
+}
+
+/// The current time cut to the microseconds Postgres keeps and a `Cursor` can hold,
+/// for stores that stamp rows themselves
+pub(crate) fn now() -> DateTime<Utc> {
+    Utc::now().trunc_subsecs(6)
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let raw = format!("{}:{}", self.created_on.timestamp_micros(), self.id);
+        raw.bytes().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    pub fn decode(value: &str) -> Option<Cursor> {
+        // An odd length leaves a half byte at the end, which `get` turns into None
+        let bytes = (0..value.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        let raw = String::from_utf8(bytes).ok()?;
+
+        let (micros, id) = raw.split_once(':')?;
+        let micros: i64 = micros.parse().ok()?;
+        let id: i32 = id.parse().ok()?;
+
+        let seconds = micros.div_euclid(1_000_000);
+        let nanos = (micros.rem_euclid(1_000_000) * 1_000) as u32;
+        let created_on = Utc.timestamp_opt(seconds, nanos).single()?;
+
+        Some(Cursor { created_on, id })
+    }
+}
+
+/// Builds an RFC 8288 `Link` header value for a page served from `path`. `filter_query` is the
+/// rest of the original query string (sorting, filters) so the links stay on the same listing.
+/// Cursor requests get `first` and `next`, offset requests also get `prev` and `last`.
+pub fn link_header<T>(
+    path: &str,
+    filter_query: &str,
+    params: &PageParams,
+    page: &Page<T>,
+) -> Option<HeaderValue> {
+    let limit = params.limit();
+    let base = if filter_query.is_empty() {
+        format!("{}?limit={}", path, limit)
+    } else {
+        format!("{}?{}&limit={}", path, filter_query, limit)
+    };
+    let mut links = vec![format!("<{}>; rel=\"first\"", base)];
+
+    if params.cursor.is_some() {
+        if let Some(next) = &page.next_cursor {
+            links.push(format!("<{}&cursor={}>; rel=\"next\"", base, next));
+        }
+    } else {
+        let offset = params.offset();
+        if let Some(next) = offset.checked_add(limit).filter(|next| *next < page.total) {
+            links.push(format!("<{}&offset={}>; rel=\"next\"", base, next));
+        }
+        if offset > 0 {
+            links.push(format!(
+                "<{}&offset={}>; rel=\"prev\"",
+                base,
+                (offset - limit).max(0)
+            ));
+        }
+        let last = ((page.total - 1).max(0) / limit) * limit;
+        links.push(format!("<{}&offset={}>; rel=\"last\"", base, last));
+    }
+
+    HeaderValue::from_str(&links.join(", ")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trips() {
+        let cursor = Cursor {
+            created_on: Utc.timestamp_opt(1_690_000_000, 123_456_000).unwrap(),
+            id: 42,
+        };
+
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+        assert_eq!(Cursor::decode("zz"), None);
+        assert_eq!(Cursor::decode("abc"), None);
+    }
+
+    #[test]
+    fn no_next_link_past_the_largest_offset() {
+        let params = PageParams {
+            offset: Some(i64::MAX),
+            ..Default::default()
+        };
+        let page: Page<()> = Page {
+            items: vec![],
+            next_cursor: None,
+            total: i64::MAX,
+        };
+
+        let link = link_header("/questions", "", &params, &page).unwrap();
+        assert!(!link.to_str().unwrap().contains("rel=\"next\""));
+    }
+}
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+use crate::config::PurgeConfig;
+use crate::repository::Repository;
+
+/// How many rows one purge run removed from each table
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PurgeCounts {
+    pub questions: u64,
+    pub answers: u64,
+    pub comments: u64,
+}
+
+/// When soft deleted posts are removed for good
+#[derive(Debug, Clone, Copy)]
+pub struct PurgeSettings {
+    /// How long a deleted post can still be undeleted
+    pub retention: chrono::Duration,
+    /// How often to look for posts past their retention
+    pub interval: Duration,
+}
+
+impl From<&PurgeConfig> for PurgeSettings {
+    fn from(config: &PurgeConfig) -> Self {
+        Self {
+            // Config::load keeps retention_days far inside the range, this only guards
+            // against a PurgeConfig that skipped it
+            retention: chrono::Duration::try_days(config.retention_days)
+                .unwrap_or_else(chrono::Duration::max_value),
+            interval: Duration::from_secs(config.interval_secs),
+        }
+    }
+}
+
+/// Runs `Repository::purge_deleted` every `settings.interval` for as long as the server is up.
+/// A failed run is logged and retried on the next tick.
+pub fn spawn_purge_task<S: Repository>(mut store: S, settings: PurgeSettings) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(settings.interval);
+        loop {
+            ticker.tick().await;
+            let Some(cutoff) = Utc::now().checked_sub_signed(settings.retention) else {
+                // Nothing can have been deleted before the earliest date there is
+                continue;
+            };
+            match store.purge_deleted(cutoff).await {
+                Ok(counts) => info!(?counts, %cutoff, "Purged deleted posts"),
+                Err(e) => error!("Purging deleted posts failed: {e:?}"),
+            }
+        }
+    })
+}
@@ -1,5 +1,10 @@
 use crate::answer::AnswerResult;
 use crate::comment::CommentResult;
+use crate::error::{AppError, FieldError};
+use crate::pagination::{parse_page_number, Cursor, PageParams, LIMIT_RANGE, OFFSET_RANGE};
+use crate::tag::normalize_tags;
+use crate::user::UserId;
+use crate::validation::{check_tags, check_text, Validate, POST_RULES, TITLE_RULES};
 use chrono::{DateTime, Utc};
 use derive_more::Display;
 use serde_derive::{Deserialize, Serialize};
@@ -7,27 +12,74 @@ use serde_derive::{Deserialize, Serialize};
 // This uses the `derive_more` crate to reduce the Display boilerplate (see below)
 #[derive(Clone, Debug, Display, Serialize, Deserialize, sqlx::FromRow)]
 #[display(
-    fmt = "id: {}, title: {}, content: {}, tags: {:?}",
+    fmt = "id: {}, title: {}, content: {}, tags: {:?}, author_id: {:?}",
     id,
     title,
     content,
-    tags
+    tags,
+    author_id
 )]
 pub struct Question {
     pub id: QuestionId,
     pub title: String,
     pub content: String,
     pub tags: Option<Vec<String>>,
+    pub author_id: Option<UserId>,
+    pub created_on: DateTime<Utc>,
+    /// Upvotes minus downvotes
+    pub score: i64,
+    /// Bumped by every edit, sent as the question's ETag
+    pub version: i32,
 }
 
 impl Question {
     #[allow(dead_code)]
-    pub fn new(id: QuestionId, title: String, content: String, tags: Option<Vec<String>>) -> Self {
+    pub fn new(
+        id: QuestionId,
+        title: String,
+        content: String,
+        tags: Option<Vec<String>>,
+        author_id: Option<UserId>,
+        created_on: DateTime<Utc>,
+        score: i64,
+    ) -> Self {
         Question {
             id,
             title,
             content,
             tags,
+            author_id,
+            created_on,
+            score,
+            version: 1,
+        }
+    }
+}
+
+// Rows from the dynamically built listing query, which can't use the query macros' type overrides
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct QuestionDbResult {
+    pub id: i32,
+    pub title: String,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+    pub author_id: Option<i32>,
+    pub created_on: DateTime<Utc>,
+    pub score: i64,
+    pub version: i32,
+}
+
+impl From<QuestionDbResult> for Question {
+    fn from(value: QuestionDbResult) -> Self {
+        Question {
+            id: QuestionId(value.id),
+            title: value.title,
+            content: value.content,
+            tags: value.tags,
+            author_id: value.author_id.map(UserId),
+            created_on: value.created_on,
+            score: value.score,
+            version: value.version,
         }
     }
 }
@@ -83,6 +135,20 @@ pub struct CreateQuestion {
     pub tags: Option<Vec<String>>,
 }
 
+impl Validate for CreateQuestion {
+    fn field_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_text("title", &self.title, TITLE_RULES, &mut errors);
+        check_text("content", &self.content, POST_RULES, &mut errors);
+        check_tags(
+            "tags",
+            self.tags.as_deref().unwrap_or_default(),
+            &mut errors,
+        );
+        errors
+    }
+}
+
 #[derive(Deserialize)]
 pub struct GetQuestionById {
     pub question_id: i32,
@@ -96,13 +162,211 @@ pub struct UpdateQuestion {
     pub tags: Option<Vec<String>>,
 }
 
+impl Validate for UpdateQuestion {
+    fn field_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_text("title", &self.title, TITLE_RULES, &mut errors);
+        check_text("content", &self.content, POST_RULES, &mut errors);
+        check_tags(
+            "tags",
+            self.tags.as_deref().unwrap_or_default(),
+            &mut errors,
+        );
+        errors
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct QuestionResult {
     pub id: i32,
     pub title: String,
     pub content: String,
     pub tags: Option<Vec<String>>,
+    pub author_id: Option<i32>,
     pub created_on: DateTime<Utc>,
+    pub score: i64,
+    /// Listed first in `answers` when set
+    pub accepted_answer_id: Option<i32>,
     pub comments: Vec<CommentResult>,
     pub answers: Vec<AnswerResult>,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuestionSort {
+    #[default]
+    Newest,
+    Oldest,
+    MostAnswers,
+    Unanswered,
+    Votes,
+}
+
+impl QuestionSort {
+    /// Only the plain date orderings can be paged through with a keyset cursor
+    pub fn supports_cursor(&self) -> bool {
+        matches!(self, QuestionSort::Newest | QuestionSort::Oldest)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagMatch {
+    #[default]
+    All,
+    Any,
+}
+
+/// A validated `GET /questions` request, see `QuestionListQuery::parse`
+#[derive(Debug, Clone, Default)]
+pub struct QuestionFilter {
+    pub sort: QuestionSort,
+    pub tags: Vec<String>,
+    pub tag_match: TagMatch,
+    pub author: Option<UserId>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub has_answers: Option<bool>,
+}
+
+/// The raw query string of `GET /questions`. Everything is taken as text so that bad values
+/// are reported field by field through `AppError::Validation` instead of a bare 400.
+/// localhost:3000/questions?sort=oldest&tag=rust,axum&tag_match=any&has_answers=false
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct QuestionListQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag_match: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_answers: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+impl QuestionListQuery {
+    pub fn parse(&self) -> Result<(PageParams, QuestionFilter), AppError> {
+        let mut errors = Vec::new();
+        let mut filter = QuestionFilter::default();
+        let mut page = PageParams::default();
+
+        if let Some(sort) = &self.sort {
+            match sort.as_str() {
+                "newest" => filter.sort = QuestionSort::Newest,
+                "oldest" => filter.sort = QuestionSort::Oldest,
+                "most_answers" => filter.sort = QuestionSort::MostAnswers,
+                "unanswered" => filter.sort = QuestionSort::Unanswered,
+                "votes" => filter.sort = QuestionSort::Votes,
+                _ => errors.push(FieldError::new(
+                    "sort",
+                    "must be one of newest, oldest, most_answers, unanswered, votes",
+                )),
+            }
+        }
+
+        if let Some(tags) = &self.tag {
+            let tags: Vec<String> = tags.split(',').map(str::to_string).collect();
+            filter.tags = normalize_tags(&tags);
+            if filter.tags.is_empty() {
+                errors.push(FieldError::new("tag", "must name at least one tag"));
+            }
+        }
+
+        if let Some(tag_match) = &self.tag_match {
+            match tag_match.as_str() {
+                "all" => filter.tag_match = TagMatch::All,
+                "any" => filter.tag_match = TagMatch::Any,
+                _ => errors.push(FieldError::new("tag_match", "must be all or any")),
+            }
+        }
+
+        if let Some(author) = &self.author {
+            match author.parse::<i32>() {
+                Ok(id) => filter.author = Some(UserId(id)),
+                Err(_) => errors.push(FieldError::new("author", "must be a user id")),
+            }
+        }
+
+        filter.created_after = parse_timestamp("created_after", &self.created_after, &mut errors);
+        filter.created_before =
+            parse_timestamp("created_before", &self.created_before, &mut errors);
+        if let (Some(after), Some(before)) = (filter.created_after, filter.created_before) {
+            if after >= before {
+                errors.push(FieldError::new(
+                    "created_after",
+                    "must be earlier than created_before",
+                ));
+            }
+        }
+
+        if let Some(has_answers) = &self.has_answers {
+            match has_answers.as_str() {
+                "true" => filter.has_answers = Some(true),
+                "false" => filter.has_answers = Some(false),
+                _ => errors.push(FieldError::new("has_answers", "must be true or false")),
+            }
+        }
+
+        page.limit = parse_page_number("limit", &self.limit, LIMIT_RANGE, &mut errors);
+        page.offset = parse_page_number("offset", &self.offset, OFFSET_RANGE, &mut errors);
+
+        if let Some(cursor) = &self.cursor {
+            if Cursor::decode(cursor).is_none() {
+                errors.push(FieldError::new("cursor", "is not a valid cursor"));
+            } else if !filter.sort.supports_cursor() {
+                errors.push(FieldError::new(
+                    "cursor",
+                    "is only supported with sort=newest or sort=oldest, use offset instead",
+                ));
+            }
+            page.cursor = Some(cursor.clone());
+        }
+
+        if !errors.is_empty() {
+            return Err(AppError::Validation(errors));
+        }
+
+        Ok((page, filter))
+    }
+
+    /// The filtering part of the query string, for building pagination links
+    pub fn filter_query_string(&self) -> String {
+        let filters = QuestionListQuery {
+            limit: None,
+            offset: None,
+            cursor: None,
+            ..self.clone()
+        };
+
+        serde_urlencoded::to_string(filters).unwrap_or_default()
+    }
+}
+
+fn parse_timestamp(
+    field: &str,
+    value: &Option<String>,
+    errors: &mut Vec<FieldError>,
+) -> Option<DateTime<Utc>> {
+    let value = value.as_ref()?;
+
+    match DateTime::parse_from_rfc3339(value) {
+        Ok(timestamp) => Some(timestamp.with_timezone(&Utc)),
+        Err(_) => {
+            errors.push(FieldError::new(
+                field,
+                "must be an RFC 3339 timestamp, e.g. 2023-07-01T00:00:00Z",
+            ));
+            None
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::answer::{Answer, AnswerId};
+use crate::auth::AuthUser;
+use crate::comment::{CommentDbResult, CommentId, CommentTarget};
+use crate::error::AppError;
+use crate::etag::EtagCondition;
+use crate::pagination::{Page, PageParams};
+use crate::purge::PurgeCounts;
+use crate::question::{
+    IntoQuestionId, Question, QuestionFilter, QuestionId, QuestionResult, UpdateQuestion,
+};
+use crate::revision::{AnswerRevision, QuestionRevision, RevisionDiff};
+use crate::search::SearchResult;
+use crate::tag::{MergeTag, Tag, TagMerge};
+use crate::user::{Role, User, UserDbResult, UserId};
+use crate::vote::{VoteDirection, VoteSummary, VoteTarget};
+
+/// Everything the handlers need from storage. `db::Store` keeps it in Postgres and
+/// `memory::MemoryStore` in process memory, for running the API without a database.
+///
+/// Deleted posts are hidden from every read, and whoever calls an editing method has
+/// already been let through by `layers::route_policy`, but the per-post author checks
+/// happen here.
+#[async_trait]
+pub trait Repository: Clone + Send + Sync + 'static {
+    /// Answers `question_id`, which has to exist
+    async fn add_answer(
+        &mut self,
+        content: String,
+        question_id: i32,
+        author_id: UserId,
+    ) -> Result<Answer, AppError>;
+
+    /// A question's answers, oldest first
+    async fn get_answers_for_question(
+        &mut self,
+        question_id: QuestionId,
+    ) -> Result<Vec<Answer>, AppError>;
+
+    async fn get_answer_by_id(&mut self, answer_id: AnswerId) -> Result<Answer, AppError>;
+
+    async fn update_answer(
+        &mut self,
+        answer_id: AnswerId,
+        content: String,
+        user: &AuthUser,
+    ) -> Result<Answer, AppError>;
+
+    /// Soft deletes the answer and its comments
+    async fn delete_answer(&mut self, answer_id: AnswerId, user: &AuthUser)
+        -> Result<(), AppError>;
+
+    /// One page of questions, filtered and sorted as asked for
+    async fn get_all_questions(
+        &mut self,
+        params: &PageParams,
+        filter: &QuestionFilter,
+    ) -> Result<Page<Question>, AppError>;
+
+    async fn get_question_by_id<T: IntoQuestionId + Send>(
+        &mut self,
+        id: T,
+    ) -> Result<Question, AppError>;
+
+    /// The question with its comments, answers and the answers' comments
+    async fn get_question_comments(&mut self, question_id: i32)
+        -> Result<QuestionResult, AppError>;
+
+    async fn add_question(
+        &mut self,
+        title: String,
+        content: String,
+        tags: Option<Vec<String>>,
+        author_id: UserId,
+    ) -> Result<Question, AppError>;
+
+    /// Saves an edit. With `if_match` the edit only goes through while the question is still
+    /// at one of the versions the client names, otherwise it fails with `VersionMismatch`.
+    async fn update_question(
+        &mut self,
+        new_question: UpdateQuestion,
+        if_match: Option<&EtagCondition>,
+        user: &AuthUser,
+    ) -> Result<Question, AppError>;
+
+    /// Every saved version of a question, oldest first
+    async fn get_question_revisions(
+        &mut self,
+        question_id: QuestionId,
+    ) -> Result<Vec<QuestionRevision>, AppError>;
+
+    async fn get_question_revision(
+        &mut self,
+        question_id: QuestionId,
+        revision: i32,
+    ) -> Result<QuestionRevision, AppError>;
+
+    async fn diff_question_revisions(
+        &mut self,
+        question_id: QuestionId,
+        from: i32,
+        to: i32,
+    ) -> Result<RevisionDiff, AppError>;
+
+    /// Restores an earlier revision, saved as a new revision of its own
+    async fn rollback_question(
+        &mut self,
+        question_id: QuestionId,
+        revision: i32,
+        user: &AuthUser,
+    ) -> Result<Question, AppError>;
+
+    /// Every saved version of an answer, oldest first
+    async fn get_answer_revisions(
+        &mut self,
+        answer_id: AnswerId,
+    ) -> Result<Vec<AnswerRevision>, AppError>;
+
+    async fn get_answer_revision(
+        &mut self,
+        answer_id: AnswerId,
+        revision: i32,
+    ) -> Result<AnswerRevision, AppError>;
+
+    async fn diff_answer_revisions(
+        &mut self,
+        answer_id: AnswerId,
+        from: i32,
+        to: i32,
+    ) -> Result<RevisionDiff, AppError>;
+
+    async fn rollback_answer(
+        &mut self,
+        answer_id: AnswerId,
+        revision: i32,
+        user: &AuthUser,
+    ) -> Result<Answer, AppError>;
+
+    /// Every tag with the number of questions carrying it, most used first
+    async fn get_tags(&mut self) -> Result<Vec<Tag>, AppError>;
+
+    /// Looks a tag up by its slug or by one of its synonyms
+    async fn get_tag(&mut self, slug: &str) -> Result<Tag, AppError>;
+
+    /// Folds the tag `source` into `merge.into` and keeps `source` as a synonym of it
+    async fn merge_tags(
+        &mut self,
+        source: &str,
+        merge: MergeTag,
+        user: &AuthUser,
+    ) -> Result<TagMerge, AppError>;
+
+    /// Soft deletes the question along with its answers and comments
+    async fn delete_question(&mut self, question_id: i32, user: &AuthUser) -> Result<(), AppError>;
+
+    /// Brings back a deleted question together with what was deleted along with it
+    async fn undelete_question(&mut self, question_id: QuestionId) -> Result<Question, AppError>;
+
+    /// Removes for good every post that was soft deleted before `cutoff`
+    async fn purge_deleted(&mut self, cutoff: DateTime<Utc>) -> Result<PurgeCounts, AppError>;
+
+    /// Marks `answer_id` as the one that solved the question, replacing any earlier choice
+    async fn accept_answer(
+        &mut self,
+        question_id: QuestionId,
+        answer_id: AnswerId,
+        user: &AuthUser,
+    ) -> Result<QuestionResult, AppError>;
+
+    async fn unaccept_answer(
+        &mut self,
+        question_id: QuestionId,
+        user: &AuthUser,
+    ) -> Result<QuestionResult, AppError>;
+
+    /// Records `user_id`'s vote on a question or answer, replacing any earlier vote of theirs
+    async fn cast_vote(
+        &mut self,
+        target: VoteTarget,
+        user_id: UserId,
+        direction: VoteDirection,
+    ) -> Result<VoteSummary, AppError>;
+
+    /// Takes back `user_id`'s vote, if they had cast one
+    async fn retract_vote(
+        &mut self,
+        target: VoteTarget,
+        user_id: UserId,
+    ) -> Result<VoteSummary, AppError>;
+
+    /// Comments on `target`, which has to exist
+    async fn add_comment(
+        &mut self,
+        content: String,
+        target: CommentTarget,
+        author_id: UserId,
+    ) -> Result<CommentDbResult, AppError>;
+
+    async fn get_comments_for_question(
+        &mut self,
+        question_id: QuestionId,
+    ) -> Result<Vec<CommentDbResult>, AppError>;
+
+    async fn get_comments_for_answer(
+        &mut self,
+        answer_id: AnswerId,
+    ) -> Result<Vec<CommentDbResult>, AppError>;
+
+    async fn get_comment_by_id(
+        &mut self,
+        comment_id: CommentId,
+    ) -> Result<CommentDbResult, AppError>;
+
+    async fn update_comment(
+        &mut self,
+        comment_id: CommentId,
+        content: String,
+        user: &AuthUser,
+    ) -> Result<CommentDbResult, AppError>;
+
+    async fn delete_comment(
+        &mut self,
+        comment_id: CommentId,
+        user: &AuthUser,
+    ) -> Result<(), AppError>;
+
+    /// Ranked matches across questions, answers and comments
+    async fn search(
+        &mut self,
+        terms: &str,
+        params: &PageParams,
+    ) -> Result<Page<SearchResult>, AppError>;
+
+    async fn add_user(&mut self, email: String, password_hash: String) -> Result<User, AppError>;
+
+    async fn get_user_by_email(&mut self, email: &str) -> Result<Option<UserDbResult>, AppError>;
+
+    /// The user as stored right now, which may differ from what their token claims
+    async fn get_user_by_id(&mut self, user_id: UserId) -> Result<Option<User>, AppError>;
+
+    async fn get_all_users(&mut self) -> Result<Vec<User>, AppError>;
+
+    async fn set_user_role(&mut self, user_id: UserId, role: Role) -> Result<User, AppError>;
+
+    async fn delete_user(&mut self, user_id: UserId) -> Result<(), AppError>;
+}
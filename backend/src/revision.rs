@@ -0,0 +1,150 @@
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+
+/// One saved version of a question, as listed by `GET /question/:question_id/revisions`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestionRevision {
+    pub revision: i32,
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub editor_id: Option<i32>,
+    pub edited_on: DateTime<Utc>,
+}
+
+/// One saved version of an answer, as listed by `GET /answer/:answer_id/revisions`
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AnswerRevision {
+    pub revision: i32,
+    pub content: String,
+    pub editor_id: Option<i32>,
+    pub edited_on: DateTime<Utc>,
+}
+
+/// The two revisions to compare, e.g. `/question/5/revisions/diff?from=1&to=3`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffQuery {
+    pub from: i32,
+    pub to: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffLine {
+    pub op: DiffOp,
+    pub text: String,
+}
+
+/// A line by line diff of one field, only fields that changed are listed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldDiff {
+    pub field: String,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevisionDiff {
+    pub from: i32,
+    pub to: i32,
+    pub fields: Vec<FieldDiff>,
+}
+
+impl RevisionDiff {
+    pub fn between_questions(from: &QuestionRevision, to: &QuestionRevision) -> Self {
+        let fields = [
+            diff_field("title", &from.title, &to.title),
+            diff_field("content", &from.content, &to.content),
+            // One tag per line so added and removed tags show up as inserts and deletes
+            diff_field("tags", &from.tags.join("\n"), &to.tags.join("\n")),
+        ];
+
+        RevisionDiff {
+            from: from.revision,
+            to: to.revision,
+            fields: fields.into_iter().flatten().collect(),
+        }
+    }
+
+    pub fn between_answers(from: &AnswerRevision, to: &AnswerRevision) -> Self {
+        RevisionDiff {
+            from: from.revision,
+            to: to.revision,
+            fields: diff_field("content", &from.content, &to.content)
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+fn diff_field(field: &str, old: &str, new: &str) -> Option<FieldDiff> {
+    if old == new {
+        return None;
+    }
+
+    let lines = TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            op: match change.tag() {
+                ChangeTag::Equal => DiffOp::Equal,
+                ChangeTag::Insert => DiffOp::Insert,
+                ChangeTag::Delete => DiffOp::Delete,
+            },
+            text: change.value().trim_end_matches('\n').to_string(),
+        })
+        .collect();
+
+    Some(FieldDiff {
+        field: field.to_string(),
+        lines,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changed_fields_are_diffed() {
+        let revision = |revision: i32, content: &str, tags: &[&str]| QuestionRevision {
+            revision,
+            title: "Title".to_string(),
+            content: content.to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            editor_id: None,
+            edited_on: Utc::now(),
+        };
+
+        let diff = RevisionDiff::between_questions(
+            &revision(1, "first line\nsecond line", &["rust"]),
+            &revision(2, "first line\nchanged line", &["rust", "axum"]),
+        );
+
+        let fields: Vec<&str> = diff.fields.iter().map(|f| f.field.as_str()).collect();
+        assert_eq!(fields, vec!["content", "tags"]);
+        assert_eq!(
+            diff.fields[0].lines,
+            vec![
+                DiffLine {
+                    op: DiffOp::Equal,
+                    text: "first line".to_string()
+                },
+                DiffLine {
+                    op: DiffOp::Delete,
+                    text: "second line".to_string()
+                },
+                DiffLine {
+                    op: DiffOp::Insert,
+                    text: "changed line".to_string()
+                },
+            ]
+        );
+    }
+}
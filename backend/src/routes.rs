@@ -1,45 +1,141 @@
-use axum::response::Response;
+use axum::middleware;
 use axum::routing::*;
 use axum::Router;
-use http::StatusCode;
 use hyper::Body;
-use sqlx::PgPool;
+use tower_http::cors::AllowOrigin;
 use tracing::info;
 
-use crate::db::Store;
+use crate::error::AppError;
 use crate::handlers::root;
+use crate::repository::Repository;
 use crate::{handlers, layers};
 
-pub async fn app(pool: PgPool) -> Router {
-    let db = Store::with_pool(pool);
+/// The whole API on top of `db`, whichever storage backend it is, callable from any origin
+pub async fn app<S: Repository>(db: S) -> Router {
+    app_with_cors(db, AllowOrigin::any()).await
+}
 
+/// `app`, with browsers only allowed to call it from `allow_origin`
+pub async fn app_with_cors<S: Repository>(db: S, allow_origin: AllowOrigin) -> Router {
     info!("Seeded database");
 
-    let (cors_layer, trace_layer) = layers::get_layers();
+    let (cors_layer, trace_layer) = layers::get_layers(allow_origin);
 
     Router::new()
         // The router matches these FROM TOP TO BOTTOM explicitly!
+        // Who may call each of these is decided by layers::route_policy
         .route("/", get(root))
-        .route("/questions", get(handlers::get_questions))
-        .route("/question/:question_id", get(handlers::get_question_by_id))
+        .route("/questions", get(handlers::get_questions::<S>))
+        .route(
+            "/question/:question_id",
+            get(handlers::get_question_by_id::<S>),
+        )
         .route(
             "/question_comments/:question_id",
-            get(handlers::get_question_comments),
+            get(handlers::get_question_comments::<S>),
+        )
+        .route("/question", post(handlers::create_question::<S>))
+        .route("/question", put(handlers::update_question::<S>))
+        .route("/question", delete(handlers::delete_question::<S>))
+        .route(
+            "/question/:question_id/undelete",
+            post(handlers::undelete_question::<S>),
+        )
+        .route(
+            "/question/:question_id/answers",
+            get(handlers::get_question_answers::<S>),
+        )
+        .route(
+            "/question/:question_id/comments",
+            get(handlers::get_question_comment_list::<S>),
+        )
+        .route(
+            "/question/:question_id/vote",
+            post(handlers::vote_on_question::<S>),
+        )
+        .route(
+            "/question/:question_id/vote",
+            delete(handlers::retract_question_vote::<S>),
+        )
+        .route(
+            "/question/:question_id/accept/:answer_id",
+            post(handlers::accept_answer::<S>),
+        )
+        .route(
+            "/question/:question_id/accept",
+            delete(handlers::unaccept_answer::<S>),
+        )
+        .route(
+            "/question/:question_id/revisions",
+            get(handlers::get_question_revisions::<S>),
+        )
+        .route(
+            "/question/:question_id/revisions/diff",
+            get(handlers::diff_question_revisions::<S>),
+        )
+        .route(
+            "/question/:question_id/revisions/:revision/rollback",
+            post(handlers::rollback_question::<S>),
+        )
+        .route("/answer", post(handlers::create_answer::<S>))
+        .route("/answer/:answer_id", get(handlers::get_answer_by_id::<S>))
+        .route("/answer/:answer_id", put(handlers::update_answer::<S>))
+        .route("/answer/:answer_id", delete(handlers::delete_answer::<S>))
+        .route(
+            "/answer/:answer_id/comments",
+            get(handlers::get_answer_comments::<S>),
+        )
+        .route(
+            "/answer/:answer_id/vote",
+            post(handlers::vote_on_answer::<S>),
+        )
+        .route(
+            "/answer/:answer_id/vote",
+            delete(handlers::retract_answer_vote::<S>),
+        )
+        .route(
+            "/answer/:answer_id/revisions",
+            get(handlers::get_answer_revisions::<S>),
+        )
+        .route(
+            "/answer/:answer_id/revisions/diff",
+            get(handlers::diff_answer_revisions::<S>),
+        )
+        .route(
+            "/answer/:answer_id/revisions/:revision/rollback",
+            post(handlers::rollback_answer::<S>),
+        )
+        .route("/comment", post(handlers::create_comment::<S>))
+        .route("/comment/:comment_id", put(handlers::update_comment::<S>))
+        .route(
+            "/comment/:comment_id",
+            delete(handlers::delete_comment::<S>),
+        )
+        .route("/tags", get(handlers::get_tags::<S>))
+        .route(
+            "/tags/:slug/questions",
+            get(handlers::get_tag_questions::<S>),
+        )
+        .route("/tags/:slug/merge", post(handlers::merge_tag::<S>))
+        .route("/search", get(handlers::search::<S>))
+        .route("/register", post(handlers::register::<S>))
+        .route("/login", post(handlers::login::<S>))
+        .route("/admin/users", get(handlers::get_users::<S>))
+        .route("/admin/users/:user_id", delete(handlers::delete_user::<S>))
+        .route(
+            "/admin/users/:user_id/role",
+            put(handlers::update_user_role::<S>),
         )
-        .route("/question", post(handlers::create_question))
-        .route("/question", put(handlers::update_question))
-        .route("/question", delete(handlers::delete_question))
-        .route("/answer", post(handlers::create_answer))
-        .route("/comment", post(handlers::create_comment))
         .route("/*_", get(handle_404))
+        .route_layer(middleware::from_fn_with_state(
+            db.clone(),
+            layers::enforce_policy::<S, Body>,
+        ))
         .layer(cors_layer)
         .layer(trace_layer)
         .with_state(db)
 }
 
-async fn handle_404() -> Response<Body> {
-    Response::builder()
-        .status(StatusCode::NOT_FOUND)
-        .body(Body::from("The requested page could not be found"))
-        .unwrap()
+async fn handle_404() -> AppError {
+    AppError::RouteNotFound
 }
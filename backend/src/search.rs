@@ -0,0 +1,240 @@
+use derive_more::Display;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::{AppError, FieldError};
+use crate::pagination::{parse_page_number, Page, PageParams, LIMIT_RANGE, OFFSET_RANGE};
+
+/// The kind of post a search hit came from
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchEntity {
+    #[display(fmt = "question")]
+    Question,
+    #[display(fmt = "answer")]
+    Answer,
+    #[display(fmt = "comment")]
+    Comment,
+}
+
+impl SearchEntity {
+    pub(crate) fn from_db(value: &str) -> Self {
+        match value {
+            "answer" => SearchEntity::Answer,
+            "comment" => SearchEntity::Comment,
+            _ => SearchEntity::Question,
+        }
+    }
+}
+
+/// One match from `GET /search`. Every hit names the question thread it belongs to,
+/// so clients can link straight to it whatever kind of post matched.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub entity_type: SearchEntity,
+    pub id: i32,
+    pub question_id: i32,
+    pub question_title: String,
+    /// The matching text, HTML escaped, with the search terms wrapped in `<b></b>`.
+    /// Those are the only tags it can contain, so it is safe to render as HTML.
+    pub snippet: String,
+    pub rank: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchDbResult {
+    pub entity_type: String,
+    pub id: i32,
+    pub question_id: i32,
+    pub question_title: String,
+    pub snippet: String,
+    pub rank: f32,
+}
+
+impl From<SearchDbResult> for SearchResult {
+    fn from(value: SearchDbResult) -> Self {
+        SearchResult {
+            entity_type: SearchEntity::from_db(&value.entity_type),
+            id: value.id,
+            question_id: value.question_id,
+            question_title: value.question_title,
+            snippet: escape_headline(&value.snippet),
+            rank: value.rank,
+        }
+    }
+}
+
+/// What `ts_headline` is told to wrap the matches in instead of `<b></b>`. Control
+/// characters survive HTML escaping untouched, so they can be swapped for the real tags
+/// afterwards. Posts are stripped of them before highlighting.
+pub(crate) const HEADLINE_MARKERS: &str = "\u{2}\u{3}";
+
+/// The `ts_headline` options that make it use `HEADLINE_MARKERS`
+pub(crate) const HEADLINE_OPTIONS: &str = "StartSel=\u{2}, StopSel=\u{3}";
+
+/// Escapes a headline made with `HEADLINE_OPTIONS`, then marks the matches up with `<b></b>`
+fn escape_headline(headline: &str) -> String {
+    html_escape::encode_text(headline)
+        .replace('\u{2}', "<b>")
+        .replace('\u{3}', "</b>")
+}
+
+/// Search for the backends without a full-text index, done on the post bodies in Rust.
+/// Quoted phrases are searched word by word, `or` is ignored and `-word` excludes posts
+/// containing it. Words match on prefix, a rough stand-in for the stemming Postgres does.
+pub(crate) struct TermSearch {
+    pub wanted: Vec<String>,
+    pub unwanted: Vec<String>,
+}
+
+impl TermSearch {
+    pub fn parse(terms: &str) -> Self {
+        let mut wanted = Vec::new();
+        let mut unwanted = Vec::new();
+        for word in terms.to_lowercase().split_whitespace() {
+            let (list, word) = match word.strip_prefix('-') {
+                Some(word) => (&mut unwanted, word),
+                None => (&mut wanted, word),
+            };
+            let word: String = word.chars().filter(|c| c.is_alphanumeric()).collect();
+            if !word.is_empty() && word != "or" {
+                list.push(word);
+            }
+        }
+
+        Self { wanted, unwanted }
+    }
+
+    /// `body` as a hit if it matches, ranked by how often the wanted words occur in it
+    pub fn hit(
+        &self,
+        entity_type: SearchEntity,
+        id: i32,
+        question_id: i32,
+        question_title: String,
+        body: &str,
+    ) -> Option<SearchResult> {
+        let words: Vec<String> = body
+            .split_whitespace()
+            .map(|word| {
+                word.chars()
+                    .filter(|c| c.is_alphanumeric())
+                    .collect::<String>()
+                    .to_lowercase()
+            })
+            .collect();
+        let occurrences = |term: &String| {
+            words
+                .iter()
+                .filter(|word| word.starts_with(term.as_str()))
+                .count()
+        };
+
+        if self.wanted.is_empty()
+            || self.wanted.iter().any(|term| occurrences(term) == 0)
+            || self.unwanted.iter().any(|term| occurrences(term) > 0)
+        {
+            return None;
+        }
+
+        let hits: usize = self.wanted.iter().map(occurrences).sum();
+        Some(SearchResult {
+            entity_type,
+            id,
+            question_id,
+            question_title,
+            snippet: self.snippet(body),
+            rank: hits as f32 / (words.len() as f32 + 1.0),
+        })
+    }
+
+    /// The start of `body`, escaped, with the matching words wrapped in `<b></b>`,
+    /// like the Postgres search does
+    fn snippet(&self, body: &str) -> String {
+        body.split_whitespace()
+            .take(35)
+            .map(|word| {
+                let bare = word.to_lowercase();
+                let escaped = html_escape::encode_text(word);
+                if self.wanted.iter().any(|term| {
+                    bare.trim_start_matches(|c: char| !c.is_alphanumeric())
+                        .starts_with(term.as_str())
+                }) {
+                    format!("<b>{}</b>", escaped)
+                } else {
+                    escaped.into_owned()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The requested page of `hits`, in the same order the Postgres search returns them
+    pub fn page(&self, mut hits: Vec<SearchResult>, params: &PageParams) -> Page<SearchResult> {
+        hits.sort_by(|a, b| {
+            b.rank
+                .total_cmp(&a.rank)
+                .then_with(|| a.entity_type.to_string().cmp(&b.entity_type.to_string()))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+
+        let total = hits.len() as i64;
+        let items = hits
+            .into_iter()
+            .skip(params.offset() as usize)
+            .take(params.limit() as usize)
+            .collect();
+
+        Page {
+            items,
+            next_cursor: None,
+            total,
+        }
+    }
+}
+
+/// Query string for `GET /search`. `q` takes web search syntax: quoted phrases, `or`, `-word`.
+/// Results are ordered by rank, so only offset pagination is offered.
+/// localhost:3000/search?q=borrow+checker&limit=10
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SearchQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<String>,
+}
+
+impl SearchQuery {
+    pub fn parse(&self) -> Result<(String, PageParams), AppError> {
+        let mut errors = Vec::new();
+
+        let terms = self.q.as_deref().unwrap_or_default().trim().to_string();
+        if terms.is_empty() {
+            errors.push(FieldError::new("q", "must not be blank"));
+        }
+
+        let page = PageParams {
+            limit: parse_page_number("limit", &self.limit, LIMIT_RANGE, &mut errors),
+            offset: parse_page_number("offset", &self.offset, OFFSET_RANGE, &mut errors),
+            cursor: None,
+        };
+
+        if !errors.is_empty() {
+            return Err(AppError::Validation(errors));
+        }
+
+        Ok((terms, page))
+    }
+
+    /// The search terms for the `Link` header, without the paging parameters
+    pub fn filter_query_string(&self) -> String {
+        let filters = SearchQuery {
+            q: self.q.clone(),
+            limit: None,
+            offset: None,
+        };
+
+        serde_urlencoded::to_string(filters).unwrap_or_default()
+    }
+}
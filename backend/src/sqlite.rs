@@ -0,0 +1,1550 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use axum::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::migrate::MigrateError;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::types::Json;
+use sqlx::{QueryBuilder, Sqlite, SqlitePool, Transaction};
+
+use crate::answer::{Answer, AnswerDbResult, AnswerId, AnswerResult};
+use crate::auth::AuthUser;
+use crate::comment::{CommentDbResult, CommentId, CommentResult, CommentTarget};
+use crate::config::DatabaseConfig;
+use crate::error::{
+    AnswerError, AppError, CommentError, FieldError, Forbidden, QuestionError, RevisionError,
+    TagError, UserError,
+};
+use crate::etag::EtagCondition;
+use crate::pagination::{self, Cursor, Page, PageParams};
+use crate::purge::PurgeCounts;
+use crate::question::{
+    IntoQuestionId, Question, QuestionDbResult, QuestionFilter, QuestionId, QuestionResult,
+    QuestionSort, TagMatch, UpdateQuestion,
+};
+use crate::repository::Repository;
+use crate::revision::{AnswerRevision, QuestionRevision, RevisionDiff};
+use crate::search::{SearchEntity, SearchResult, TermSearch};
+use crate::tag::{normalize_tags, slugify, MergeTag, Tag, TagMerge};
+use crate::user::{Role, User, UserDbResult, UserId};
+use crate::vote::{VoteDirection, VoteSummary, VoteTarget};
+
+/// The SQLite backed `Repository`, for running on a local database file instead of Postgres.
+/// Its schema is kept in migrations_sqlite and applied by `migrate_up`.
+///
+/// Timestamps are stored as text and always bound from Rust rather than defaulted by
+/// SQLite, so every row shares one format and they compare correctly as strings.
+#[derive(Clone)]
+pub struct SqliteStore {
+    pub conn_pool: SqlitePool,
+}
+
+/// Opens the database at `config.sqlite_url`, e.g. `sqlite://backend.db`, creating the file
+/// if need be. `sqlite::memory:` gives a throwaway database, which starts out without tables.
+pub async fn new_sqlite_pool(config: &DatabaseConfig) -> Result<SqlitePool, sqlx::Error> {
+    let options = SqliteConnectOptions::from_str(&config.sqlite_url)?
+        .create_if_missing(true)
+        .foreign_keys(true);
+
+    // Every connection to an in-memory database gets a database of its own, so there can
+    // only be the one and it has to live as long as the pool
+    let pool_options = if config.sqlite_url.contains(":memory:") {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+    } else {
+        SqlitePoolOptions::new()
+            .max_connections(config.max_connections)
+            .min_connections(config.min_connections)
+            .idle_timeout(config.idle_timeout())
+    };
+
+    pool_options
+        .acquire_timeout(config.acquire_timeout())
+        .connect_with(options)
+        .await
+}
+
+/// Applies every pending migration in migrations_sqlite
+pub async fn migrate_up(pool: &SqlitePool) -> Result<(), MigrateError> {
+    sqlx::migrate!("./migrations_sqlite").run(pool).await
+}
+
+/// The columns of a question row, see `QuestionRow`
+const QUESTION_COLUMNS: &str = "q.id, q.title, q.content, q.author_id, q.created_on, \
+     (SELECT json_group_array(t.slug) FROM question_tags qt JOIN tags t ON t.id = qt.tag_id \
+      WHERE qt.question_id = q.id) AS tags, \
+     COALESCE((SELECT SUM(v.value) FROM votes v WHERE v.question_id = q.id), 0) AS score, \
+     q.version, q.accepted_answer_id";
+
+const ANSWER_COLUMNS: &str = "id, content, question_id, author_id, created_on";
+
+const COMMENT_COLUMNS: &str =
+    "id, content, applied_to_question_id, applied_to_answer_id, author_id, created_on";
+
+const USER_COLUMNS: &str = "id, email, password_hash, role, created_on";
+
+/// A question as SQLite hands it back, with the tags as a JSON array
+#[derive(sqlx::FromRow)]
+struct QuestionRow {
+    id: i32,
+    title: String,
+    content: String,
+    tags: Json<Vec<String>>,
+    author_id: Option<i32>,
+    created_on: DateTime<Utc>,
+    score: i64,
+    version: i32,
+    accepted_answer_id: Option<i32>,
+}
+
+impl From<QuestionRow> for Question {
+    fn from(value: QuestionRow) -> Self {
+        // json_group_array doesn't promise any order
+        let mut tags = value.tags.0;
+        tags.sort();
+
+        QuestionDbResult {
+            id: value.id,
+            title: value.title,
+            content: value.content,
+            tags: Some(tags),
+            author_id: value.author_id,
+            created_on: value.created_on,
+            score: value.score,
+            version: value.version,
+        }
+        .into()
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct QuestionRevisionRow {
+    revision: i32,
+    title: String,
+    content: String,
+    tags: Json<Vec<String>>,
+    editor_id: Option<i32>,
+    edited_on: DateTime<Utc>,
+}
+
+impl From<QuestionRevisionRow> for QuestionRevision {
+    fn from(value: QuestionRevisionRow) -> Self {
+        QuestionRevision {
+            revision: value.revision,
+            title: value.title,
+            content: value.content,
+            tags: value.tags.0,
+            editor_id: value.editor_id,
+            edited_on: value.edited_on,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ThreadAnswerRow {
+    id: i32,
+    content: String,
+    author_id: Option<i32>,
+    created_on: DateTime<Utc>,
+    score: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct SearchRow {
+    entity_type: String,
+    id: i32,
+    question_id: i32,
+    question_title: String,
+    body: String,
+}
+
+/// CommentId only maps onto a Postgres column, so comments are read through this
+#[derive(sqlx::FromRow)]
+struct CommentRow {
+    id: i32,
+    content: String,
+    applied_to_question_id: Option<i32>,
+    applied_to_answer_id: Option<i32>,
+    author_id: Option<i32>,
+    created_on: NaiveDateTime,
+}
+
+impl From<CommentRow> for CommentDbResult {
+    fn from(value: CommentRow) -> Self {
+        CommentDbResult {
+            id: CommentId(value.id),
+            content: value.content,
+            applied_to_question_id: value.applied_to_question_id,
+            applied_to_answer_id: value.applied_to_answer_id,
+            author_id: value.author_id,
+            created_on: value.created_on,
+        }
+    }
+}
+
+fn comment_result(row: CommentRow) -> CommentResult {
+    CommentResult {
+        id: row.id,
+        content: row.content,
+        author_id: row.author_id,
+        created_on: row.created_on,
+    }
+}
+
+impl SqliteStore {
+    pub fn with_pool(pool: SqlitePool) -> Self {
+        Self { conn_pool: pool }
+    }
+
+    async fn vote_summary(
+        &mut self,
+        target: VoteTarget,
+        user_id: UserId,
+    ) -> Result<VoteSummary, AppError> {
+        let (column, id) = vote_column(target);
+        let (score, vote): (i64, Option<i16>) = sqlx::query_as(&format!(
+            "SELECT COALESCE(SUM(value), 0), MAX(value) FILTER (WHERE user_id = ?2) \
+             FROM votes WHERE {column} = ?1"
+        ))
+        .bind(id)
+        .bind(user_id.0)
+        .fetch_one(&self.conn_pool)
+        .await?;
+
+        Ok(VoteSummary {
+            score,
+            vote: vote.and_then(VoteDirection::from_value),
+        })
+    }
+}
+
+/// The votes column pointing at `target`, and its id
+fn vote_column(target: VoteTarget) -> (&'static str, i32) {
+    match target {
+        VoteTarget::Question(question_id) => ("question_id", question_id.0),
+        VoteTarget::Answer(answer_id) => ("answer_id", answer_id.0),
+    }
+}
+
+#[async_trait]
+impl Repository for SqliteStore {
+    async fn add_answer(
+        &mut self,
+        content: String,
+        question_id: i32,
+        author_id: UserId,
+    ) -> Result<Answer, AppError> {
+        // Deleted questions take no new answers
+        self.get_question_by_id(question_id).await?;
+
+        let mut tx = self.conn_pool.begin().await?;
+
+        let row: AnswerDbResult = sqlx::query_as(&format!(
+            "INSERT INTO answers (content, question_id, author_id, created_on) \
+             VALUES (?1, ?2, ?3, ?4) RETURNING {ANSWER_COLUMNS}"
+        ))
+        .bind(content)
+        .bind(question_id)
+        .bind(author_id.0)
+        .bind(pagination::now())
+        .fetch_one(&mut tx)
+        .await?;
+
+        record_answer_revision(&mut tx, row.id, Some(author_id)).await?;
+        tx.commit().await?;
+
+        Ok(row.into())
+    }
+
+    async fn get_answers_for_question(
+        &mut self,
+        question_id: QuestionId,
+    ) -> Result<Vec<Answer>, AppError> {
+        // 404 for an unknown question rather than an empty list
+        self.get_question_by_id(question_id).await?;
+
+        let rows: Vec<AnswerDbResult> = sqlx::query_as(&format!(
+            "SELECT {ANSWER_COLUMNS} FROM answers \
+             WHERE question_id = ?1 AND deleted_at IS NULL ORDER BY created_on, id"
+        ))
+        .bind(question_id.0)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Answer::from).collect())
+    }
+
+    async fn get_answer_by_id(&mut self, answer_id: AnswerId) -> Result<Answer, AppError> {
+        let row: AnswerDbResult = sqlx::query_as(&format!(
+            "SELECT {ANSWER_COLUMNS} FROM answers WHERE id = ?1 AND deleted_at IS NULL"
+        ))
+        .bind(answer_id.0)
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::Answer(AnswerError::InvalidId))?;
+
+        Ok(row.into())
+    }
+
+    async fn update_answer(
+        &mut self,
+        answer_id: AnswerId,
+        content: String,
+        user: &AuthUser,
+    ) -> Result<Answer, AppError> {
+        let existing = self.get_answer_by_id(answer_id).await?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let mut tx = self.conn_pool.begin().await?;
+        let row = apply_answer_edit(&mut tx, answer_id, &content, user.id).await?;
+        tx.commit().await?;
+
+        Ok(row.into())
+    }
+
+    async fn delete_answer(
+        &mut self,
+        answer_id: AnswerId,
+        user: &AuthUser,
+    ) -> Result<(), AppError> {
+        let existing = self.get_answer_by_id(answer_id).await?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let now = pagination::now();
+        let mut tx = self.conn_pool.begin().await?;
+
+        sqlx::query("UPDATE answers SET deleted_at = ?2 WHERE id = ?1")
+            .bind(answer_id.0)
+            .bind(now)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query(
+            "UPDATE comments SET deleted_at = ?2 \
+             WHERE applied_to_answer_id = ?1 AND deleted_at IS NULL",
+        )
+        .bind(answer_id.0)
+        .bind(now)
+        .execute(&mut tx)
+        .await?;
+
+        // A deleted answer can't stay the accepted one
+        sqlx::query("UPDATE questions SET accepted_answer_id = NULL WHERE accepted_answer_id = ?1")
+            .bind(answer_id.0)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_all_questions(
+        &mut self,
+        params: &PageParams,
+        filter: &QuestionFilter,
+    ) -> Result<Page<Question>, AppError> {
+        let cursor = match &params.cursor {
+            Some(cursor) => Some(Cursor::decode(cursor).ok_or_else(|| {
+                AppError::Validation(vec![FieldError::new("cursor", "is not a valid cursor")])
+            })?),
+            None => None,
+        };
+        let limit = params.limit();
+        // Keyset pages start right after the cursor, so the offset only applies without one
+        let offset = if cursor.is_some() { 0 } else { params.offset() };
+
+        let mut query = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {QUESTION_COLUMNS} FROM questions q WHERE q.deleted_at IS NULL"
+        ));
+        push_question_filters(&mut query, filter);
+
+        if let Some(cursor) = cursor {
+            query.push(if filter.sort == QuestionSort::Oldest {
+                " AND (q.created_on, q.id) > ("
+            } else {
+                " AND (q.created_on, q.id) < ("
+            });
+            query.push_bind(cursor.created_on);
+            query.push(", ");
+            query.push_bind(cursor.id);
+            query.push(")");
+        }
+
+        query.push(match filter.sort {
+            QuestionSort::Newest => " ORDER BY q.created_on DESC, q.id DESC",
+            QuestionSort::Oldest => " ORDER BY q.created_on ASC, q.id ASC",
+            QuestionSort::MostAnswers => {
+                " ORDER BY (SELECT COUNT(*) FROM answers a \
+                  WHERE a.question_id = q.id AND a.deleted_at IS NULL) DESC, \
+                 q.created_on DESC, q.id DESC"
+            }
+            QuestionSort::Votes => " ORDER BY score DESC, q.created_on DESC, q.id DESC",
+            QuestionSort::Unanswered => {
+                " ORDER BY EXISTS (SELECT 1 FROM answers a WHERE a.question_id = q.id AND a.deleted_at IS NULL), \
+                 q.created_on DESC, q.id DESC"
+            }
+        });
+
+        // Fetch one extra row to find out whether there is a next page
+        query.push(" LIMIT ");
+        query.push_bind(limit + 1);
+        query.push(" OFFSET ");
+        query.push_bind(offset);
+
+        let mut questions: Vec<Question> = query
+            .build_query_as::<QuestionRow>()
+            .fetch_all(&self.conn_pool)
+            .await?
+            .into_iter()
+            .map(Question::from)
+            .collect();
+
+        let has_more = questions.len() as i64 > limit;
+        questions.truncate(limit as usize);
+
+        let next_cursor = match questions.last() {
+            Some(last) if has_more && filter.sort.supports_cursor() => Some(
+                Cursor {
+                    created_on: last.created_on,
+                    id: last.id.0,
+                }
+                .encode(),
+            ),
+            _ => None,
+        };
+
+        let mut count = QueryBuilder::<Sqlite>::new(
+            "SELECT COUNT(*) FROM questions q WHERE q.deleted_at IS NULL",
+        );
+        push_question_filters(&mut count, filter);
+        let (total,): (i64,) = count.build_query_as().fetch_one(&self.conn_pool).await?;
+
+        Ok(Page {
+            items: questions,
+            next_cursor,
+            total,
+        })
+    }
+
+    async fn get_question_by_id<T: IntoQuestionId + Send>(
+        &mut self,
+        id: T,
+    ) -> Result<Question, AppError> {
+        let row = fetch_question(&self.conn_pool, id.into_question_id()).await?;
+
+        Ok(row.into())
+    }
+
+    /// Loads a whole question thread, in four queries like the Postgres store
+    async fn get_question_comments(
+        &mut self,
+        question_id: i32,
+    ) -> Result<QuestionResult, AppError> {
+        let q_row = fetch_question(&self.conn_pool, QuestionId(question_id)).await?;
+
+        let c_rows: Vec<CommentRow> = sqlx::query_as(&format!(
+            "SELECT {COMMENT_COLUMNS} FROM comments \
+             WHERE applied_to_question_id = ?1 AND deleted_at IS NULL \
+             ORDER BY created_on DESC"
+        ))
+        .bind(question_id)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        let a_rows: Vec<ThreadAnswerRow> = sqlx::query_as(
+            "SELECT a.id, a.content, a.author_id, a.created_on, \
+             COALESCE((SELECT SUM(v.value) FROM votes v WHERE v.answer_id = a.id), 0) AS score \
+             FROM answers a \
+             WHERE a.question_id = ?1 AND a.deleted_at IS NULL \
+             ORDER BY COALESCE(a.id = ?2, 0) DESC, score DESC, a.created_on DESC, a.id DESC",
+        )
+        .bind(question_id)
+        .bind(q_row.accepted_answer_id)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        // One batched lookup for every answer's comments, grouped by answer below
+        let ac_rows: Vec<CommentRow> = sqlx::query_as(&format!(
+            "SELECT {COMMENT_COLUMNS} FROM comments \
+             WHERE applied_to_answer_id IN \
+                 (SELECT id FROM answers WHERE question_id = ?1 AND deleted_at IS NULL) \
+             AND deleted_at IS NULL \
+             ORDER BY created_on DESC"
+        ))
+        .bind(question_id)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        let mut answer_comments: HashMap<i32, Vec<CommentResult>> = HashMap::new();
+        for row in ac_rows {
+            if let Some(answer_id) = row.applied_to_answer_id {
+                answer_comments
+                    .entry(answer_id)
+                    .or_default()
+                    .push(comment_result(row));
+            }
+        }
+
+        let accepted_answer_id = q_row.accepted_answer_id;
+        let question = Question::from(q_row);
+
+        Ok(QuestionResult {
+            id: question.id.0,
+            title: question.title,
+            content: question.content,
+            tags: question.tags,
+            author_id: question.author_id.map(|id| id.0),
+            created_on: question.created_on,
+            score: question.score,
+            accepted_answer_id,
+            comments: c_rows.into_iter().map(comment_result).collect(),
+            answers: a_rows
+                .into_iter()
+                .map(|row| AnswerResult {
+                    id: row.id,
+                    content: row.content,
+                    author_id: row.author_id,
+                    created_on: row.created_on,
+                    score: row.score,
+                    comments: answer_comments.remove(&row.id).unwrap_or_default(),
+                })
+                .collect(),
+        })
+    }
+
+    async fn add_question(
+        &mut self,
+        title: String,
+        content: String,
+        tags: Option<Vec<String>>,
+        author_id: UserId,
+    ) -> Result<Question, AppError> {
+        let mut tx = self.conn_pool.begin().await?;
+
+        let question_id: i32 = sqlx::query_scalar(
+            "INSERT INTO questions (title, content, author_id, created_on) \
+             VALUES (?1, ?2, ?3, ?4) RETURNING id",
+        )
+        .bind(title)
+        .bind(content)
+        .bind(author_id.0)
+        .bind(pagination::now())
+        .fetch_one(&mut tx)
+        .await?;
+
+        set_question_tags(&mut tx, question_id, &tags.unwrap_or_default()).await?;
+        record_question_revision(&mut tx, question_id, Some(author_id)).await?;
+        tx.commit().await?;
+
+        self.get_question_by_id(QuestionId(question_id)).await
+    }
+
+    async fn update_question(
+        &mut self,
+        new_question: UpdateQuestion,
+        if_match: Option<&EtagCondition>,
+        user: &AuthUser,
+    ) -> Result<Question, AppError> {
+        let existing = self.get_question_by_id(new_question.id).await?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let mut tx = self.conn_pool.begin().await?;
+
+        // SQLite has no FOR UPDATE. Writing first takes the database's write lock right away,
+        // so a concurrent edit can't slip in between check and write.
+        sqlx::query("UPDATE questions SET version = version WHERE id = ?1")
+            .bind(new_question.id.0)
+            .execute(&mut tx)
+            .await?;
+
+        let version: i32 = sqlx::query_scalar("SELECT version FROM questions WHERE id = ?1")
+            .bind(new_question.id.0)
+            .fetch_one(&mut tx)
+            .await?;
+
+        if let Some(if_match) = if_match {
+            if !if_match.matches(version) {
+                return Err(AppError::Question(QuestionError::VersionMismatch));
+            }
+        }
+
+        apply_question_edit(
+            &mut tx,
+            new_question.id,
+            &new_question.title,
+            &new_question.content,
+            &new_question.tags.unwrap_or_default(),
+            user.id,
+        )
+        .await?;
+        tx.commit().await?;
+
+        self.get_question_by_id(new_question.id).await
+    }
+
+    async fn get_question_revisions(
+        &mut self,
+        question_id: QuestionId,
+    ) -> Result<Vec<QuestionRevision>, AppError> {
+        self.get_question_by_id(question_id).await?;
+
+        let rows: Vec<QuestionRevisionRow> = sqlx::query_as(
+            "SELECT revision, title, content, tags, editor_id, edited_on \
+             FROM question_revisions WHERE question_id = ?1 ORDER BY revision",
+        )
+        .bind(question_id.0)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        Ok(rows.into_iter().map(QuestionRevision::from).collect())
+    }
+
+    async fn get_question_revision(
+        &mut self,
+        question_id: QuestionId,
+        revision: i32,
+    ) -> Result<QuestionRevision, AppError> {
+        let row: QuestionRevisionRow = sqlx::query_as(
+            "SELECT revision, title, content, tags, editor_id, edited_on \
+             FROM question_revisions WHERE question_id = ?1 AND revision = ?2",
+        )
+        .bind(question_id.0)
+        .bind(revision)
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::Revision(RevisionError::NotFound))?;
+
+        Ok(row.into())
+    }
+
+    async fn diff_question_revisions(
+        &mut self,
+        question_id: QuestionId,
+        from: i32,
+        to: i32,
+    ) -> Result<RevisionDiff, AppError> {
+        self.get_question_by_id(question_id).await?;
+
+        let from = self.get_question_revision(question_id, from).await?;
+        let to = self.get_question_revision(question_id, to).await?;
+
+        Ok(RevisionDiff::between_questions(&from, &to))
+    }
+
+    async fn rollback_question(
+        &mut self,
+        question_id: QuestionId,
+        revision: i32,
+        user: &AuthUser,
+    ) -> Result<Question, AppError> {
+        let existing = self.get_question_by_id(question_id).await?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let revision = self.get_question_revision(question_id, revision).await?;
+
+        let mut tx = self.conn_pool.begin().await?;
+        apply_question_edit(
+            &mut tx,
+            question_id,
+            &revision.title,
+            &revision.content,
+            &revision.tags,
+            user.id,
+        )
+        .await?;
+        tx.commit().await?;
+
+        self.get_question_by_id(question_id).await
+    }
+
+    async fn get_answer_revisions(
+        &mut self,
+        answer_id: AnswerId,
+    ) -> Result<Vec<AnswerRevision>, AppError> {
+        self.get_answer_by_id(answer_id).await?;
+
+        let revisions = sqlx::query_as(
+            "SELECT revision, content, editor_id, edited_on \
+             FROM answer_revisions WHERE answer_id = ?1 ORDER BY revision",
+        )
+        .bind(answer_id.0)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        Ok(revisions)
+    }
+
+    async fn get_answer_revision(
+        &mut self,
+        answer_id: AnswerId,
+        revision: i32,
+    ) -> Result<AnswerRevision, AppError> {
+        let revision = sqlx::query_as(
+            "SELECT revision, content, editor_id, edited_on \
+             FROM answer_revisions WHERE answer_id = ?1 AND revision = ?2",
+        )
+        .bind(answer_id.0)
+        .bind(revision)
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::Revision(RevisionError::NotFound))?;
+
+        Ok(revision)
+    }
+
+    async fn diff_answer_revisions(
+        &mut self,
+        answer_id: AnswerId,
+        from: i32,
+        to: i32,
+    ) -> Result<RevisionDiff, AppError> {
+        self.get_answer_by_id(answer_id).await?;
+
+        let from = self.get_answer_revision(answer_id, from).await?;
+        let to = self.get_answer_revision(answer_id, to).await?;
+
+        Ok(RevisionDiff::between_answers(&from, &to))
+    }
+
+    async fn rollback_answer(
+        &mut self,
+        answer_id: AnswerId,
+        revision: i32,
+        user: &AuthUser,
+    ) -> Result<Answer, AppError> {
+        let existing = self.get_answer_by_id(answer_id).await?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let revision = self.get_answer_revision(answer_id, revision).await?;
+
+        let mut tx = self.conn_pool.begin().await?;
+        let row = apply_answer_edit(&mut tx, answer_id, &revision.content, user.id).await?;
+        tx.commit().await?;
+
+        Ok(row.into())
+    }
+
+    async fn get_tags(&mut self) -> Result<Vec<Tag>, AppError> {
+        let tags = sqlx::query_as(
+            "SELECT t.slug, COUNT(qt.question_id) AS question_count \
+             FROM tags t \
+             LEFT JOIN (question_tags qt JOIN questions q \
+                        ON q.id = qt.question_id AND q.deleted_at IS NULL) \
+                 ON qt.tag_id = t.id \
+             GROUP BY t.id \
+             ORDER BY COUNT(qt.question_id) DESC, t.slug",
+        )
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        Ok(tags)
+    }
+
+    async fn get_tag(&mut self, slug: &str) -> Result<Tag, AppError> {
+        let tag = sqlx::query_as(
+            "SELECT t.slug, COUNT(qt.question_id) AS question_count \
+             FROM tags t \
+             LEFT JOIN (question_tags qt JOIN questions q \
+                        ON q.id = qt.question_id AND q.deleted_at IS NULL) \
+                 ON qt.tag_id = t.id \
+             WHERE t.slug = ?1 OR t.id = (SELECT tag_id FROM tag_synonyms WHERE synonym = ?1) \
+             GROUP BY t.id",
+        )
+        .bind(slug)
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::Tag(TagError::NotFound))?;
+
+        Ok(tag)
+    }
+
+    async fn merge_tags(
+        &mut self,
+        source: &str,
+        merge: MergeTag,
+        user: &AuthUser,
+    ) -> Result<TagMerge, AppError> {
+        let source = slugify(source);
+        let target = self.get_tag(&slugify(&merge.into)).await?;
+        if source.is_empty() || source == target.slug {
+            return Err(AppError::Validation(vec![FieldError::new(
+                "into",
+                "must name a different tag than the one being merged",
+            )]));
+        }
+
+        let mut tx = self.conn_pool.begin().await?;
+
+        let target_id: i32 = sqlx::query_scalar("SELECT id FROM tags WHERE slug = ?1")
+            .bind(&target.slug)
+            .fetch_one(&mut tx)
+            .await?;
+
+        let source_id: Option<i32> = sqlx::query_scalar("SELECT id FROM tags WHERE slug = ?1")
+            .bind(&source)
+            .fetch_optional(&mut tx)
+            .await?;
+
+        let mut retagged: Vec<i32> = Vec::new();
+        if let Some(source_id) = source_id {
+            retagged = sqlx::query_scalar(
+                "SELECT question_id FROM question_tags WHERE tag_id = ?1 ORDER BY question_id",
+            )
+            .bind(source_id)
+            .fetch_all(&mut tx)
+            .await?;
+
+            sqlx::query(
+                "INSERT OR IGNORE INTO question_tags (question_id, tag_id) \
+                 SELECT question_id, ?2 FROM question_tags WHERE tag_id = ?1",
+            )
+            .bind(source_id)
+            .bind(target_id)
+            .execute(&mut tx)
+            .await?;
+
+            // Synonyms of the old tag now point at the new one
+            sqlx::query("UPDATE tag_synonyms SET tag_id = ?2 WHERE tag_id = ?1")
+                .bind(source_id)
+                .bind(target_id)
+                .execute(&mut tx)
+                .await?;
+
+            // Takes the source's question_tags rows with it
+            sqlx::query("DELETE FROM tags WHERE id = ?1")
+                .bind(source_id)
+                .execute(&mut tx)
+                .await?;
+
+            // Retagging changes the questions like an edit would
+            for question_id in &retagged {
+                sqlx::query("UPDATE questions SET version = version + 1 WHERE id = ?1")
+                    .bind(question_id)
+                    .execute(&mut tx)
+                    .await?;
+                record_question_revision(&mut tx, *question_id, Some(user.id)).await?;
+            }
+        }
+
+        let now = pagination::now();
+        sqlx::query(
+            "INSERT INTO tag_synonyms (synonym, tag_id, created_by, created_on) \
+             VALUES (?1, ?2, ?3, ?4) \
+             ON CONFLICT (synonym) DO UPDATE \
+             SET tag_id = excluded.tag_id, created_by = excluded.created_by",
+        )
+        .bind(&source)
+        .bind(target_id)
+        .bind(user.id.0)
+        .bind(now)
+        .execute(&mut tx)
+        .await?;
+
+        let merged = sqlx::query_as(
+            "INSERT INTO tag_merges (source_slug, target_slug, question_count, merged_by, merged_on) \
+             VALUES (?1, ?2, ?3, ?4, ?5) \
+             RETURNING source_slug, target_slug, question_count, merged_by, merged_on",
+        )
+        .bind(&source)
+        .bind(&target.slug)
+        .bind(retagged.len() as i32)
+        .bind(user.id.0)
+        .bind(now)
+        .fetch_one(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(merged)
+    }
+
+    async fn delete_question(&mut self, question_id: i32, user: &AuthUser) -> Result<(), AppError> {
+        let existing = self.get_question_by_id(question_id).await?;
+        if !user.can_modify(existing.author_id) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        // The question and everything under it share one deleted_at, see undelete_question
+        let now = pagination::now();
+        let mut tx = self.conn_pool.begin().await?;
+
+        sqlx::query("UPDATE questions SET deleted_at = ?2 WHERE id = ?1")
+            .bind(question_id)
+            .bind(now)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query(
+            "UPDATE comments SET deleted_at = ?2 \
+             WHERE deleted_at IS NULL \
+               AND (applied_to_question_id = ?1 \
+                    OR applied_to_answer_id IN (SELECT id FROM answers WHERE question_id = ?1))",
+        )
+        .bind(question_id)
+        .bind(now)
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            "UPDATE answers SET deleted_at = ?2 WHERE question_id = ?1 AND deleted_at IS NULL",
+        )
+        .bind(question_id)
+        .bind(now)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn undelete_question(&mut self, question_id: QuestionId) -> Result<Question, AppError> {
+        // Read back as the stored text, so comparing it below can't trip over formatting
+        let deleted_at: Option<String> =
+            sqlx::query_scalar("SELECT deleted_at FROM questions WHERE id = ?1")
+                .bind(question_id.0)
+                .fetch_optional(&self.conn_pool)
+                .await?
+                .ok_or(AppError::Question(QuestionError::InvalidId))?;
+
+        if let Some(deleted_at) = deleted_at {
+            let mut tx = self.conn_pool.begin().await?;
+
+            sqlx::query(
+                "UPDATE comments SET deleted_at = NULL \
+                 WHERE deleted_at = ?2 \
+                   AND (applied_to_question_id = ?1 \
+                        OR applied_to_answer_id IN (SELECT id FROM answers WHERE question_id = ?1))",
+            )
+            .bind(question_id.0)
+            .bind(&deleted_at)
+            .execute(&mut tx)
+            .await?;
+
+            sqlx::query(
+                "UPDATE answers SET deleted_at = NULL WHERE question_id = ?1 AND deleted_at = ?2",
+            )
+            .bind(question_id.0)
+            .bind(&deleted_at)
+            .execute(&mut tx)
+            .await?;
+
+            sqlx::query("UPDATE questions SET deleted_at = NULL WHERE id = ?1")
+                .bind(question_id.0)
+                .execute(&mut tx)
+                .await?;
+
+            tx.commit().await?;
+        }
+
+        self.get_question_by_id(question_id).await
+    }
+
+    async fn purge_deleted(&mut self, cutoff: DateTime<Utc>) -> Result<PurgeCounts, AppError> {
+        let mut tx = self.conn_pool.begin().await?;
+
+        let questions = sqlx::query("DELETE FROM questions WHERE deleted_at < ?1")
+            .bind(cutoff)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+
+        let answers = sqlx::query("DELETE FROM answers WHERE deleted_at < ?1")
+            .bind(cutoff)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+
+        let comments = sqlx::query("DELETE FROM comments WHERE deleted_at < ?1")
+            .bind(cutoff)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+
+        Ok(PurgeCounts {
+            questions,
+            answers,
+            comments,
+        })
+    }
+
+    async fn accept_answer(
+        &mut self,
+        question_id: QuestionId,
+        answer_id: AnswerId,
+        user: &AuthUser,
+    ) -> Result<QuestionResult, AppError> {
+        let question = self.get_question_by_id(question_id).await?;
+        if question.author_id != Some(user.id) {
+            return Err(AppError::Forbidden(Forbidden::not_question_author()));
+        }
+
+        let answer = self.get_answer_by_id(answer_id).await?;
+        if answer.question_id != question_id {
+            return Err(AppError::Answer(AnswerError::NotOnQuestion));
+        }
+
+        sqlx::query("UPDATE questions SET accepted_answer_id = ?1 WHERE id = ?2")
+            .bind(answer_id.0)
+            .bind(question_id.0)
+            .execute(&self.conn_pool)
+            .await?;
+
+        self.get_question_comments(question_id.0).await
+    }
+
+    async fn unaccept_answer(
+        &mut self,
+        question_id: QuestionId,
+        user: &AuthUser,
+    ) -> Result<QuestionResult, AppError> {
+        let question = self.get_question_by_id(question_id).await?;
+        if question.author_id != Some(user.id) {
+            return Err(AppError::Forbidden(Forbidden::not_question_author()));
+        }
+
+        sqlx::query("UPDATE questions SET accepted_answer_id = NULL WHERE id = ?1")
+            .bind(question_id.0)
+            .execute(&self.conn_pool)
+            .await?;
+
+        self.get_question_comments(question_id.0).await
+    }
+
+    async fn cast_vote(
+        &mut self,
+        target: VoteTarget,
+        user_id: UserId,
+        direction: VoteDirection,
+    ) -> Result<VoteSummary, AppError> {
+        match target {
+            VoteTarget::Question(question_id) => {
+                self.get_question_by_id(question_id).await?;
+            }
+            VoteTarget::Answer(answer_id) => {
+                self.get_answer_by_id(answer_id).await?;
+            }
+        }
+
+        let (column, id) = vote_column(target);
+        sqlx::query(&format!(
+            "INSERT INTO votes (user_id, {column}, value, created_on) VALUES (?1, ?2, ?3, ?4) \
+             ON CONFLICT (user_id, {column}) DO UPDATE \
+             SET value = excluded.value, created_on = excluded.created_on"
+        ))
+        .bind(user_id.0)
+        .bind(id)
+        .bind(direction.value())
+        .bind(pagination::now())
+        .execute(&self.conn_pool)
+        .await?;
+
+        self.vote_summary(target, user_id).await
+    }
+
+    async fn retract_vote(
+        &mut self,
+        target: VoteTarget,
+        user_id: UserId,
+    ) -> Result<VoteSummary, AppError> {
+        match target {
+            VoteTarget::Question(question_id) => {
+                self.get_question_by_id(question_id).await?;
+            }
+            VoteTarget::Answer(answer_id) => {
+                self.get_answer_by_id(answer_id).await?;
+            }
+        }
+
+        let (column, id) = vote_column(target);
+        sqlx::query(&format!(
+            "DELETE FROM votes WHERE user_id = ?1 AND {column} = ?2"
+        ))
+        .bind(user_id.0)
+        .bind(id)
+        .execute(&self.conn_pool)
+        .await?;
+
+        self.vote_summary(target, user_id).await
+    }
+
+    async fn add_comment(
+        &mut self,
+        content: String,
+        target: CommentTarget,
+        author_id: UserId,
+    ) -> Result<CommentDbResult, AppError> {
+        let (question_id, answer_id) = match target {
+            CommentTarget::Question(question_id) => {
+                self.get_question_by_id(question_id).await?;
+                (Some(question_id.0), None)
+            }
+            CommentTarget::Answer(answer_id) => {
+                self.get_answer_by_id(answer_id).await?;
+                (None, Some(answer_id.0))
+            }
+        };
+
+        let row: CommentRow = sqlx::query_as(&format!(
+            "INSERT INTO comments \
+                 (content, applied_to_question_id, applied_to_answer_id, author_id, created_on) \
+             VALUES (?1, ?2, ?3, ?4, ?5) RETURNING {COMMENT_COLUMNS}"
+        ))
+        .bind(content)
+        .bind(question_id)
+        .bind(answer_id)
+        .bind(author_id.0)
+        .bind(pagination::now().naive_utc())
+        .fetch_one(&self.conn_pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    async fn get_comments_for_question(
+        &mut self,
+        question_id: QuestionId,
+    ) -> Result<Vec<CommentDbResult>, AppError> {
+        // 404 for an unknown question rather than an empty list
+        self.get_question_by_id(question_id).await?;
+
+        let rows: Vec<CommentRow> = sqlx::query_as(&format!(
+            "SELECT {COMMENT_COLUMNS} FROM comments \
+             WHERE applied_to_question_id = ?1 AND deleted_at IS NULL \
+             ORDER BY created_on, id"
+        ))
+        .bind(question_id.0)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        Ok(rows.into_iter().map(CommentDbResult::from).collect())
+    }
+
+    async fn get_comments_for_answer(
+        &mut self,
+        answer_id: AnswerId,
+    ) -> Result<Vec<CommentDbResult>, AppError> {
+        self.get_answer_by_id(answer_id).await?;
+
+        let rows: Vec<CommentRow> = sqlx::query_as(&format!(
+            "SELECT {COMMENT_COLUMNS} FROM comments \
+             WHERE applied_to_answer_id = ?1 AND deleted_at IS NULL \
+             ORDER BY created_on, id"
+        ))
+        .bind(answer_id.0)
+        .fetch_all(&self.conn_pool)
+        .await?;
+
+        Ok(rows.into_iter().map(CommentDbResult::from).collect())
+    }
+
+    async fn get_comment_by_id(
+        &mut self,
+        comment_id: CommentId,
+    ) -> Result<CommentDbResult, AppError> {
+        let row: CommentRow = sqlx::query_as(&format!(
+            "SELECT {COMMENT_COLUMNS} FROM comments WHERE id = ?1 AND deleted_at IS NULL"
+        ))
+        .bind(comment_id.0)
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::Comment(CommentError::InvalidId))?;
+
+        Ok(row.into())
+    }
+
+    async fn update_comment(
+        &mut self,
+        comment_id: CommentId,
+        content: String,
+        user: &AuthUser,
+    ) -> Result<CommentDbResult, AppError> {
+        let existing = self.get_comment_by_id(comment_id).await?;
+        if !user.can_modify(existing.author_id.map(UserId)) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        let row: CommentRow = sqlx::query_as(&format!(
+            "UPDATE comments SET content = ?1 WHERE id = ?2 RETURNING {COMMENT_COLUMNS}"
+        ))
+        .bind(content)
+        .bind(comment_id.0)
+        .fetch_one(&self.conn_pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    async fn delete_comment(
+        &mut self,
+        comment_id: CommentId,
+        user: &AuthUser,
+    ) -> Result<(), AppError> {
+        let existing = self.get_comment_by_id(comment_id).await?;
+        if !user.can_modify(existing.author_id.map(UserId)) {
+            return Err(AppError::Forbidden(Forbidden::not_author()));
+        }
+
+        sqlx::query("UPDATE comments SET deleted_at = ?2 WHERE id = ?1")
+            .bind(comment_id.0)
+            .bind(pagination::now())
+            .execute(&self.conn_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// SQLite has no full-text search built in, so the posts containing every wanted word
+    /// are narrowed down with LIKE and then ranked by `TermSearch`
+    async fn search(
+        &mut self,
+        terms: &str,
+        params: &PageParams,
+    ) -> Result<Page<SearchResult>, AppError> {
+        let search = TermSearch::parse(terms);
+        if search.wanted.is_empty() {
+            return Ok(search.page(Vec::new(), params));
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "WITH hits AS ( \
+                 SELECT 'question' AS entity_type, q.id, q.id AS question_id, \
+                        q.title AS question_title, q.title || ' ' || q.content AS body \
+                 FROM questions q WHERE q.deleted_at IS NULL \
+                 UNION ALL \
+                 SELECT 'answer', a.id, q.id, q.title, a.content \
+                 FROM answers a JOIN questions q ON q.id = a.question_id \
+                 WHERE a.deleted_at IS NULL \
+                 UNION ALL \
+                 SELECT 'comment', c.id, q.id, q.title, c.content \
+                 FROM comments c \
+                 LEFT JOIN answers a ON a.id = c.applied_to_answer_id \
+                 JOIN questions q ON q.id = COALESCE(c.applied_to_question_id, a.question_id) \
+                 WHERE c.deleted_at IS NULL \
+             ) \
+             SELECT entity_type, id, question_id, question_title, body FROM hits WHERE 1 = 1",
+        );
+        // The words are plain letters and digits, so they need no escaping
+        for word in &search.wanted {
+            query.push(" AND body LIKE ");
+            query.push_bind(format!("%{}%", word));
+        }
+
+        let rows: Vec<SearchRow> = query.build_query_as().fetch_all(&self.conn_pool).await?;
+
+        let hits = rows
+            .into_iter()
+            .filter_map(|row| {
+                search.hit(
+                    SearchEntity::from_db(&row.entity_type),
+                    row.id,
+                    row.question_id,
+                    row.question_title,
+                    &row.body,
+                )
+            })
+            .collect();
+
+        Ok(search.page(hits, params))
+    }
+
+    async fn add_user(&mut self, email: String, password_hash: String) -> Result<User, AppError> {
+        let row: Option<UserDbResult> = sqlx::query_as(&format!(
+            "INSERT INTO users (email, password_hash, created_on) VALUES (?1, ?2, ?3) \
+             ON CONFLICT (email) DO NOTHING RETURNING {USER_COLUMNS}"
+        ))
+        .bind(email)
+        .bind(password_hash)
+        .bind(pagination::now())
+        .fetch_optional(&self.conn_pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(row.into()),
+            None => Err(AppError::User(UserError::EmailTaken)),
+        }
+    }
+
+    async fn get_user_by_email(&mut self, email: &str) -> Result<Option<UserDbResult>, AppError> {
+        let row = sqlx::query_as(&format!(
+            "SELECT {USER_COLUMNS} FROM users WHERE email = ?1"
+        ))
+        .bind(email)
+        .fetch_optional(&self.conn_pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    async fn get_user_by_id(&mut self, user_id: UserId) -> Result<Option<User>, AppError> {
+        let row: Option<UserDbResult> =
+            sqlx::query_as(&format!("SELECT {USER_COLUMNS} FROM users WHERE id = ?1"))
+                .bind(user_id.0)
+                .fetch_optional(&self.conn_pool)
+                .await?;
+
+        Ok(row.map(User::from))
+    }
+
+    async fn get_all_users(&mut self) -> Result<Vec<User>, AppError> {
+        let rows: Vec<UserDbResult> =
+            sqlx::query_as(&format!("SELECT {USER_COLUMNS} FROM users ORDER BY id"))
+                .fetch_all(&self.conn_pool)
+                .await?;
+
+        Ok(rows.into_iter().map(User::from).collect())
+    }
+
+    async fn set_user_role(&mut self, user_id: UserId, role: Role) -> Result<User, AppError> {
+        let row: UserDbResult = sqlx::query_as(&format!(
+            "UPDATE users SET role = ?1 WHERE id = ?2 RETURNING {USER_COLUMNS}"
+        ))
+        .bind(role.as_str())
+        .bind(user_id.0)
+        .fetch_optional(&self.conn_pool)
+        .await?
+        .ok_or(AppError::User(UserError::NotFound))?;
+
+        Ok(row.into())
+    }
+
+    async fn delete_user(&mut self, user_id: UserId) -> Result<(), AppError> {
+        let result = sqlx::query("DELETE FROM users WHERE id = ?1")
+            .bind(user_id.0)
+            .execute(&self.conn_pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::User(UserError::NotFound));
+        }
+
+        Ok(())
+    }
+}
+
+async fn fetch_question<'c, E>(
+    executor: E,
+    question_id: QuestionId,
+) -> Result<QuestionRow, AppError>
+where
+    E: sqlx::Executor<'c, Database = Sqlite>,
+{
+    let row = sqlx::query_as(&format!(
+        "SELECT {QUESTION_COLUMNS} FROM questions q WHERE q.id = ?1 AND q.deleted_at IS NULL"
+    ))
+    .bind(question_id.0)
+    .fetch_optional(executor)
+    .await?
+    .ok_or(AppError::Question(QuestionError::InvalidId))?;
+
+    Ok(row)
+}
+
+/// Saves an edit to a question along with the revision recording it
+async fn apply_question_edit(
+    tx: &mut Transaction<'_, Sqlite>,
+    question_id: QuestionId,
+    title: &str,
+    content: &str,
+    tags: &[String],
+    editor_id: UserId,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "UPDATE questions SET title = ?1, content = ?2, version = version + 1 WHERE id = ?3",
+    )
+    .bind(title)
+    .bind(content)
+    .bind(question_id.0)
+    .execute(&mut *tx)
+    .await?;
+
+    set_question_tags(&mut *tx, question_id.0, tags).await?;
+    record_question_revision(tx, question_id.0, Some(editor_id)).await
+}
+
+/// Snapshots the question as it now stands under the next revision number. Every question
+/// here was written by this store, so unlike in Postgres there are none without history.
+async fn record_question_revision(
+    tx: &mut Transaction<'_, Sqlite>,
+    question_id: i32,
+    editor_id: Option<UserId>,
+) -> Result<(), AppError> {
+    let question = Question::from(
+        sqlx::query_as::<_, QuestionRow>(&format!(
+            "SELECT {QUESTION_COLUMNS} FROM questions q WHERE q.id = ?1"
+        ))
+        .bind(question_id)
+        .fetch_one(&mut *tx)
+        .await?,
+    );
+
+    sqlx::query(
+        "INSERT INTO question_revisions \
+             (question_id, revision, title, content, tags, editor_id, edited_on) \
+         VALUES (?1, \
+                 COALESCE((SELECT MAX(revision) FROM question_revisions WHERE question_id = ?1), 0) + 1, \
+                 ?2, ?3, ?4, ?5, ?6)",
+    )
+    .bind(question_id)
+    .bind(question.title)
+    .bind(question.content)
+    .bind(Json(question.tags.unwrap_or_default()))
+    .bind(editor_id.map(|id| id.0))
+    .bind(pagination::now())
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Saves an edit to an answer along with the revision recording it
+async fn apply_answer_edit(
+    tx: &mut Transaction<'_, Sqlite>,
+    answer_id: AnswerId,
+    content: &str,
+    editor_id: UserId,
+) -> Result<AnswerDbResult, AppError> {
+    let row = sqlx::query_as(&format!(
+        "UPDATE answers SET content = ?1 WHERE id = ?2 RETURNING {ANSWER_COLUMNS}"
+    ))
+    .bind(content)
+    .bind(answer_id.0)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    record_answer_revision(tx, answer_id.0, Some(editor_id)).await?;
+
+    Ok(row)
+}
+
+/// Snapshots the answer as it now stands under the next revision number
+async fn record_answer_revision(
+    tx: &mut Transaction<'_, Sqlite>,
+    answer_id: i32,
+    editor_id: Option<UserId>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO answer_revisions (answer_id, revision, content, editor_id, edited_on) \
+         SELECT a.id, \
+                COALESCE((SELECT MAX(revision) FROM answer_revisions WHERE answer_id = a.id), 0) + 1, \
+                a.content, ?2, ?3 \
+         FROM answers a WHERE a.id = ?1",
+    )
+    .bind(answer_id)
+    .bind(editor_id.map(|id| id.0))
+    .bind(pagination::now())
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Points a question at exactly the given tags, creating any tag that doesn't exist yet
+async fn set_question_tags(
+    tx: &mut Transaction<'_, Sqlite>,
+    question_id: i32,
+    tags: &[String],
+) -> Result<(), AppError> {
+    // Registered synonyms stand for their tag, so `js` is saved as `javascript`
+    let mut slugs = Vec::new();
+    for slug in normalize_tags(tags) {
+        let resolved: Option<String> = sqlx::query_scalar(
+            "SELECT t.slug FROM tag_synonyms ts JOIN tags t ON t.id = ts.tag_id \
+             WHERE ts.synonym = ?1",
+        )
+        .bind(&slug)
+        .fetch_optional(&mut *tx)
+        .await?;
+        slugs.push(resolved.unwrap_or(slug));
+    }
+
+    sqlx::query("DELETE FROM question_tags WHERE question_id = ?1")
+        .bind(question_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let now = pagination::now();
+    for slug in normalize_tags(&slugs) {
+        sqlx::query(
+            "INSERT INTO tags (slug, created_on) VALUES (?1, ?2) ON CONFLICT (slug) DO NOTHING",
+        )
+        .bind(&slug)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO question_tags (question_id, tag_id) SELECT ?1, id FROM tags WHERE slug = ?2",
+        )
+        .bind(question_id)
+        .bind(&slug)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// The WHERE conditions shared by the question listing and its total count
+fn push_question_filters(query: &mut QueryBuilder<Sqlite>, filter: &QuestionFilter) {
+    if !filter.tags.is_empty() {
+        query.push(
+            " AND (SELECT COUNT(*) FROM question_tags qt JOIN tags t ON t.id = qt.tag_id \
+             WHERE qt.question_id = q.id AND t.slug IN (",
+        );
+        let mut slugs = query.separated(", ");
+        for tag in &filter.tags {
+            slugs.push_bind(tag.clone());
+        }
+        match filter.tag_match {
+            TagMatch::All => {
+                query.push(")) = ");
+                query.push_bind(filter.tags.len() as i64);
+            }
+            TagMatch::Any => {
+                query.push(")) > 0");
+            }
+        }
+    }
+
+    if let Some(author) = filter.author {
+        query.push(" AND q.author_id = ");
+        query.push_bind(author.0);
+    }
+
+    if let Some(created_after) = filter.created_after {
+        query.push(" AND q.created_on > ");
+        query.push_bind(created_after);
+    }
+
+    if let Some(created_before) = filter.created_before {
+        query.push(" AND q.created_on < ");
+        query.push_bind(created_before);
+    }
+
+    match filter.has_answers {
+        Some(true) => {
+            query.push(" AND EXISTS (SELECT 1 FROM answers a WHERE a.question_id = q.id AND a.deleted_at IS NULL)");
+        }
+        Some(false) => {
+            query.push(" AND NOT EXISTS (SELECT 1 FROM answers a WHERE a.question_id = q.id AND a.deleted_at IS NULL)");
+        }
+        None => {}
+    }
+}
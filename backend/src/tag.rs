@@ -0,0 +1,100 @@
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::{AppError, FieldError};
+use crate::validation::{check_tag, check_text, Rule, Validate, TAG_MAX_CHARS};
+
+/// A tag and how many questions carry it, as listed by `GET /tags`
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Tag {
+    pub slug: String,
+    pub question_count: i64,
+}
+
+// Moderators send this to POST /tags/:slug/merge to fold :slug into another tag
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MergeTag {
+    pub into: String,
+}
+
+impl Validate for MergeTag {
+    fn field_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_text("into", &self.into, &[Rule::NotBlank], &mut errors);
+        check_tag("into", &self.into, &mut errors);
+        errors
+    }
+}
+
+/// Checks the `:slug` being merged away by the same rules as `into`
+pub fn check_source_slug(slug: &str) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+    check_text("slug", slug, &[Rule::NotBlank], &mut errors);
+    check_tag("slug", slug, &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Validation(errors))
+    }
+}
+
+/// The record of one tag merge, kept in tag_merges
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TagMerge {
+    pub source_slug: String,
+    pub target_slug: String,
+    /// How many questions carried the merged tag
+    pub question_count: i32,
+    pub merged_by: Option<i32>,
+    pub merged_on: DateTime<Utc>,
+}
+
+/// The canonical spelling of a tag, so "Rust", "rust" and " rust" all end up as `rust`.
+/// Has to agree with the expression the tags migration used on the old arrays, which also
+/// cut them to TAG_MAX_CHARS.
+pub fn slugify(tag: &str) -> String {
+    uncapped_slug(tag).chars().take(TAG_MAX_CHARS).collect()
+}
+
+/// `slugify` before the cut, for telling whether a tag is too long to keep
+pub(crate) fn uncapped_slug(tag: &str) -> String {
+    tag.split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+}
+
+/// Slugs for the tags a client sent, without blanks or repeats, in the order first seen
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut slugs: Vec<String> = Vec::new();
+    for slug in tags.iter().map(|tag| slugify(tag)) {
+        if !slug.is_empty() && !slugs.contains(&slug) {
+            slugs.push(slug);
+        }
+    }
+    slugs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_normalized() {
+        let tags = vec![
+            "Rust".to_string(),
+            " rust".to_string(),
+            "Async  Await".to_string(),
+            "  ".to_string(),
+        ];
+
+        assert_eq!(normalize_tags(&tags), vec!["rust", "async-await"]);
+    }
+
+    #[test]
+    fn long_tags_are_cut_like_the_migration() {
+        let long = "x".repeat(TAG_MAX_CHARS);
+        assert_eq!(slugify(&format!("{} b", long)), long);
+        assert_eq!(uncapped_slug(&format!("{} b", long)), format!("{}-b", long));
+    }
+}
@@ -0,0 +1,140 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::FieldError;
+use crate::validation::{check_email, check_text, Validate, PASSWORD_RULES};
+
+// Never carries the password hash, so it is safe to hand back to clients
+#[derive(Clone, Debug, Display, Serialize, Deserialize)]
+#[display(fmt = "id: {}, email: {}, role: {}", id, email, role)]
+pub struct User {
+    pub id: UserId,
+    pub email: String,
+    pub role: Role,
+    pub created_on: DateTime<Utc>,
+}
+
+// Stored as plain text in users.role, the CHECK constraint keeps it to these three
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[display(fmt = "user")]
+    User,
+    #[display(fmt = "moderator")]
+    Moderator,
+    #[display(fmt = "admin")]
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+
+    /// Moderators and admins may edit or delete anyone's posts
+    pub fn is_staff(&self) -> bool {
+        matches!(self, Role::Moderator | Role::Admin)
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "user" => Ok(Role::User),
+            "moderator" => Ok(Role::Moderator),
+            "admin" => Ok(Role::Admin),
+            other => Err(format!("unknown role {}", other)),
+        }
+    }
+}
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    sqlx::Type,
+    Display,
+    derive_more::Deref,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub struct UserId(pub i32);
+
+impl From<i32> for UserId {
+    fn from(value: i32) -> Self {
+        UserId(value)
+    }
+}
+
+impl From<UserId> for i32 {
+    fn from(value: UserId) -> Self {
+        value.0
+    }
+}
+
+// Only used internally by login, this is the one place the hash leaves the database
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct UserDbResult {
+    pub id: i32,
+    pub email: String,
+    pub password_hash: String,
+    pub role: String,
+    pub created_on: DateTime<Utc>,
+}
+
+impl From<UserDbResult> for User {
+    fn from(value: UserDbResult) -> Self {
+        User {
+            id: UserId(value.id),
+            email: value.email,
+            // Fall back to the least privileged role rather than failing the request
+            role: value.role.parse().unwrap_or(Role::User),
+            created_on: value.created_on,
+        }
+    }
+}
+
+// Clients use this to register a new account
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateUser {
+    pub email: String,
+    pub password: String,
+}
+
+impl Validate for CreateUser {
+    fn field_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_email("email", &self.email, &mut errors);
+        check_text("password", &self.password, PASSWORD_RULES, &mut errors);
+        errors
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginUser {
+    pub email: String,
+    pub password: String,
+}
+
+/// Emails are compared case-insensitively, so store and look them up in one canonical form
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+// Admins use this to promote or demote a user
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateRole {
+    pub role: Role,
+}
@@ -0,0 +1,229 @@
+use axum::async_trait;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{FromRequest, Json};
+use axum::response::{IntoResponse, Response};
+use http::Request;
+use serde::de::DeserializeOwned;
+
+use crate::error::{AppError, FieldError};
+use crate::tag::{normalize_tags, uncapped_slug};
+
+pub const TITLE_MAX_CHARS: usize = 255;
+pub const POST_MAX_CHARS: usize = 30_000;
+pub const COMMENT_MAX_CHARS: usize = 1_000;
+pub const TAGS_MAX_COUNT: usize = 5;
+/// The width of tags.slug
+pub const TAG_MAX_CHARS: usize = 64;
+/// The width of users.email
+pub const EMAIL_MAX_CHARS: usize = 255;
+pub const PASSWORD_MIN_CHARS: usize = 8;
+
+pub const TITLE_RULES: &[Rule] = &[Rule::NotBlank, Rule::MaxChars(TITLE_MAX_CHARS)];
+pub const POST_RULES: &[Rule] = &[Rule::NotBlank, Rule::MaxChars(POST_MAX_CHARS)];
+pub const COMMENT_RULES: &[Rule] = &[Rule::NotBlank, Rule::MaxChars(COMMENT_MAX_CHARS)];
+pub const PASSWORD_RULES: &[Rule] = &[Rule::MinChars(PASSWORD_MIN_CHARS)];
+
+/// One check a text field has to pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    NotBlank,
+    MinChars(usize),
+    MaxChars(usize),
+}
+
+/// A request body that can tell what is wrong with it
+pub trait Validate {
+    /// Every offending field, empty when the value is fine
+    fn field_errors(&self) -> Vec<FieldError>;
+
+    fn validate(&self) -> Result<(), AppError> {
+        let errors = self.field_errors();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(errors))
+        }
+    }
+}
+
+/// Records a FieldError for each of `rules` that `value` breaks
+pub fn check_text(field: &str, value: &str, rules: &[Rule], errors: &mut Vec<FieldError>) {
+    for rule in rules {
+        match *rule {
+            Rule::NotBlank if value.trim().is_empty() => {
+                errors.push(FieldError::new(field, "must not be blank"));
+            }
+            Rule::MinChars(min) if value.chars().count() < min => {
+                errors.push(FieldError::new(
+                    field,
+                    format!("must be at least {} characters", min),
+                ));
+            }
+            // Characters, not bytes, the same way Postgres counts VARCHAR(n)
+            Rule::MaxChars(max) if value.chars().count() > max => {
+                errors.push(FieldError::new(
+                    field,
+                    format!("must be at most {} characters", max),
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Only checks the rough shape, `name@domain.tld` without spaces. Whether the address
+/// really exists is not something we can tell from here.
+pub fn check_email(field: &str, value: &str, errors: &mut Vec<FieldError>) {
+    let email = value.trim();
+    if email.is_empty() {
+        errors.push(FieldError::new(field, "must not be blank"));
+        return;
+    }
+
+    let well_formed = match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !email.chars().any(char::is_whitespace)
+        }
+        None => false,
+    };
+    if !well_formed {
+        errors.push(FieldError::new(field, "must be an email address"));
+    } else if email.chars().count() > EMAIL_MAX_CHARS {
+        errors.push(FieldError::new(
+            field,
+            format!("must be at most {} characters", EMAIL_MAX_CHARS),
+        ));
+    }
+}
+
+/// Tags may be letters, digits, spaces and `-+#._`, so `c++`, `c#` and `node.js` are fine.
+/// Blank entries and repeats are dropped by `normalize_tags` and don't count against the limit.
+pub fn check_tags(field: &str, tags: &[String], errors: &mut Vec<FieldError>) {
+    if normalize_tags(tags).len() > TAGS_MAX_COUNT {
+        errors.push(FieldError::new(
+            field,
+            format!("must have at most {} tags", TAGS_MAX_COUNT),
+        ));
+    }
+
+    for (i, tag) in tags.iter().enumerate() {
+        check_tag(&format!("{}[{}]", field, i), tag, errors);
+    }
+}
+
+/// The charset and length rules of `check_tags` for a single tag, which may still be blank
+pub fn check_tag(field: &str, tag: &str, errors: &mut Vec<FieldError>) {
+    if !tag
+        .chars()
+        .all(|c| c.is_alphanumeric() || c.is_whitespace() || "-+#._".contains(c))
+    {
+        errors.push(FieldError::new(
+            field,
+            "may only contain letters, digits, spaces and -+#._",
+        ));
+    } else if uncapped_slug(tag).chars().count() > TAG_MAX_CHARS {
+        errors.push(FieldError::new(
+            field,
+            format!("must be at most {} characters", TAG_MAX_CHARS),
+        ));
+    }
+}
+
+/// `Json<T>` that also runs `T::validate`, so a handler only ever sees well-formed input.
+/// Bodies that fail come back as a 422 listing every offending field.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidJson<T>(pub T);
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for ValidJson<T>
+where
+    T: DeserializeOwned + Validate,
+    Json<T>: FromRequest<S, B, Rejection = JsonRejection>,
+    S: Send + Sync,
+    B: Send + 'static,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(request, state)
+            .await
+            .map_err(|rejection| AppError::InvalidBody(rejection).into_response())?;
+        value.validate().map_err(IntoResponse::into_response)?;
+
+        Ok(ValidJson(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(errors: &[FieldError]) -> Vec<&str> {
+        errors.iter().map(|error| error.field.as_str()).collect()
+    }
+
+    #[test]
+    fn reports_every_broken_rule() {
+        let mut errors = Vec::new();
+        check_text("title", "  ", TITLE_RULES, &mut errors);
+        check_text(
+            "content",
+            &"x".repeat(POST_MAX_CHARS + 1),
+            POST_RULES,
+            &mut errors,
+        );
+        check_text("ok", "fine", COMMENT_RULES, &mut errors);
+        check_text("password", "hunter2", PASSWORD_RULES, &mut errors);
+        assert_eq!(fields(&errors), vec!["title", "content", "password"]);
+    }
+
+    #[test]
+    fn checks_email_shape() {
+        let mut errors = Vec::new();
+        for (i, email) in [
+            " Alice@Example.com ",
+            "",
+            "alice",
+            "@example.com",
+            "alice@example",
+            "alice@@example.com",
+            "ali ce@example.com",
+            "alice@example.com.",
+        ]
+        .iter()
+        .enumerate()
+        {
+            check_email(&format!("email[{}]", i), email, &mut errors);
+        }
+        assert_eq!(
+            fields(&errors),
+            vec![
+                "email[1]", "email[2]", "email[3]", "email[4]", "email[5]", "email[6]", "email[7]"
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_tag_count_and_charset() {
+        let mut errors = Vec::new();
+        let tags: Vec<String> = ["c++", "node.js", "c#", "bad/tag", "", "rust", "go", "Go"]
+            .iter()
+            .map(|tag| tag.to_string())
+            .collect();
+        check_tags("tags", &tags, &mut errors);
+        assert_eq!(fields(&errors), vec!["tags", "tags[3]"]);
+    }
+
+    #[test]
+    fn checks_tag_length_before_the_cut() {
+        let mut errors = Vec::new();
+        check_tag("tag", &"x".repeat(TAG_MAX_CHARS), &mut errors);
+        check_tag("tag", &"x".repeat(TAG_MAX_CHARS + 1), &mut errors);
+        assert_eq!(fields(&errors), vec!["tag"]);
+    }
+}
@@ -0,0 +1,53 @@
+use derive_more::Display;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::answer::AnswerId;
+use crate::question::QuestionId;
+
+/// Which way a user voted, stored as +1 or -1 in votes.value
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VoteDirection {
+    #[display(fmt = "up")]
+    Up,
+    #[display(fmt = "down")]
+    Down,
+}
+
+impl VoteDirection {
+    pub fn value(&self) -> i16 {
+        match self {
+            VoteDirection::Up => 1,
+            VoteDirection::Down => -1,
+        }
+    }
+
+    pub fn from_value(value: i16) -> Option<Self> {
+        match value {
+            1 => Some(VoteDirection::Up),
+            -1 => Some(VoteDirection::Down),
+            _ => None,
+        }
+    }
+}
+
+/// The post a vote is cast on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoteTarget {
+    Question(QuestionId),
+    Answer(AnswerId),
+}
+
+// Clients send this to POST /question/:question_id/vote and POST /answer/:answer_id/vote.
+// Voting again replaces the earlier vote, DELETE on the same path retracts it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CastVote {
+    pub direction: VoteDirection,
+}
+
+/// What the vote endpoints hand back: the post's new score and the caller's own vote, if any
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoteSummary {
+    pub score: i64,
+    pub vote: Option<VoteDirection>,
+}
@@ -1,4 +1,5 @@
-INSERT INTO answers(content, question_id) VALUES ('some content 1', 1);
-INSERT INTO answers(content, question_id) VALUES ('some content 2', 2);
-INSERT INTO answers(content, question_id) VALUES ('some content 3', 3);
-
+INSERT INTO answers(content, question_id) VALUES ('Answer  Content', 1);
+INSERT INTO answers(content, question_id, author_id) VALUES ('some content 1', 1, 2);
+INSERT INTO answers(content, question_id, author_id) VALUES ('some content 2', 2, 2);
+INSERT INTO answers(content, question_id, author_id) VALUES ('some content 3', 3, 1);
+INSERT INTO answers(content, question_id, author_id) VALUES ('some content 4', 2, 1);
@@ -0,0 +1,7 @@
+INSERT INTO comments(content, applied_to_question_id) VALUES ('comment to question 1', 1);
+INSERT INTO comments(content, applied_to_answer_id) VALUES ('comment to answer 1', 1);
+INSERT INTO comments(content, applied_to_question_id, author_id) VALUES ('question comment 1', 2, 1);
+INSERT INTO comments(content, applied_to_question_id, author_id) VALUES ('question comment 2', 2, 2);
+INSERT INTO comments(content, applied_to_answer_id, author_id) VALUES ('answer comment 1', 2, 1);
+INSERT INTO comments(content, applied_to_answer_id, author_id) VALUES ('answer comment 2', 3, 2);
+INSERT INTO comments(content, applied_to_answer_id, author_id) VALUES ('answer comment 3', 5, 2);
@@ -1,4 +1,11 @@
-INSERT INTO questions(title, content, tags) VALUES ('TestTitle1', 'Question Content', ARRAY['tag1', 'tag2']);
-INSERT INTO questions(title, content, tags) VALUES ('TestTitle2', 'Another Question Content', ARRAY['tag1', 'tag2']);
-INSERT INTO questions(title, content, tags) VALUES ('TestTitle3', 'Question Content', ARRAY['tag1', 'tag2']);
-INSERT INTO questions(title, content) VALUES ('TestTitle4', 'Another Question Content');
+-- The first rows of each fixture used to come from seed migrations, the ids below count on them
+INSERT INTO questions(title, content) VALUES ('Question Title', 'Question Content');
+INSERT INTO tags(slug) VALUES ('tag1'), ('tag2');
+INSERT INTO question_tags(question_id, tag_id) SELECT 1, id FROM tags;
+INSERT INTO questions(title, content, author_id) VALUES ('TestTitle1', 'Question Content', 1);
+INSERT INTO questions(title, content, author_id) VALUES ('TestTitle2', 'Another Question Content', 1);
+INSERT INTO questions(title, content, author_id) VALUES ('TestTitle3', 'Question Content', 2);
+INSERT INTO questions(title, content, author_id) VALUES ('TestTitle4', 'Another Question Content', 2);
+INSERT INTO question_tags(question_id, tag_id)
+    SELECT questions.id, tags.id FROM questions, tags
+    WHERE questions.title IN ('TestTitle1', 'TestTitle2', 'TestTitle3') AND tags.slug IN ('tag1', 'tag2');
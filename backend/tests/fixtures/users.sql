@@ -0,0 +1,5 @@
+-- Every fixture user has the password "password" (bcrypt, cost 4 to keep the tests fast)
+INSERT INTO users(email, password_hash) VALUES ('alice@example.com', '$2a$04$l/vpXKxbtBYM3/AYzThFheLbca8mHuGft5e2CEn2ZaXO6gNkPov6e');
+INSERT INTO users(email, password_hash) VALUES ('bob@example.com', '$2a$04$aIKtQsXE5A/iiCMFOaAZXuY5RY/Ytkq52Vca9ziISUUacWyUJXmVi');
+INSERT INTO users(email, password_hash, role) VALUES ('mod@example.com', '$2a$04$0JH3gaQTxmM8G3Aji0wbHeQTmJuv7kZs9rO6MVdlWMTjErr6lBcZy', 'moderator');
+INSERT INTO users(email, password_hash, role) VALUES ('admin@example.com', '$2a$04$0JH3gaQTxmM8G3Aji0wbHeQTmJuv7kZs9rO6MVdlWMTjErr6lBcZy', 'admin');
@@ -1,15 +1,61 @@
 use http::{Request, StatusCode};
 use hyper::Body;
+use sqlx::migrate::Migrate;
 use sqlx::PgPool;
 use tower::ServiceExt;
 
-use backend::answer::CreateAnswer;
-use backend::question::{CreateQuestion, Question};
+use backend::answer::{Answer, CreateAnswer, UpdateAnswer};
+use backend::auth::{init_keys, issue_token, AuthBody, AuthUser, Claims};
+use backend::comment::{CommentDbResult, CommentTarget, CreateComment, UpdateComment};
+#[cfg(feature = "sqlite")]
+use backend::config::DatabaseConfig;
+use backend::db::Store;
+use backend::error::{AppError, QuestionError};
+use backend::etag::EtagCondition;
+use backend::memory::MemoryStore;
+use backend::migrations::{migrate_down, migrate_up, migration_status, seed_dev_data, MIGRATOR};
+use backend::pagination::{Page, PageParams};
+use backend::purge::PurgeCounts;
+use backend::question::{
+    CreateQuestion, Question, QuestionFilter, QuestionId, QuestionResult, QuestionSort,
+    UpdateQuestion,
+};
+use backend::repository::Repository;
+use backend::revision::{AnswerRevision, QuestionRevision, RevisionDiff};
 use backend::routes::app;
+use backend::search::{SearchEntity, SearchResult};
+#[cfg(feature = "sqlite")]
+use backend::sqlite::{self, new_sqlite_pool, SqliteStore};
+use backend::tag::{slugify, MergeTag, Tag, TagMerge};
+use backend::user::{CreateUser, LoginUser, Role, UpdateRole, User, UserId};
+use backend::vote::{CastVote, VoteDirection, VoteSummary, VoteTarget};
 
-#[sqlx::test(fixtures("questions"))]
+fn init_test_keys() {
+    init_keys("integration-test-secret");
+}
+
+// Value for the Authorization header, signed for one of the users in fixtures/users.sql
+fn bearer(user_id: i32) -> String {
+    bearer_as(user_id, Role::User)
+}
+
+fn bearer_as(user_id: i32, role: Role) -> String {
+    init_test_keys();
+
+    let user = User {
+        id: UserId(user_id),
+        email: format!("user{}@example.com", user_id),
+        role,
+        created_on: chrono::Utc::now(),
+    };
+    let token = issue_token(&Claims::new(&user)).unwrap();
+
+    format!("Bearer {}", token.access_token)
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
 async fn test_add_question(db_pool: PgPool) {
-    let mut app = app(db_pool).await;
+    let app = app(Store::with_pool(db_pool)).await;
 
     let question = CreateQuestion {
         title: "New Title".into(),
@@ -22,6 +68,7 @@ async fn test_add_question(db_pool: PgPool) {
             Request::builder()
                 .method(http::Method::POST)
                 .uri("/question")
+                .header(http::header::AUTHORIZATION, bearer(1))
                 .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
                 .body(Body::from(serde_json::to_string(&question).unwrap()))
                 .unwrap(),
@@ -32,9 +79,9 @@ async fn test_add_question(db_pool: PgPool) {
     assert_eq!(response.status(), StatusCode::OK);
 }
 
-#[sqlx::test(fixtures("questions"))]
+#[sqlx::test(fixtures("users", "questions"))]
 async fn test_get_questions(db_pool: PgPool) {
-    let app = app(db_pool).await;
+    let app = app(Store::with_pool(db_pool)).await;
 
     let response = app
         .oneshot(
@@ -50,19 +97,20 @@ async fn test_get_questions(db_pool: PgPool) {
     assert_eq!(response.status(), StatusCode::OK);
 
     let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-    let questions: Vec<Question> = serde_json::from_slice(&body).unwrap();
-    assert!(!questions.is_empty());
+    let questions: Page<Question> = serde_json::from_slice(&body).unwrap();
+    assert!(!questions.items.is_empty());
 }
 
-#[sqlx::test(fixtures("questions"))]
-async fn test_get_question_by_id(db_pool: PgPool) {
-    let app = app(db_pool).await;
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_get_questions_cursor_pagination(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
 
     let response = app
+        .clone()
         .oneshot(
             Request::builder()
                 .method(http::Method::GET)
-                .uri("/question/1")
+                .uri("/questions?limit=3")
                 .body(Body::empty())
                 .unwrap(),
         )
@@ -70,84 +118,3178 @@ async fn test_get_question_by_id(db_pool: PgPool) {
         .unwrap();
 
     assert_eq!(response.status(), StatusCode::OK);
+    let link = response.headers()[http::header::LINK].to_str().unwrap();
+    assert!(link.contains("rel=\"next\""));
 
     let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-    let question: Question = serde_json::from_slice(&body).unwrap();
-    assert_eq!(question.id.0, 1);
+    let first_page: Page<Question> = serde_json::from_slice(&body).unwrap();
+    // 4 fixture rows plus the seeded question
+    assert_eq!(first_page.total, 5);
+    assert_eq!(first_page.items.len(), 3);
+    let cursor = first_page.next_cursor.expect("a second page");
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri(format!("/questions?limit=3&cursor={}", cursor))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let second_page: Page<Question> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(second_page.items.len(), 2);
+    assert!(second_page.next_cursor.is_none());
+
+    let mut ids: Vec<i32> = first_page
+        .items
+        .iter()
+        .chain(second_page.items.iter())
+        .map(|question| question.id.0)
+        .collect();
+    ids.sort();
+    assert_eq!(ids, vec![1, 2, 3, 4, 5]);
 }
 
-#[sqlx::test(fixtures("questions"))]
-async fn test_update_question(db_pool: PgPool) {
-    let mut app = app(db_pool).await;
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_get_questions_offset_pagination(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
 
-    let updated_question = Question {
-        id: 1.into(),
-        title: "Updated Title".into(),
-        content: "Updated content".into(),
-        tags: None,
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/questions?limit=2&offset=2")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let link = response.headers()[http::header::LINK].to_str().unwrap();
+    assert!(link.contains("</questions?limit=2&offset=0>; rel=\"prev\""));
+    assert!(link.contains("</questions?limit=2&offset=4>; rel=\"next\""));
+    assert!(link.contains("</questions?limit=2&offset=4>; rel=\"last\""));
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let page: Page<Question> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(page.items.len(), 2);
+    assert_eq!(page.total, 5);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_get_questions_rejects_bad_cursor(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/questions?cursor=not-a-cursor")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+// Ids of the questions GET `uri` returns, in the order they were returned
+async fn question_ids(app: axum::Router, uri: &str) -> Vec<i32> {
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let page: Page<Question> = serde_json::from_slice(&body).unwrap();
+    page.items.iter().map(|question| question.id.0).collect()
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_get_questions_sorted(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    assert_eq!(
+        question_ids(app.clone(), "/questions?sort=oldest").await,
+        vec![1, 2, 3, 4, 5]
+    );
+    assert_eq!(
+        question_ids(app.clone(), "/questions?sort=newest").await,
+        vec![5, 4, 3, 2, 1]
+    );
+    // Questions 1 and 2 have two answers each, question 3 has one
+    assert_eq!(
+        question_ids(app.clone(), "/questions?sort=most_answers&limit=3").await,
+        vec![2, 1, 3]
+    );
+    assert_eq!(
+        question_ids(app, "/questions?sort=unanswered&limit=2").await,
+        vec![5, 4]
+    );
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_get_questions_filtered(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    assert_eq!(
+        question_ids(
+            app.clone(),
+            "/questions?sort=oldest&tag=tag1,missing&tag_match=any"
+        )
+        .await,
+        vec![1, 2, 3, 4]
+    );
+    assert!(question_ids(app.clone(), "/questions?tag=tag1,missing")
+        .await
+        .is_empty());
+    assert_eq!(
+        question_ids(app.clone(), "/questions?sort=oldest&author=2").await,
+        vec![4, 5]
+    );
+    assert_eq!(
+        question_ids(app.clone(), "/questions?sort=oldest&has_answers=false").await,
+        vec![4, 5]
+    );
+    assert!(
+        question_ids(app, "/questions?created_after=2100-01-01T00:00:00Z")
+            .await
+            .is_empty()
+    );
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_get_questions_rejects_bad_filters(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/questions?sort=loudest&author=bob&has_answers=maybe")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let fields: Vec<&str> = body["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|field| field["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, vec!["sort", "author", "has_answers"]);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_huge_offsets_are_rejected(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    for uri in [
+        "/questions?offset=9223372036854775807",
+        "/search?q=another&offset=9223372036854775807",
+    ] {
+        let (status, body) =
+            send_json(app.clone(), http::Method::GET, uri, None, String::new()).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{uri}");
+
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["fields"][0]["field"], "offset", "{uri}");
+    }
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_out_of_range_paging_is_rejected(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    for (uri, field) in [
+        ("/questions?limit=0", "limit"),
+        ("/questions?limit=1000", "limit"),
+        ("/questions?offset=-1", "offset"),
+        ("/search?q=another&limit=101", "limit"),
+    ] {
+        let (status, body) =
+            send_json(app.clone(), http::Method::GET, uri, None, String::new()).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{uri}");
+
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["fields"][0]["field"], field, "{uri}");
+        assert_eq!(body["code"], "validation_failed", "{uri}");
+    }
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_get_questions_cursor_needs_date_sort(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/questions?limit=2")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let page: Page<Question> = serde_json::from_slice(&body).unwrap();
+    let cursor = page.next_cursor.unwrap();
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri(format!("/questions?sort=most_answers&cursor={}", cursor))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_add_question_rejects_invalid_fields(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    // Too long for VARCHAR(255), which used to surface as a database error
+    let question = CreateQuestion {
+        title: "t".repeat(256),
+        content: "   ".into(),
+        tags: Some(vec!["rust".into(), "bad/tag".into()]),
     };
 
     let response = app
         .oneshot(
             Request::builder()
-                .method(http::Method::PUT)
+                .method(http::Method::POST)
                 .uri("/question")
+                .header(http::header::AUTHORIZATION, bearer(1))
                 .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
-                .body(Body::from(
-                    serde_json::to_string(&updated_question).unwrap(),
-                ))
+                .body(Body::from(serde_json::to_string(&question).unwrap()))
                 .unwrap(),
         )
         .await
         .unwrap();
 
-    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let fields: Vec<&str> = body["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|field| field["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, vec!["title", "content", "tags[1]"]);
 }
 
-#[sqlx::test(fixtures("questions"))]
-async fn test_delete_question(db_pool: PgPool) {
-    println!("In test delete");
-    let app = app(db_pool).await;
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_create_blank_comment(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
 
-    let query_uri = format!("/question?question_id=1");
+    let comment = CreateComment {
+        content: "".into(),
+        target: CommentTarget::Question(2.into()),
+    };
 
     let response = app
         .oneshot(
             Request::builder()
-                .method(http::Method::DELETE)
-                .uri(query_uri)
+                .method(http::Method::POST)
+                .uri("/comment")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&comment).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_add_question_normalizes_tags(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let question = CreateQuestion {
+        title: "New Title".into(),
+        content: "Test content2".into(),
+        tags: Some(vec!["Rust".into(), " rust".into(), "Async  Await".into()]),
+    };
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/question")
+                .header(http::header::AUTHORIZATION, bearer(1))
                 .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&question).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The fixtures end at question 5
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/question/6")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let question: Question = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        question.tags,
+        Some(vec!["async-await".to_string(), "rust".to_string()])
+    );
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/tags")
                 .body(Body::empty())
                 .unwrap(),
         )
         .await
         .unwrap();
 
-    dbg!("DELETED QUESTION RESPONSE");
-    dbg!(&response);
     assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let tags: Vec<Tag> = serde_json::from_slice(&body).unwrap();
+    let counts: Vec<(&str, i64)> = tags
+        .iter()
+        .map(|tag| (tag.slug.as_str(), tag.question_count))
+        .collect();
+    assert_eq!(
+        counts,
+        vec![("tag1", 4), ("tag2", 4), ("async-await", 1), ("rust", 1)]
+    );
 }
 
-#[sqlx::test(fixtures("questions", "answers"))]
-async fn test_create_answer(db_pool: PgPool) {
-    let app = app(db_pool).await;
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_get_tag_questions(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
 
-    let answer = CreateAnswer {
-        content: "New Answer".into(),
-        question_id: 1i32,
+    assert_eq!(
+        question_ids(app.clone(), "/tags/TAG1/questions?sort=oldest").await,
+        vec![1, 2, 3, 4]
+    );
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/tags/missing/questions")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_merge_tags(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/tags/tag2/merge")
+                .header(http::header::AUTHORIZATION, bearer_as(3, Role::Moderator))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(
+                    serde_json::to_string(&MergeTag {
+                        into: "tag1".into(),
+                    })
+                    .unwrap(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let merged: TagMerge = serde_json::from_slice(&body).unwrap();
+    assert_eq!(merged.target_slug, "tag1");
+    assert_eq!(merged.question_count, 4);
+    assert_eq!(merged.merged_by, Some(3));
+
+    // The merged tag is now a synonym, so saving it lands on the target
+    let question = CreateQuestion {
+        title: "New Title".into(),
+        content: "Test content2".into(),
+        tags: Some(vec!["Tag2".into()]),
     };
 
     let response = app
+        .clone()
         .oneshot(
             Request::builder()
                 .method(http::Method::POST)
-                .uri("/answer")
+                .uri("/question")
+                .header(http::header::AUTHORIZATION, bearer(1))
                 .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
-                .body(Body::from(serde_json::to_string(&answer).unwrap()))
+                .body(Body::from(serde_json::to_string(&question).unwrap()))
                 .unwrap(),
         )
         .await
         .unwrap();
 
     assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/tags")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let tags: Vec<Tag> = serde_json::from_slice(&body).unwrap();
+    let counts: Vec<(&str, i64)> = tags
+        .iter()
+        .map(|tag| (tag.slug.as_str(), tag.question_count))
+        .collect();
+    assert_eq!(counts, vec![("tag1", 5)]);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_merge_registers_synonym_up_front(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/tags/js/merge")
+                .header(http::header::AUTHORIZATION, bearer_as(4, Role::Admin))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(r#"{"into":"tag1"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    assert_eq!(
+        question_ids(app, "/tags/js/questions?sort=oldest").await,
+        vec![1, 2, 3, 4]
+    );
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_merge_tags_is_staff_only(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/tags/tag2/merge")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(r#"{"into":"tag1"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_merge_tags_rejects_invalid_target(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    for into in ["  ", "bad/tag"] {
+        let merge = MergeTag { into: into.into() };
+        let (status, body) = send_json(
+            app.clone(),
+            http::Method::POST,
+            "/tags/tag2/merge",
+            Some(bearer_as(4, Role::Admin)),
+            serde_json::to_string(&merge).unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["fields"][0]["field"], "into");
+    }
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_merge_tags_rejects_invalid_source(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    for uri in ["/tags/%20%20/merge", "/tags/bad%2Ftag/merge"] {
+        let (status, body) = send_json(
+            app.clone(),
+            http::Method::POST,
+            uri,
+            Some(bearer_as(4, Role::Admin)),
+            r#"{"into":"tag1"}"#.into(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["fields"][0]["field"], "slug");
+    }
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_get_question_by_id(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/question/1")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let question: Question = serde_json::from_slice(&body).unwrap();
+    assert_eq!(question.id.0, 1);
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers", "comments"))]
+async fn test_get_question_comments(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/question_comments/2")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let thread: QuestionResult = serde_json::from_slice(&body).unwrap();
+    assert_eq!(thread.id, 2);
+    assert_eq!(thread.comments.len(), 2);
+    assert_eq!(thread.answers.len(), 2);
+
+    // Every answer carries only its own comments
+    for answer in &thread.answers {
+        let contents: Vec<&str> = answer.comments.iter().map(|c| c.content.as_str()).collect();
+        match answer.id {
+            3 => assert_eq!(contents, vec!["answer comment 2"]),
+            5 => assert_eq!(contents, vec!["answer comment 3"]),
+            other => panic!("unexpected answer {} in thread", other),
+        }
+    }
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_get_missing_question_comments(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/question_comments/999")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_update_question(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    // Question 1 comes from the seed migration, 2 is the first fixture row (owned by user 1)
+    let updated_question = UpdateQuestion {
+        id: 2.into(),
+        title: "Updated Title".into(),
+        content: "Updated content".into(),
+        tags: None,
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::PUT)
+                .uri("/question")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(
+                    serde_json::to_string(&updated_question).unwrap(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+// PUTs an edit to question 2 as its author, sending `if_match` as the If-Match header
+async fn put_question_if_match(
+    app: axum::Router,
+    if_match: &str,
+) -> http::Response<axum::body::BoxBody> {
+    let updated_question = UpdateQuestion {
+        id: 2.into(),
+        title: "Updated Title".into(),
+        content: "Updated content".into(),
+        tags: None,
+    };
+
+    app.oneshot(
+        Request::builder()
+            .method(http::Method::PUT)
+            .uri("/question")
+            .header(http::header::AUTHORIZATION, bearer(1))
+            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .header(http::header::IF_MATCH, if_match)
+            .body(Body::from(
+                serde_json::to_string(&updated_question).unwrap(),
+            ))
+            .unwrap(),
+    )
+    .await
+    .unwrap()
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_update_question_if_match(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/question/2")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()[http::header::ETAG].clone();
+    assert!(etag.to_str().unwrap().starts_with("\"1-"));
+
+    let response = put_question_if_match(app.clone(), etag.to_str().unwrap()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(etag_of(&response).starts_with("\"2-"));
+
+    // A second editor still holding version 1 is turned away
+    let response = put_question_if_match(app.clone(), etag.to_str().unwrap()).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+    let response = put_question_if_match(app, "*").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(etag_of(&response).starts_with("\"3-"));
+}
+
+fn etag_of(response: &http::Response<axum::body::BoxBody>) -> String {
+    response.headers()[http::header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_transaction_rolls_back_on_error(db_pool: PgPool) {
+    let store = Store::with_pool(db_pool.clone());
+    let count_questions = || async {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM questions")
+            .fetch_one(&db_pool)
+            .await
+            .unwrap();
+        count
+    };
+    let before = count_questions().await;
+
+    let result: Result<(), AppError> = store
+        .transaction(move |tx| {
+            Box::pin(async move {
+                sqlx::query("INSERT INTO questions (title, content) VALUES ('Half', 'done')")
+                    .execute(&mut *tx)
+                    .await?;
+                Err(AppError::Question(QuestionError::InvalidId))
+            })
+        })
+        .await;
+    assert!(result.is_err());
+    assert_eq!(count_questions().await, before);
+
+    store
+        .transaction(move |tx| {
+            Box::pin(async move {
+                sqlx::query("INSERT INTO questions (title, content) VALUES ('Whole', 'done')")
+                    .execute(&mut *tx)
+                    .await?;
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+    assert_eq!(count_questions().await, before + 1);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_update_question_rolls_back_partial_edit(db_pool: PgPool) {
+    let mut store = Store::with_pool(db_pool.clone());
+    let author = AuthUser {
+        id: UserId(1),
+        email: "alice@example.com".to_string(),
+        role: Role::User,
+    };
+    let (id,): (i32,) = sqlx::query_as("SELECT id FROM questions WHERE title = 'TestTitle1'")
+        .fetch_one(&db_pool)
+        .await
+        .unwrap();
+    let edit = |tags: Vec<String>| UpdateQuestion {
+        id: QuestionId(id),
+        title: "Edited".to_string(),
+        content: "Edited content".to_string(),
+        tags: Some(tags),
+    };
+
+    // The title is written before the tags, Postgres then refuses the NUL byte in the tag
+    // and that fails the whole edit
+    let err = store
+        .update_question(edit(vec!["nul\0tag".to_string()]), None, &author)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, AppError::Database(_)));
+
+    // So does an If-Match that no longer holds, after the UPDATE has already gone through
+    let stale = EtagCondition::Tags(vec!["\"7\"".to_string()]);
+    let err = store
+        .update_question(edit(vec!["rust".to_string()]), Some(&stale), &author)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        AppError::Question(QuestionError::VersionMismatch)
+    ));
+
+    let question = store.get_question_by_id(QuestionId(id)).await.unwrap();
+    assert_eq!(question.title, "TestTitle1");
+    assert_eq!(question.version, 1);
+    assert_eq!(
+        question.tags,
+        Some(vec!["tag1".to_string(), "tag2".to_string()])
+    );
+    let (revisions,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM question_revisions WHERE question_id = $1")
+            .bind(id)
+            .fetch_one(&db_pool)
+            .await
+            .unwrap();
+    assert_eq!(revisions, 0);
+
+    let question = store
+        .update_question(edit(vec!["rust".to_string()]), None, &author)
+        .await
+        .unwrap();
+    assert_eq!(question.title, "Edited");
+    assert_eq!(question.version, 2);
+    assert_eq!(question.tags, Some(vec!["rust".to_string()]));
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_get_question_if_none_match(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let get_if_none_match = |if_none_match: String| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/question/2")
+                .header(http::header::IF_NONE_MATCH, if_none_match)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let response = get_if_none_match("\"0\"".into()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = etag_of(&response);
+
+    let response = get_if_none_match(format!("W/{}", etag)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(etag_of(&response), etag);
+
+    // A vote changes the score in the body without an edit
+    let status = vote_as(app.clone(), "/question/2/vote", 2, VoteDirection::Up)
+        .await
+        .status();
+    assert_eq!(status, StatusCode::OK);
+    let response = get_if_none_match(etag.clone()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let voted_etag = etag_of(&response);
+    assert_ne!(voted_etag, etag);
+
+    // Which doesn't get in the way of editing version 1
+    let response = put_question_if_match(app.clone(), &voted_etag).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = get_if_none_match(voted_etag).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_question_revisions(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let updated_question = UpdateQuestion {
+        id: 2.into(),
+        title: "Updated Title".into(),
+        content: "Question Content".into(),
+        tags: Some(vec!["tag1".into()]),
+    };
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::PUT)
+                .uri("/question")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(
+                    serde_json::to_string(&updated_question).unwrap(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/question/2/revisions")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let revisions: Vec<QuestionRevision> = serde_json::from_slice(&body).unwrap();
+    let titles: Vec<(i32, &str)> = revisions
+        .iter()
+        .map(|revision| (revision.revision, revision.title.as_str()))
+        .collect();
+    assert_eq!(titles, vec![(1, "TestTitle1"), (2, "Updated Title")]);
+    assert_eq!(revisions[1].editor_id, Some(1));
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/question/2/revisions/diff?from=1&to=2")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let diff: RevisionDiff = serde_json::from_slice(&body).unwrap();
+    let fields: Vec<&str> = diff.fields.iter().map(|f| f.field.as_str()).collect();
+    assert_eq!(fields, vec!["title", "tags"]);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/question/2/revisions/1/rollback")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let question: Question = serde_json::from_slice(&body).unwrap();
+    assert_eq!(question.title, "TestTitle1");
+    assert_eq!(
+        question.tags,
+        Some(vec!["tag1".to_string(), "tag2".to_string()])
+    );
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_diff_missing_revision(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/question/1/revisions/diff?from=1&to=9")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_delete_question(db_pool: PgPool) {
+    println!("In test delete");
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let query_uri = "/question?question_id=2";
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::DELETE)
+                .uri(query_uri)
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    dbg!("DELETED QUESTION RESPONSE");
+    dbg!(&response);
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+// Sends `method uri` with no body as `authorization`, returning the status
+async fn status_of(
+    app: axum::Router,
+    method: http::Method,
+    uri: &str,
+    authorization: Option<String>,
+) -> StatusCode {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(authorization) = authorization {
+        request = request.header(http::header::AUTHORIZATION, authorization);
+    }
+
+    app.oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+        .status()
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers", "comments"))]
+async fn test_deleted_question_is_hidden_until_undeleted(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    // Answer 5 goes first on its own, so undeleting the question must leave it deleted
+    let status = status_of(
+        app.clone(),
+        http::Method::DELETE,
+        "/answer/5",
+        Some(bearer(1)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let status = status_of(
+        app.clone(),
+        http::Method::DELETE,
+        "/question?question_id=2",
+        Some(bearer(1)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    for uri in [
+        "/question/2",
+        "/question_comments/2",
+        "/answer/3",
+        "/question/2/comments",
+    ] {
+        let status = status_of(app.clone(), http::Method::GET, uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "GET {uri}");
+    }
+    assert!(!question_ids(app.clone(), "/questions").await.contains(&2));
+
+    let status = status_of(
+        app.clone(),
+        http::Method::POST,
+        "/question/2/undelete",
+        Some(bearer(1)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let status = status_of(
+        app.clone(),
+        http::Method::POST,
+        "/question/2/undelete",
+        Some(bearer_as(3, Role::Moderator)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/question_comments/2")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let thread: QuestionResult = serde_json::from_slice(&body).unwrap();
+    let answer_ids: Vec<i32> = thread.answers.iter().map(|answer| answer.id).collect();
+    assert_eq!(answer_ids, vec![3]);
+    assert_eq!(thread.comments.len(), 2);
+    assert_eq!(thread.answers[0].comments.len(), 1);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_errors_are_problem_details(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/question/99")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.headers()[http::header::CONTENT_TYPE],
+        "application/problem+json"
+    );
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(problem["status"], 404);
+    assert_eq!(problem["code"], "question_not_found");
+    assert_eq!(problem["title"], "Not Found");
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_rejected_requests_are_problem_details(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let cases = [
+        (http::Method::GET, "/question/abc", None, "", "invalid_path"),
+        (
+            http::Method::GET,
+            "/question/2/revisions/diff?from=a&to=2",
+            None,
+            "",
+            "invalid_query",
+        ),
+        (
+            http::Method::GET,
+            "/no/such/page",
+            None,
+            "",
+            "route_not_found",
+        ),
+        (http::Method::POST, "/login", None, "{", "invalid_body"),
+        (http::Method::POST, "/register", None, "{", "invalid_body"),
+        (
+            http::Method::POST,
+            "/question/2/vote",
+            Some(bearer(2)),
+            r#"{"direction":"sideways"}"#,
+            "invalid_body",
+        ),
+        (
+            http::Method::POST,
+            "/tags/tag2/merge",
+            Some(bearer_as(4, Role::Admin)),
+            "[]",
+            "invalid_body",
+        ),
+        (
+            http::Method::PUT,
+            "/admin/users/2/role",
+            Some(bearer_as(4, Role::Admin)),
+            r#"{"role":"owner"}"#,
+            "invalid_body",
+        ),
+    ];
+
+    for (method, uri, authorization, body, code) in cases {
+        let mut request = Request::builder()
+            .method(method.clone())
+            .uri(uri)
+            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref());
+        if let Some(authorization) = authorization {
+            request = request.header(http::header::AUTHORIZATION, authorization);
+        }
+        let response = app
+            .clone()
+            .oneshot(request.body(Body::from(body)).unwrap())
+            .await
+            .unwrap();
+
+        assert!(response.status().is_client_error(), "{method} {uri}");
+        assert_eq!(
+            response.headers()[http::header::CONTENT_TYPE],
+            "application/problem+json",
+            "{method} {uri}"
+        );
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["code"], code, "{method} {uri}");
+    }
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_comment_on_missing_question(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let comment = CreateComment {
+        content: "Nobody will read this".into(),
+        target: CommentTarget::Question(99.into()),
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/comment")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&comment).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(problem["code"], "question_not_found");
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_comment_needs_exactly_one_target(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool.clone())).await;
+
+    // The database holds the line too
+    let both = sqlx::query(
+        "INSERT INTO comments (content, applied_to_question_id, applied_to_answer_id) \
+         VALUES ('Both', 2, 3)",
+    )
+    .execute(&db_pool)
+    .await;
+    assert!(both.is_err());
+
+    let bodies = [
+        r#"{"content": "Both", "target": {"question": 2, "answer": 3}}"#,
+        r#"{"content": "Neither", "target": {}}"#,
+        r#"{"content": "Old shape", "applied_to_question_id": 2, "applied_to_answer_id": 0}"#,
+    ];
+    for body in bodies {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/comment")
+                    .header(http::header::AUTHORIZATION, bearer(1))
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert!(response.status().is_client_error(), "{body}");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["code"], "invalid_body");
+    }
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_undelete_missing_question(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let status = status_of(
+        app,
+        http::Method::POST,
+        "/question/99/undelete",
+        Some(bearer_as(3, Role::Moderator)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers", "comments"))]
+async fn test_purge_deleted(db_pool: PgPool) {
+    let mut store = Store::with_pool(db_pool.clone());
+    let app = app(Store::with_pool(db_pool.clone())).await;
+
+    let status = status_of(
+        app.clone(),
+        http::Method::DELETE,
+        "/question?question_id=2",
+        Some(bearer(1)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Still within the retention period
+    let an_hour_ago = chrono::Utc::now() - chrono::Duration::hours(1);
+    let counts = store.purge_deleted(an_hour_ago).await.unwrap();
+    assert_eq!(counts, PurgeCounts::default());
+
+    let in_an_hour = chrono::Utc::now() + chrono::Duration::hours(1);
+    let counts = store.purge_deleted(in_an_hour).await.unwrap();
+    assert_eq!(counts.questions, 1);
+
+    // Its answers and comments went with it
+    let (answers,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM answers WHERE question_id = 2")
+        .fetch_one(&db_pool)
+        .await
+        .unwrap();
+    assert_eq!(answers, 0);
+
+    let status = status_of(
+        app,
+        http::Method::POST,
+        "/question/2/undelete",
+        Some(bearer_as(3, Role::Moderator)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_create_answer(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let answer = CreateAnswer {
+        content: "New Answer".into(),
+        question_id: 1i32,
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/answer")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&answer).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_get_question_answers(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/question/2/answers")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let answers: Vec<Answer> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(answers.len(), 2);
+    assert_eq!(answers[0].content, "some content 2");
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_get_answer_by_id(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/answer/2")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let answer: Answer = serde_json::from_slice(&body).unwrap();
+    assert_eq!(answer.id.0, 2);
+    assert_eq!(answer.content, "some content 1");
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_get_missing_answer(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/answer/999")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_update_answer(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let update = UpdateAnswer {
+        content: "Edited answer".into(),
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::PUT)
+                .uri("/answer/2")
+                .header(http::header::AUTHORIZATION, bearer(2))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&update).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let answer: Answer = serde_json::from_slice(&body).unwrap();
+    assert_eq!(answer.content, "Edited answer");
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_answer_rollback(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let update = UpdateAnswer {
+        content: "Edited answer".into(),
+    };
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::PUT)
+                .uri("/answer/2")
+                .header(http::header::AUTHORIZATION, bearer(2))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&update).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Only the author or staff may roll back
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/answer/2/revisions/1/rollback")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/answer/2/revisions/1/rollback")
+                .header(http::header::AUTHORIZATION, bearer(2))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let answer: Answer = serde_json::from_slice(&body).unwrap();
+    assert_eq!(answer.content, "some content 1");
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/answer/2/revisions")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let revisions: Vec<AnswerRevision> = serde_json::from_slice(&body).unwrap();
+    let contents: Vec<&str> = revisions
+        .iter()
+        .map(|revision| revision.content.as_str())
+        .collect();
+    assert_eq!(
+        contents,
+        vec!["some content 1", "Edited answer", "some content 1"]
+    );
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_update_answer_by_other_user_is_forbidden(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let update = UpdateAnswer {
+        content: "Edited answer".into(),
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::PUT)
+                .uri("/answer/2")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&update).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_delete_answer(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::DELETE)
+                .uri("/answer/2")
+                .header(http::header::AUTHORIZATION, bearer(2))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/answer/2")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_accept_answer(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    // Answer 3 is the older of question 2's answers, accepting it pins it first
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/question/2/accept/3")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let thread: QuestionResult = serde_json::from_slice(&body).unwrap();
+    assert_eq!(thread.accepted_answer_id, Some(3));
+    let ids: Vec<i32> = thread.answers.iter().map(|answer| answer.id).collect();
+    assert_eq!(ids, vec![3, 5]);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::DELETE)
+                .uri("/question/2/accept")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let thread: QuestionResult = serde_json::from_slice(&body).unwrap();
+    assert_eq!(thread.accepted_answer_id, None);
+    let ids: Vec<i32> = thread.answers.iter().map(|answer| answer.id).collect();
+    assert_eq!(ids, vec![5, 3]);
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_accept_answer_from_other_question(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    // Answer 4 belongs to question 3
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/question/2/accept/4")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_only_question_author_can_accept(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    // Not even a moderator may choose for the author
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/question/2/accept/3")
+                .header(http::header::AUTHORIZATION, bearer_as(3, Role::Moderator))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["reason"], "not_question_author");
+}
+
+// POSTs `direction` to a vote endpoint as `user_id`
+async fn vote_as(
+    app: axum::Router,
+    uri: &str,
+    user_id: i32,
+    direction: VoteDirection,
+) -> http::Response<axum::body::BoxBody> {
+    app.oneshot(
+        Request::builder()
+            .method(http::Method::POST)
+            .uri(uri)
+            .header(http::header::AUTHORIZATION, bearer(user_id))
+            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(
+                serde_json::to_string(&CastVote { direction }).unwrap(),
+            ))
+            .unwrap(),
+    )
+    .await
+    .unwrap()
+}
+
+async fn vote_summary(response: http::Response<axum::body::BoxBody>) -> VoteSummary {
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_vote_on_question(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let summary =
+        vote_summary(vote_as(app.clone(), "/question/2/vote", 2, VoteDirection::Up).await).await;
+    assert_eq!(summary.score, 1);
+    assert_eq!(summary.vote, Some(VoteDirection::Up));
+
+    let summary =
+        vote_summary(vote_as(app.clone(), "/question/2/vote", 3, VoteDirection::Down).await).await;
+    assert_eq!(summary.score, 0);
+    assert_eq!(summary.vote, Some(VoteDirection::Down));
+
+    // Voting again replaces the earlier vote rather than adding a second one
+    let summary =
+        vote_summary(vote_as(app.clone(), "/question/2/vote", 2, VoteDirection::Down).await).await;
+    assert_eq!(summary.score, -2);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/question/2")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let question: Question = serde_json::from_slice(&body).unwrap();
+    assert_eq!(question.score, -2);
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_retract_answer_vote(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let summary =
+        vote_summary(vote_as(app.clone(), "/answer/3/vote", 1, VoteDirection::Up).await).await;
+    assert_eq!(summary.score, 1);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::DELETE)
+                .uri("/answer/3/vote")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let summary = vote_summary(response).await;
+    assert_eq!(summary.score, 0);
+    assert_eq!(summary.vote, None);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_vote_on_missing_question(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = vote_as(app, "/question/999/vote", 1, VoteDirection::Up).await;
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_vote_requires_login(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/question/2/vote")
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(r#"{"direction":"up"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_thread_orders_answers_by_score(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    // Answer 5 is the newer of question 2's answers, an upvote moves answer 3 ahead of it
+    vote_summary(vote_as(app.clone(), "/answer/3/vote", 1, VoteDirection::Up).await).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/question_comments/2")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let thread: QuestionResult = serde_json::from_slice(&body).unwrap();
+    let ids: Vec<(i32, i64)> = thread
+        .answers
+        .iter()
+        .map(|answer| (answer.id, answer.score))
+        .collect();
+    assert_eq!(ids, vec![(3, 1), (5, 0)]);
+
+    vote_summary(vote_as(app.clone(), "/question/3/vote", 1, VoteDirection::Up).await).await;
+    assert_eq!(
+        question_ids(app, "/questions?sort=votes&limit=1").await,
+        vec![3]
+    );
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_create_comment(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let comment = CreateComment {
+        content: "New Comment".into(),
+        target: CommentTarget::Question(2.into()),
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/comment")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&comment).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let comment: CommentDbResult = serde_json::from_slice(&body).unwrap();
+    assert_eq!(comment.applied_to_question_id, Some(2));
+    assert_eq!(comment.applied_to_answer_id, None);
+    assert_eq!(comment.author_id, Some(1));
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers"))]
+async fn test_create_answer_comment(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let comment = CreateComment {
+        content: "New Comment".into(),
+        target: CommentTarget::Answer(3.into()),
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/comment")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&comment).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let comment: CommentDbResult = serde_json::from_slice(&body).unwrap();
+    assert_eq!(comment.applied_to_question_id, None);
+    assert_eq!(comment.applied_to_answer_id, Some(3));
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers", "comments"))]
+async fn test_get_question_comment_list(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/question/2/comments")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let comments: Vec<CommentDbResult> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(comments.len(), 2);
+    assert_eq!(comments[0].content, "question comment 1");
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers", "comments"))]
+async fn test_get_answer_comments(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/answer/2/comments")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let comments: Vec<CommentDbResult> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(comments.len(), 1);
+    assert_eq!(comments[0].content, "answer comment 1");
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers", "comments"))]
+async fn test_update_comment(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    // Comments 1 and 2 come from the seed migration
+    let update = UpdateComment {
+        content: "Edited comment".into(),
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::PUT)
+                .uri("/comment/3")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&update).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let comment: CommentDbResult = serde_json::from_slice(&body).unwrap();
+    assert_eq!(comment.id.0, 3);
+    assert_eq!(comment.content, "Edited comment");
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers", "comments"))]
+async fn test_update_comment_by_other_user_is_forbidden(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let update = UpdateComment {
+        content: "Edited comment".into(),
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::PUT)
+                .uri("/comment/3")
+                .header(http::header::AUTHORIZATION, bearer(2))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&update).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers", "comments"))]
+async fn test_delete_comment(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::DELETE)
+                .uri("/comment/3")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::DELETE)
+                .uri("/comment/3")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers", "comments"))]
+async fn test_search_questions(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/search?q=another")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let page: Page<SearchResult> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(page.total, 2);
+
+    let mut ids: Vec<i32> = page.items.iter().map(|hit| hit.id).collect();
+    ids.sort();
+    assert_eq!(ids, vec![3, 5]);
+    for hit in &page.items {
+        assert_eq!(hit.entity_type, SearchEntity::Question);
+        assert_eq!(hit.question_id, hit.id);
+        assert!(hit.snippet.contains("<b>Another</b>"));
+    }
+}
+
+#[sqlx::test(fixtures("users", "questions", "answers", "comments"))]
+async fn test_search_comments_link_to_their_question(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/search?q=answer+comment+2")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let page: Page<SearchResult> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(page.total, 1);
+
+    // 'answer comment 2' is on answer 3, which answers question 2
+    let hit = &page.items[0];
+    assert_eq!(hit.entity_type, SearchEntity::Comment);
+    assert_eq!(hit.id, 6);
+    assert_eq!(hit.question_id, 2);
+    assert_eq!(hit.question_title, "TestTitle1");
+}
+
+// Snippets are meant to be rendered as HTML, so nothing from the post may come through as markup
+async fn check_search_escapes_markup<S: Repository>(mut store: S) {
+    let author = store
+        .add_user("markup@example.com".into(), "hash".into())
+        .await
+        .unwrap();
+    store
+        .add_question(
+            "Markup".into(),
+            "<img src=x onerror=alert(1)> borrow & checker".into(),
+            None,
+            author.id,
+        )
+        .await
+        .unwrap();
+
+    let page = store
+        .search("borrow", &PageParams::default())
+        .await
+        .unwrap();
+    assert_eq!(page.total, 1);
+    let snippet = &page.items[0].snippet;
+    assert!(
+        snippet.contains("&lt;img src=x onerror=alert(1)&gt;"),
+        "{}",
+        snippet
+    );
+    assert!(snippet.contains("<b>borrow</b>"), "{}", snippet);
+    assert!(snippet.contains("&amp;"), "{}", snippet);
+    assert!(!snippet.contains("<img"), "{}", snippet);
+}
+
+#[sqlx::test]
+async fn test_search_escapes_markup(db_pool: PgPool) {
+    check_search_escapes_markup(Store::with_pool(db_pool)).await;
+}
+
+#[tokio::test]
+async fn test_memory_store_search_escapes_markup() {
+    check_search_escapes_markup(MemoryStore::default()).await;
+}
+
+#[sqlx::test]
+async fn test_search_requires_terms(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/search?q=%20")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[sqlx::test]
+async fn test_register_user(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let user = CreateUser {
+        email: " New.User@Example.com ".into(),
+        password: "hunter22".into(),
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/register")
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&user).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let user: User = serde_json::from_slice(&body).unwrap();
+    assert_eq!(user.email, "new.user@example.com");
+}
+
+#[sqlx::test(fixtures("users"))]
+async fn test_register_duplicate_email(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let user = CreateUser {
+        email: "alice@example.com".into(),
+        password: "another password".into(),
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/register")
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&user).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[sqlx::test]
+async fn test_register_rejects_invalid_credentials(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let user = CreateUser {
+        email: "".into(),
+        password: "short".into(),
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/register")
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&user).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let fields: Vec<&str> = body["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|field| field["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, vec!["email", "password"]);
+}
+
+#[sqlx::test(fixtures("users"))]
+async fn test_login(db_pool: PgPool) {
+    init_test_keys();
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let credentials = LoginUser {
+        email: "alice@example.com".into(),
+        password: "password".into(),
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/login")
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&credentials).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let auth: AuthBody = serde_json::from_slice(&body).unwrap();
+    assert_eq!(auth.token_type, "Bearer");
+    assert!(!auth.access_token.is_empty());
+}
+
+#[sqlx::test(fixtures("users"))]
+async fn test_login_wrong_password(db_pool: PgPool) {
+    init_test_keys();
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let credentials = LoginUser {
+        email: "alice@example.com".into(),
+        password: "not the password".into(),
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/login")
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&credentials).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_add_question_requires_login(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let question = CreateQuestion {
+        title: "New Title".into(),
+        content: "Test content2".into(),
+        tags: None,
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri("/question")
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&question).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_delete_question_rejects_bad_token(db_pool: PgPool) {
+    init_test_keys();
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::DELETE)
+                .uri("/question?question_id=1")
+                .header(http::header::AUTHORIZATION, "Bearer not.a.jwt")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_update_question_by_other_user_is_forbidden(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let updated_question = UpdateQuestion {
+        id: 2.into(),
+        title: "Hijacked Title".into(),
+        content: "Hijacked content".into(),
+        tags: None,
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::PUT)
+                .uri("/question")
+                .header(http::header::AUTHORIZATION, bearer(2))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(
+                    serde_json::to_string(&updated_question).unwrap(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_delete_question_by_other_user_is_forbidden(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::DELETE)
+                .uri("/question?question_id=2")
+                .header(http::header::AUTHORIZATION, bearer(2))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_delete_missing_question(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::DELETE)
+                .uri("/question?question_id=999")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_moderator_can_delete_any_question(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::DELETE)
+                .uri("/question?question_id=2")
+                .header(http::header::AUTHORIZATION, bearer_as(3, Role::Moderator))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[sqlx::test(fixtures("users"))]
+async fn test_admin_routes_reject_regular_users(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/admin/users")
+                .header(http::header::AUTHORIZATION, bearer_as(3, Role::Moderator))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error["reason"], "insufficient_role");
+    assert_eq!(error["allowed_roles"], serde_json::json!(["admin"]));
+}
+
+#[sqlx::test(fixtures("users"))]
+async fn test_admin_get_users(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri("/admin/users")
+                .header(http::header::AUTHORIZATION, bearer_as(4, Role::Admin))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let users: Vec<User> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(users.len(), 4);
+    assert_eq!(users[3].role, Role::Admin);
+}
+
+#[sqlx::test(fixtures("users"))]
+async fn test_admin_update_user_role(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+
+    let update = UpdateRole {
+        role: Role::Moderator,
+    };
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(http::Method::PUT)
+                .uri("/admin/users/2/role")
+                .header(http::header::AUTHORIZATION, bearer_as(4, Role::Admin))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(serde_json::to_string(&update).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let user: User = serde_json::from_slice(&body).unwrap();
+    assert_eq!(user.role, Role::Moderator);
+}
+
+#[sqlx::test(fixtures("users", "questions"))]
+async fn test_demoted_moderator_token_loses_staff_rights(db_pool: PgPool) {
+    let app = app(Store::with_pool(db_pool)).await;
+    // Issued while they were still a moderator
+    let old_token = bearer_as(3, Role::Moderator);
+
+    let update = UpdateRole { role: Role::User };
+    let (status, _) = send_json(
+        app.clone(),
+        http::Method::PUT,
+        "/admin/users/3/role",
+        Some(bearer_as(4, Role::Admin)),
+        serde_json::to_string(&update).unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let status = status_of(
+        app.clone(),
+        http::Method::DELETE,
+        "/question?question_id=2",
+        Some(old_token),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let status = status_of(
+        app.clone(),
+        http::Method::DELETE,
+        "/admin/users/2",
+        Some(bearer_as(4, Role::Admin)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // A deleted user's token no longer gets them anywhere
+    let question = CreateQuestion {
+        title: "Still here?".into(),
+        content: "Posted after my account was deleted".into(),
+        tags: None,
+    };
+    let (status, _) = send_json(
+        app,
+        http::Method::POST,
+        "/question",
+        Some(bearer(2)),
+        serde_json::to_string(&question).unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+// Sends `body` as JSON, returning the status and the response body
+async fn send_json(
+    app: axum::Router,
+    method: http::Method,
+    uri: &str,
+    authorization: Option<String>,
+    body: String,
+) -> (StatusCode, hyper::body::Bytes) {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref());
+    if let Some(authorization) = authorization {
+        request = request.header(http::header::AUTHORIZATION, authorization);
+    }
+
+    let response = app
+        .oneshot(request.body(Body::from(body)).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    (
+        status,
+        hyper::body::to_bytes(response.into_body()).await.unwrap(),
+    )
+}
+
+// Takes a backend that starts out empty through asking, answering, voting, editing,
+// searching and deleting, for the stores that don't run against the fixtures
+async fn check_question_flow<S: Repository>(mut store: S) {
+    let app = app(store.clone()).await;
+
+    // Everyone behind a token has to exist, they get ids 1, 2 and 3
+    for email in [
+        "asker@example.com",
+        "answerer@example.com",
+        "moderator@example.com",
+    ] {
+        let user = CreateUser {
+            email: email.into(),
+            password: "hunter22".into(),
+        };
+        let (status, _) = send_json(
+            app.clone(),
+            http::Method::POST,
+            "/register",
+            None,
+            serde_json::to_string(&user).unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    store
+        .set_user_role(UserId(3), Role::Moderator)
+        .await
+        .unwrap();
+
+    let question = CreateQuestion {
+        title: "Borrowing in closures".into(),
+        content: "Why does the closure borrow my vector?".into(),
+        tags: Some(vec!["Rust".into(), "closures".into()]),
+    };
+    let (status, _) = send_json(
+        app.clone(),
+        http::Method::POST,
+        "/question",
+        Some(bearer(1)),
+        serde_json::to_string(&question).unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send_json(
+        app.clone(),
+        http::Method::GET,
+        "/questions?tags=rust",
+        None,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let page: Page<Question> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.items[0].id.0, 1);
+    assert_eq!(
+        page.items[0].tags,
+        Some(vec!["closures".to_string(), "rust".to_string()])
+    );
+
+    let answer = CreateAnswer {
+        content: "Use a move closure".into(),
+        question_id: 1,
+    };
+    let (status, _) = send_json(
+        app.clone(),
+        http::Method::POST,
+        "/answer",
+        Some(bearer(2)),
+        serde_json::to_string(&answer).unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let summary =
+        vote_summary(vote_as(app.clone(), "/question/1/vote", 2, VoteDirection::Up).await).await;
+    assert_eq!(summary.score, 1);
+
+    let (status, body) = send_json(
+        app.clone(),
+        http::Method::GET,
+        "/question_comments/1",
+        None,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let thread: QuestionResult = serde_json::from_slice(&body).unwrap();
+    assert_eq!(thread.score, 1);
+    assert_eq!(thread.answers.len(), 1);
+
+    let edit = UpdateQuestion {
+        id: 1.into(),
+        title: "Borrowing in closures".into(),
+        content: "Why does the closure borrow my vector? It only reads it.".into(),
+        tags: Some(vec!["rust".into()]),
+    };
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(http::Method::PUT)
+                .uri("/question")
+                .header(http::header::AUTHORIZATION, bearer(1))
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .header(http::header::IF_MATCH, "\"1\"")
+                .body(Body::from(serde_json::to_string(&edit).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(etag_of(&response).starts_with("\"2-"));
+
+    let (status, body) = send_json(
+        app.clone(),
+        http::Method::GET,
+        "/search?q=closure",
+        None,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let hits: Page<SearchResult> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(hits.total, 2);
+
+    let status = status_of(
+        app.clone(),
+        http::Method::DELETE,
+        "/question?question_id=1",
+        Some(bearer(1)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let status = status_of(app.clone(), http::Method::GET, "/question/1", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let status = status_of(
+        app.clone(),
+        http::Method::POST,
+        "/question/1/undelete",
+        Some(bearer_as(3, Role::Moderator)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send_json(
+        app,
+        http::Method::GET,
+        "/question/1/answers",
+        None,
+        String::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let answers: Vec<Answer> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(answers.len(), 1);
+}
+
+async fn check_register_and_login(app: axum::Router) {
+    init_test_keys();
+
+    let user = CreateUser {
+        email: "front@example.com".into(),
+        password: "hunter22".into(),
+    };
+    let (status, _) = send_json(
+        app.clone(),
+        http::Method::POST,
+        "/register",
+        None,
+        serde_json::to_string(&user).unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send_json(
+        app.clone(),
+        http::Method::POST,
+        "/register",
+        None,
+        serde_json::to_string(&user).unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let credentials = LoginUser {
+        email: user.email,
+        password: user.password,
+    };
+    let (status, body) = send_json(
+        app,
+        http::Method::POST,
+        "/login",
+        None,
+        serde_json::to_string(&credentials).unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let auth: AuthBody = serde_json::from_slice(&body).unwrap();
+    assert!(!auth.access_token.is_empty());
+}
+
+// Goes through the storage calls the flow above doesn't reach, straight on the store
+async fn check_repository<S: Repository>(mut store: S) {
+    let asker = store
+        .add_user("asker@example.com".into(), "hash".into())
+        .await
+        .unwrap();
+    let helper = store
+        .add_user("helper@example.com".into(), "hash".into())
+        .await
+        .unwrap();
+    let as_user = |user: &User| AuthUser {
+        id: user.id,
+        email: user.email.clone(),
+        role: Role::User,
+    };
+    let (asker_auth, helper_auth) = (as_user(&asker), as_user(&helper));
+
+    let first = store
+        .add_question(
+            "First".into(),
+            "Borrow checker".into(),
+            Some(vec!["js".into()]),
+            asker.id,
+        )
+        .await
+        .unwrap();
+    let second = store
+        .add_question("Second".into(), "Lifetimes".into(), None, asker.id)
+        .await
+        .unwrap();
+
+    // Tags
+    store
+        .merge_tags(
+            "js",
+            MergeTag {
+                into: "rust".into(),
+            },
+            &asker_auth,
+        )
+        .await
+        .unwrap_err();
+    store
+        .add_question(
+            "Third".into(),
+            "Traits".into(),
+            Some(vec!["rust".into()]),
+            asker.id,
+        )
+        .await
+        .unwrap();
+    let merged = store
+        .merge_tags(
+            "js",
+            MergeTag {
+                into: "rust".into(),
+            },
+            &asker_auth,
+        )
+        .await
+        .unwrap();
+    assert_eq!(merged.question_count, 1);
+    assert_eq!(store.get_tag("js").await.unwrap().slug, "rust");
+    let retagged = store.get_question_by_id(first.id).await.unwrap();
+    assert_eq!(retagged.version, first.version + 1);
+    let revisions = store.get_question_revisions(first.id).await.unwrap();
+    let last = revisions.last().unwrap();
+    assert_eq!(last.revision, retagged.version);
+    assert_eq!(last.tags, vec!["rust".to_string()]);
+    assert_eq!(last.editor_id, Some(asker.id.0));
+    let tags = store.get_tags().await.unwrap();
+    assert_eq!(tags.len(), 1);
+    assert_eq!(tags[0].question_count, 2);
+
+    // Listing
+    let params = PageParams {
+        limit: Some(1),
+        ..Default::default()
+    };
+    let filter = QuestionFilter {
+        sort: QuestionSort::Oldest,
+        ..Default::default()
+    };
+    let page = store.get_all_questions(&params, &filter).await.unwrap();
+    assert_eq!((page.total, page.items[0].id), (3, first.id));
+    let params = PageParams {
+        cursor: page.next_cursor,
+        ..params
+    };
+    let page = store.get_all_questions(&params, &filter).await.unwrap();
+    assert_eq!(page.items[0].id, second.id);
+    let filter = QuestionFilter {
+        tags: vec!["rust".into()],
+        has_answers: Some(false),
+        ..Default::default()
+    };
+    let page = store
+        .get_all_questions(&PageParams::default(), &filter)
+        .await
+        .unwrap();
+    assert_eq!(page.total, 2);
+
+    // Revisions
+    store
+        .update_question(
+            UpdateQuestion {
+                id: first.id,
+                title: "First, edited".into(),
+                content: "Borrow checker".into(),
+                tags: None,
+            },
+            None,
+            &asker_auth,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        store.get_question_revisions(first.id).await.unwrap().len(),
+        3
+    );
+    let diff = store.diff_question_revisions(first.id, 2, 3).await.unwrap();
+    assert!(!diff.fields.is_empty());
+    let rolled_back = store
+        .rollback_question(first.id, 2, &asker_auth)
+        .await
+        .unwrap();
+    assert_eq!(rolled_back.title, "First");
+    assert_eq!(rolled_back.tags, Some(vec!["rust".to_string()]));
+    assert_eq!(rolled_back.version, 4);
+
+    // Answers
+    let answer = store
+        .add_answer("Use a reference".into(), first.id.0, helper.id)
+        .await
+        .unwrap();
+    store
+        .update_answer(answer.id, "Use a shared reference".into(), &helper_auth)
+        .await
+        .unwrap();
+    store
+        .update_answer(answer.id, "Nope".into(), &asker_auth)
+        .await
+        .unwrap_err();
+    let rolled_back = store
+        .rollback_answer(answer.id, 1, &helper_auth)
+        .await
+        .unwrap();
+    assert_eq!(rolled_back.content, "Use a reference");
+    assert_eq!(
+        store.get_answer_revisions(answer.id).await.unwrap().len(),
+        3
+    );
+    store.diff_answer_revisions(answer.id, 1, 2).await.unwrap();
+
+    let thread = store
+        .accept_answer(first.id, answer.id, &asker_auth)
+        .await
+        .unwrap();
+    assert_eq!(thread.accepted_answer_id, Some(answer.id.0));
+    let thread = store.unaccept_answer(first.id, &asker_auth).await.unwrap();
+    assert_eq!(thread.accepted_answer_id, None);
+
+    let summary = store
+        .cast_vote(VoteTarget::Answer(answer.id), asker.id, VoteDirection::Down)
+        .await
+        .unwrap();
+    assert_eq!(summary.score, -1);
+    let summary = store
+        .retract_vote(VoteTarget::Answer(answer.id), asker.id)
+        .await
+        .unwrap();
+    assert_eq!((summary.score, summary.vote), (0, None));
+
+    // Comments
+    let comment = store
+        .add_comment(
+            "Which edition?".into(),
+            CommentTarget::Answer(answer.id),
+            asker.id,
+        )
+        .await
+        .unwrap();
+    store
+        .update_comment(comment.id, "Which Rust edition?".into(), &asker_auth)
+        .await
+        .unwrap();
+    let comments = store.get_comments_for_answer(answer.id).await.unwrap();
+    assert_eq!(comments[0].content, "Which Rust edition?");
+    store
+        .add_comment(
+            "Nice one".into(),
+            CommentTarget::Question(second.id),
+            helper.id,
+        )
+        .await
+        .unwrap();
+    let thread = store.get_question_comments(first.id.0).await.unwrap();
+    assert_eq!(thread.answers[0].comments.len(), 1);
+    store.delete_comment(comment.id, &asker_auth).await.unwrap();
+    assert!(store
+        .get_comments_for_answer(answer.id)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        store
+            .get_comments_for_question(second.id)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // Deleting and purging
+    store.delete_answer(answer.id, &helper_auth).await.unwrap();
+    store
+        .delete_question(second.id.0, &asker_auth)
+        .await
+        .unwrap();
+    let counts = store
+        .purge_deleted(chrono::Utc::now() + chrono::Duration::hours(1))
+        .await
+        .unwrap();
+    // The deleted comment sat on the purged answer and went with it
+    assert_eq!(
+        counts,
+        PurgeCounts {
+            questions: 1,
+            answers: 1,
+            comments: 0,
+        }
+    );
+
+    // Users
+    let moderator = store
+        .set_user_role(helper.id, Role::Moderator)
+        .await
+        .unwrap();
+    assert_eq!(moderator.role, Role::Moderator);
+    let stored = store.get_user_by_id(helper.id).await.unwrap().unwrap();
+    assert_eq!(stored.role, Role::Moderator);
+    store.delete_user(asker.id).await.unwrap();
+    assert!(store.get_user_by_id(asker.id).await.unwrap().is_none());
+    assert_eq!(store.get_all_users().await.unwrap().len(), 1);
+    assert!(store
+        .get_user_by_email("asker@example.com")
+        .await
+        .unwrap()
+        .is_none());
+    let orphaned = store.get_question_by_id(first.id).await.unwrap();
+    assert_eq!(orphaned.author_id, None);
+}
+
+#[tokio::test]
+async fn test_memory_store_question_flow() {
+    check_question_flow(MemoryStore::default()).await;
+}
+
+#[tokio::test]
+async fn test_memory_store_register_and_login() {
+    check_register_and_login(app(MemoryStore::default()).await).await;
+}
+
+#[sqlx::test]
+async fn test_postgres_store_repository(db_pool: PgPool) {
+    check_repository(Store::with_pool(db_pool)).await;
+}
+
+#[tokio::test]
+async fn test_memory_store_repository() {
+    check_repository(MemoryStore::default()).await;
+}
+
+#[cfg(feature = "sqlite")]
+async fn sqlite_store() -> SqliteStore {
+    let config = DatabaseConfig {
+        sqlite_url: "sqlite::memory:".into(),
+        ..Default::default()
+    };
+    let pool = new_sqlite_pool(&config).await.unwrap();
+    sqlite::migrate_up(&pool).await.unwrap();
+    SqliteStore::with_pool(pool)
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_sqlite_store_question_flow() {
+    check_question_flow(sqlite_store().await).await;
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_sqlite_store_register_and_login() {
+    check_register_and_login(app(sqlite_store().await).await).await;
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_sqlite_store_repository() {
+    check_repository(sqlite_store().await).await;
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_sqlite_store_search_escapes_markup() {
+    check_search_escapes_markup(sqlite_store().await).await;
+}
+
+#[sqlx::test(migrations = false)]
+async fn test_migrate_up_and_down(db_pool: PgPool) {
+    let status = migration_status(&db_pool).await.unwrap();
+    assert!(!status.is_empty());
+    assert!(status.iter().all(|migration| !migration.applied));
+
+    migrate_up(&db_pool).await.unwrap();
+    let status = migration_status(&db_pool).await.unwrap();
+    assert!(status.iter().all(|migration| migration.applied));
+
+    // Only the latest migration is reverted
+    let latest = status.last().unwrap().version;
+    assert_eq!(migrate_down(&db_pool).await.unwrap(), Some(latest));
+    let status = migration_status(&db_pool).await.unwrap();
+    let pending: Vec<i64> = status
+        .iter()
+        .filter(|migration| !migration.applied)
+        .map(|migration| migration.version)
+        .collect();
+    assert_eq!(pending, vec![latest]);
+
+    migrate_up(&db_pool).await.unwrap();
+    assert!(migration_status(&db_pool)
+        .await
+        .unwrap()
+        .iter()
+        .all(|migration| migration.applied));
+}
+
+#[sqlx::test(migrations = false)]
+async fn test_tags_migration_truncates_long_tags(db_pool: PgPool) {
+    const TAGS_MIGRATION: i64 = 20230807120000;
+
+    // Up to just before tags moved out of the questions.tags array
+    let mut conn = db_pool.acquire().await.unwrap();
+    conn.ensure_migrations_table().await.unwrap();
+    for migration in MIGRATOR.iter().filter(|migration| {
+        migration.version < TAGS_MIGRATION && !migration.migration_type.is_down_migration()
+    }) {
+        conn.apply(migration).await.unwrap();
+    }
+    drop(conn);
+
+    let long = "x".repeat(64);
+    sqlx::query("INSERT INTO questions(title, content, tags) VALUES ('Legacy', 'Old tags', $1)")
+        .bind(vec![
+            format!("{}a", long),
+            format!("{} b", long),
+            "Rust".into(),
+        ])
+        .execute(&db_pool)
+        .await
+        .unwrap();
+
+    migrate_up(&db_pool).await.unwrap();
+
+    let slugs: Vec<String> = sqlx::query_scalar(
+        "SELECT tags.slug FROM question_tags JOIN tags ON tags.id = question_tags.tag_id
+         ORDER BY tags.slug",
+    )
+    .fetch_all(&db_pool)
+    .await
+    .unwrap();
+    // The same cut slugify makes, so the old spellings still find the migrated tag
+    assert_eq!(slugify(&format!("{} b", long)), long);
+    assert_eq!(slugs, vec!["rust".to_string(), long]);
+}
+
+#[sqlx::test]
+async fn test_seed_dev_data_only_into_an_empty_database(db_pool: PgPool) {
+    // Migrations alone leave the database empty
+    let (questions,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM questions")
+        .fetch_one(&db_pool)
+        .await
+        .unwrap();
+    assert_eq!(questions, 0);
+
+    assert!(seed_dev_data(&db_pool).await.unwrap());
+    assert!(!seed_dev_data(&db_pool).await.unwrap());
+
+    let mut store = Store::with_pool(db_pool);
+    let thread = store.get_question_comments(1).await.unwrap();
+    assert_eq!(thread.title, "Question Title");
+    assert_eq!(
+        thread.tags,
+        Some(vec!["tag1".to_string(), "tag2".to_string()])
+    );
+    assert_eq!(thread.comments.len(), 1);
+    assert_eq!(thread.answers.len(), 1);
+    assert_eq!(thread.answers[0].comments.len(), 1);
 }